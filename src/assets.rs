@@ -0,0 +1,1002 @@
+use std::any::{Any, TypeId};
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::mipmap::MipmapGenerator;
+use crate::scene_file::SceneFile;
+use crate::shader::Shader;
+use crate::skeleton::Skeleton;
+use crate::sprite_animation::SpriteSheet;
+use crate::text::Font;
+use crate::texture::{premultiply_alpha, ImageTexture, TextureOptions};
+
+/// Most worker threads an [`AssetServer`] starts, however many cores there are.
+const MAX_WORKERS: usize = 4;
+
+/// Any error an [`Asset`] fails to load with.
+pub type BoxedError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum AssetError {
+    /// The file couldn't be read.
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// The file was read but isn't a valid asset of the type asked for.
+    Load { path: PathBuf, error: BoxedError },
+}
+
+impl AssetError {
+    pub fn path(&self) -> &Path {
+        match self {
+            AssetError::Io { path, .. } | AssetError::Load { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Io { path, error } => {
+                write!(f, "couldn't read asset '{}': {error}", path.display())
+            }
+            AssetError::Load { path, error } => {
+                write!(f, "couldn't load asset '{}': {error}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for AssetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AssetError::Io { error, .. } => Some(error),
+            AssetError::Load { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
+
+/// What an [`Asset`] gets to finish loading with on the render thread.
+pub struct LoadContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    /// Shared by every texture the server loads.
+    pub mipmaps: &'a MipmapGenerator,
+    /// The asset's path relative to the asset root, for labels and messages.
+    pub path: &'a Path,
+}
+
+/// Something [`AssetServer`] can load from a file.
+///
+/// Loading happens in two steps so the slow part stays off the render thread:
+/// [`Asset::decode`] runs on a worker thread with the file's bytes, then
+/// [`Asset::finish`] runs on the render thread to create whatever needs the GPU.
+pub trait Asset: Sized + 'static {
+    /// Options for [`AssetServer::load_with`]. [`AssetServer::load`] uses the default.
+    type Settings: Default + PartialEq + Clone + Send + 'static;
+    /// What [`Asset::decode`] hands over to [`Asset::finish`].
+    type Decoded: Send + 'static;
+
+    fn decode(bytes: Vec<u8>, settings: &Self::Settings) -> Result<Self::Decoded, BoxedError>;
+
+    fn finish(
+        decoded: Self::Decoded,
+        settings: &Self::Settings,
+        context: &LoadContext,
+    ) -> Result<Self, BoxedError>;
+
+    /// Updates an already loaded asset after its file changed on disk. Replaces it
+    /// with a freshly finished one unless overridden. On error the asset must be
+    /// left as it was.
+    fn reload(
+        &mut self,
+        decoded: Self::Decoded,
+        settings: &Self::Settings,
+        context: &LoadContext,
+    ) -> Result<(), BoxedError> {
+        *self = Self::finish(decoded, settings, context)?;
+        Ok(())
+    }
+}
+
+impl Asset for ImageTexture {
+    type Settings = TextureOptions;
+    type Decoded = image::RgbaImage;
+
+    /// Decodes the image, and premultiplies it while still on the worker.
+    fn decode(bytes: Vec<u8>, settings: &TextureOptions) -> Result<image::RgbaImage, BoxedError> {
+        let mut rgba = image::load_from_memory(&bytes)?.to_rgba8();
+        if settings.premultiply_alpha {
+            premultiply_alpha(&mut rgba, settings.srgb);
+        }
+        Ok(rgba)
+    }
+
+    fn finish(
+        rgba: image::RgbaImage,
+        settings: &TextureOptions,
+        context: &LoadContext,
+    ) -> Result<Self, BoxedError> {
+        let options = TextureOptions {
+            premultiply_alpha: false,
+            ..*settings
+        };
+        Ok(ImageTexture::from_rgba(
+            context.device,
+            context.queue,
+            rgba,
+            Some(&context.path.to_string_lossy()),
+            &options,
+            Some(context.mipmaps),
+        ))
+    }
+
+    /// Writes the new pixels into the existing texture when the size is unchanged,
+    /// so bind groups made from it pick them up. Otherwise makes a new texture.
+    fn reload(
+        &mut self,
+        rgba: image::RgbaImage,
+        settings: &TextureOptions,
+        context: &LoadContext,
+    ) -> Result<(), BoxedError> {
+        if self.layers() == 1 && rgba.dimensions() == (self.width(), self.height()) {
+            self.write_rgba(context.device, context.queue, &rgba, Some(context.mipmaps))?;
+        } else {
+            *self = Self::finish(rgba, settings, context)?;
+        }
+        Ok(())
+    }
+}
+
+/// A decoded image kept on the CPU, for generating data from rather than drawing.
+impl Asset for image::RgbaImage {
+    type Settings = ();
+    type Decoded = image::RgbaImage;
+
+    fn decode(bytes: Vec<u8>, _: &()) -> Result<image::RgbaImage, BoxedError> {
+        Ok(image::load_from_memory(&bytes)?.to_rgba8())
+    }
+
+    fn finish(rgba: image::RgbaImage, _: &(), _: &LoadContext) -> Result<Self, BoxedError> {
+        Ok(rgba)
+    }
+}
+
+/// A WGSL module, validated so errors are reported when it loads. Register a
+/// clone of it with [`PipelineRegistry::add_shader`](crate::pipeline::PipelineRegistry::add_shader)
+/// to draw with it, or swap it in for one already registered with
+/// [`PipelineRegistry::replace_shader`](crate::pipeline::PipelineRegistry::replace_shader).
+impl Asset for Shader {
+    type Settings = ();
+    type Decoded = String;
+
+    fn decode(bytes: Vec<u8>, _: &()) -> Result<String, BoxedError> {
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    fn finish(source: String, _: &(), context: &LoadContext) -> Result<Self, BoxedError> {
+        let label = context.path.to_string_lossy();
+        Ok(Shader::from_wgsl(context.device, &label, &source)?)
+    }
+}
+
+/// A UTF-8 text file, such as one of several WGSL files a shader is put together
+/// from.
+impl Asset for String {
+    type Settings = ();
+    type Decoded = String;
+
+    fn decode(bytes: Vec<u8>, _: &()) -> Result<String, BoxedError> {
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    fn finish(text: String, _: &(), _: &LoadContext) -> Result<Self, BoxedError> {
+        Ok(text)
+    }
+}
+
+/// How a TrueType or OpenType font is rasterized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontSettings {
+    /// Pixels from ascent to descent, as in [`Font::from_ttf`].
+    pub size: f32,
+}
+
+impl Default for FontSettings {
+    fn default() -> Self {
+        Self { size: 16.0 }
+    }
+}
+
+impl Asset for Font {
+    type Settings = FontSettings;
+    type Decoded = Vec<u8>;
+
+    fn decode(bytes: Vec<u8>, _: &FontSettings) -> Result<Vec<u8>, BoxedError> {
+        Ok(bytes)
+    }
+
+    fn finish(
+        bytes: Vec<u8>,
+        settings: &FontSettings,
+        context: &LoadContext,
+    ) -> Result<Self, BoxedError> {
+        Ok(Font::from_ttf(
+            context.device,
+            context.queue,
+            bytes,
+            settings.size,
+        )?)
+    }
+}
+
+/// A level or prefab library in the [`SceneFile`] format.
+impl Asset for SceneFile {
+    type Settings = ();
+    type Decoded = SceneFile;
+
+    fn decode(bytes: Vec<u8>, _: &()) -> Result<SceneFile, BoxedError> {
+        Ok(SceneFile::parse(&String::from_utf8(bytes)?)?)
+    }
+
+    fn finish(file: SceneFile, _: &(), _: &LoadContext) -> Result<Self, BoxedError> {
+        Ok(file)
+    }
+}
+
+/// Frames and clips exported from Aseprite, as read by
+/// [`SpriteSheet::from_aseprite_json`]. The sheet's image is loaded separately.
+impl Asset for SpriteSheet {
+    type Settings = ();
+    type Decoded = SpriteSheet;
+
+    fn decode(bytes: Vec<u8>, _: &()) -> Result<SpriteSheet, BoxedError> {
+        Ok(SpriteSheet::from_aseprite_json(&String::from_utf8(bytes)?)?)
+    }
+
+    fn finish(sheet: SpriteSheet, _: &(), _: &LoadContext) -> Result<Self, BoxedError> {
+        Ok(sheet)
+    }
+}
+
+/// How a Spine skeleton is brought into world units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkeletonSettings {
+    /// World units per skeleton unit, as in [`Skeleton::from_spine_json`].
+    pub scale: f32,
+}
+
+impl Default for SkeletonSettings {
+    fn default() -> Self {
+        Self { scale: 1.0 }
+    }
+}
+
+/// A skeleton exported as JSON from Spine, as read by [`Skeleton::from_spine_json`].
+impl Asset for Skeleton {
+    type Settings = SkeletonSettings;
+    type Decoded = Skeleton;
+
+    fn decode(bytes: Vec<u8>, settings: &SkeletonSettings) -> Result<Skeleton, BoxedError> {
+        Ok(Skeleton::from_spine_json(
+            &String::from_utf8(bytes)?,
+            settings.scale,
+        )?)
+    }
+
+    fn finish(
+        skeleton: Skeleton,
+        _: &SkeletonSettings,
+        _: &LoadContext,
+    ) -> Result<Self, BoxedError> {
+        Ok(skeleton)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioFormat {
+    Wav,
+    Ogg,
+    Mp3,
+    Flac,
+}
+
+impl AudioFormat {
+    /// Recognises a format from the first bytes of a file.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(Self::Wav),
+            [b'O', b'g', b'g', b'S', ..] => Some(Self::Ogg),
+            [b'f', b'L', b'a', b'C', ..] => Some(Self::Flac),
+            // An ID3 tag, or an MPEG frame sync.
+            [b'I', b'D', b'3', ..] => Some(Self::Mp3),
+            [0xff, second, ..] if second & 0xe0 == 0xe0 => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+}
+
+/// An encoded sound file, left compressed for an audio backend to decode as it
+/// plays.
+#[derive(Clone, Debug)]
+pub struct AudioClip {
+    pub format: AudioFormat,
+    pub bytes: Vec<u8>,
+}
+
+impl Asset for AudioClip {
+    type Settings = ();
+    type Decoded = AudioClip;
+
+    fn decode(bytes: Vec<u8>, _: &()) -> Result<AudioClip, BoxedError> {
+        let format = AudioFormat::detect(&bytes).ok_or("not a WAV, Ogg, MP3 or FLAC file")?;
+        Ok(Self { format, bytes })
+    }
+
+    fn finish(clip: AudioClip, _: &(), _: &LoadContext) -> Result<Self, BoxedError> {
+        Ok(clip)
+    }
+}
+
+/// Where a [`Handle`]'s asset is up to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    /// See [`Handle::error`] for why.
+    Failed,
+}
+
+enum Status<T> {
+    Loading,
+    Loaded(T),
+    Failed(Rc<AssetError>),
+}
+
+struct Slot<T> {
+    path: PathBuf,
+    status: RefCell<Status<T>>,
+    version: Cell<u64>,
+}
+
+/// A shared reference to an asset. The asset is freed when the last handle to it
+/// is dropped.
+///
+/// Handles are given out straight away and the asset arrives once it has loaded,
+/// so check [`Handle::state`] or use [`Handle::get`] before relying on it. Assets
+/// are borrowed through the handle like a `RefCell`, since fonts and the like
+/// change as they are used.
+pub struct Handle<T> {
+    slot: Rc<Slot<T>>,
+}
+
+impl<T> Handle<T> {
+    /// The path the asset was loaded from, relative to the asset root.
+    pub fn path(&self) -> &Path {
+        &self.slot.path
+    }
+
+    pub fn state(&self) -> LoadState {
+        match *self.slot.status.borrow() {
+            Status::Loading => LoadState::Loading,
+            Status::Loaded(_) => LoadState::Loaded,
+            Status::Failed(_) => LoadState::Failed,
+        }
+    }
+
+    /// How many times the asset has been reloaded since it first loaded. Compare it
+    /// with the last version seen to tell when bind groups and the like made from
+    /// the asset need remaking.
+    pub fn version(&self) -> u64 {
+        self.slot.version.get()
+    }
+
+    /// The asset, if it has loaded. Panics if it is mutably borrowed.
+    pub fn get(&self) -> Option<Ref<'_, T>> {
+        Ref::filter_map(self.slot.status.borrow(), |status| match status {
+            Status::Loaded(asset) => Some(asset),
+            _ => None,
+        })
+        .ok()
+    }
+
+    /// The asset, if it has loaded. Panics if it is already borrowed.
+    pub fn get_mut(&self) -> Option<RefMut<'_, T>> {
+        RefMut::filter_map(self.slot.status.borrow_mut(), |status| match status {
+            Status::Loaded(asset) => Some(asset),
+            _ => None,
+        })
+        .ok()
+    }
+
+    /// Why the asset failed to load, if it did.
+    pub fn error(&self) -> Option<Ref<'_, AssetError>> {
+        Ref::filter_map(self.slot.status.borrow(), |status| match status {
+            Status::Failed(error) => Some(&**error),
+            _ => None,
+        })
+        .ok()
+    }
+
+    /// Stands `asset` in for one that failed or hasn't loaded yet, such as a
+    /// placeholder, or replaces the loaded one. If the file loads later it is
+    /// reloaded over `asset` as usual. Panics if the asset is borrowed.
+    pub fn set(&self, asset: T) {
+        let mut status = self.slot.status.borrow_mut();
+        if let Status::Loaded(_) = *status {
+            self.slot.version.set(self.slot.version.get() + 1);
+        }
+        *status = Status::Loaded(asset);
+    }
+
+    /// Like [`Handle::get`], for assets that must be there. Panics if the asset
+    /// hasn't loaded.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.get().unwrap_or_else(|| self.not_loaded())
+    }
+
+    /// Like [`Handle::get_mut`], for assets that must be there. Panics if the
+    /// asset hasn't loaded.
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.get_mut().unwrap_or_else(|| self.not_loaded())
+    }
+
+    fn not_loaded(&self) -> ! {
+        match &*self.slot.status.borrow() {
+            Status::Failed(error) => panic!("{error}"),
+            _ => panic!("asset '{}' hasn't loaded yet", self.path().display()),
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+/// Handles are equal when they share an asset.
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.slot, &other.slot)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("path", &self.slot.path)
+            .field("state", &self.state())
+            .finish()
+    }
+}
+
+/// An asset in the server's table, with the settings it was loaded with. The
+/// server only holds weak references so it never keeps an asset alive by itself.
+struct Loaded<T: Asset> {
+    settings: T::Settings,
+    slot: Weak<Slot<T>>,
+}
+
+trait Entry {
+    fn is_alive(&self) -> bool;
+    fn as_any(&self) -> &dyn Any;
+    /// A pending load to read the asset again, and the job that decodes it.
+    fn reload(&self, id: u64, root: &Path) -> Option<(Box<dyn Pending>, Job)>;
+}
+
+impl<T: Asset> Entry for Loaded<T> {
+    fn is_alive(&self) -> bool {
+        self.slot.strong_count() > 0
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn reload(&self, id: u64, root: &Path) -> Option<(Box<dyn Pending>, Job)> {
+        let slot = self.slot.upgrade()?;
+        let job = decode_job::<T>(id, root, &slot.path, self.settings.clone());
+        let pending = Box::new(Loaded::<T> {
+            settings: self.settings.clone(),
+            slot: self.slot.clone(),
+        });
+        Some((pending, job))
+    }
+}
+
+/// What a worker sends back: the id of the load and the decoded asset.
+type Decoded = (u64, Result<Box<dyn Any + Send>, AssetError>);
+type Job = Box<dyn FnOnce() -> Decoded + Send>;
+
+/// A job that reads `root/path` and decodes it as a `T`.
+fn decode_job<T: Asset>(id: u64, root: &Path, path: &Path, settings: T::Settings) -> Job {
+    let file = root.join(path);
+    let path = path.to_path_buf();
+    Box::new(move || {
+        let decoded = std::fs::read(&file)
+            .map_err(|error| AssetError::Io {
+                path: path.clone(),
+                error,
+            })
+            .and_then(|bytes| {
+                T::decode(bytes, &settings).map_err(|error| AssetError::Load { path, error })
+            })
+            .map(|decoded| Box::new(decoded) as Box<dyn Any + Send>);
+        (id, decoded)
+    })
+}
+
+/// A load waiting on a worker, ready to finish the asset with what it decodes.
+trait Pending {
+    /// Returns how the load went, or `None` if nothing wanted it any more. An
+    /// asset that is already loaded is reloaded in place, and kept as it was if
+    /// that fails.
+    fn finish(
+        self: Box<Self>,
+        decoded: Result<Box<dyn Any + Send>, AssetError>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &MipmapGenerator,
+    ) -> Option<Result<(), Rc<AssetError>>>;
+}
+
+impl<T: Asset> Pending for Loaded<T> {
+    fn finish(
+        self: Box<Self>,
+        decoded: Result<Box<dyn Any + Send>, AssetError>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &MipmapGenerator,
+    ) -> Option<Result<(), Rc<AssetError>>> {
+        let slot = self.slot.upgrade()?;
+        let context = LoadContext {
+            device,
+            queue,
+            mipmaps,
+            path: &slot.path,
+        };
+        let decoded = decoded.map(|decoded| {
+            *decoded
+                .downcast::<T::Decoded>()
+                .expect("workers decode to the asset's own type")
+        });
+        let load_error = |error| AssetError::Load {
+            path: slot.path.clone(),
+            error,
+        };
+
+        let mut status = slot.status.borrow_mut();
+        if let Status::Loaded(asset) = &mut *status {
+            let reloaded = decoded.and_then(|decoded| {
+                asset
+                    .reload(decoded, &self.settings, &context)
+                    .map_err(load_error)
+            });
+            return Some(match reloaded {
+                Ok(()) => {
+                    slot.version.set(slot.version.get() + 1);
+                    Ok(())
+                }
+                Err(error) => Err(Rc::new(error)),
+            });
+        }
+        let reloading = matches!(*status, Status::Failed(_));
+        let finished = decoded
+            .and_then(|decoded| T::finish(decoded, &self.settings, &context).map_err(load_error));
+        Some(match finished {
+            Ok(asset) => {
+                *status = Status::Loaded(asset);
+                if reloading {
+                    slot.version.set(slot.version.get() + 1);
+                }
+                Ok(())
+            }
+            Err(error) => {
+                let error = Rc::new(error);
+                *status = Status::Failed(error.clone());
+                Err(error)
+            }
+        })
+    }
+}
+
+/// Threads that read and decode files in the background.
+struct Workers {
+    jobs: Option<Sender<Job>>,
+    decoded: Receiver<Decoded>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+    fn new(count: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (decoded_sender, decoded) = mpsc::channel();
+        let threads = (0..count)
+            .map(|i| {
+                let jobs = job_receiver.clone();
+                let decoded = decoded_sender.clone();
+                std::thread::Builder::new()
+                    .name(format!("asset worker {i}"))
+                    .spawn(move || loop {
+                        // The lock is only held while waiting, so the others can
+                        // take the next job as soon as this one starts.
+                        let job = jobs.lock().unwrap().recv();
+                        let Ok(job) = job else { break };
+                        if decoded.send(job()).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("failed to start an asset worker")
+            })
+            .collect();
+        Self {
+            jobs: Some(jobs),
+            decoded,
+            threads,
+        }
+    }
+
+    fn send(&self, job: Job) {
+        if let Some(jobs) = &self.jobs {
+            // Only fails if every worker has died, which can't happen while we
+            // hold the results channel.
+            let _ = jobs.send(job);
+        }
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        // Closing the job channel lets each worker finish what it's on and stop.
+        self.jobs = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// How far through its loads an [`AssetServer`] is, for a loading screen.
+///
+/// Counts start again from zero with the first load after everything has
+/// finished, so each batch of loads gets its own progress bar.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub total: usize,
+    pub loaded: usize,
+    pub failed: usize,
+}
+
+impl LoadProgress {
+    pub fn pending(&self) -> usize {
+        self.total - self.loaded - self.failed
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending() == 0
+    }
+
+    /// How much of the batch has finished, loaded or failed, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed) as f32 / self.total as f32
+        }
+    }
+}
+
+/// Something that happened to a loaded asset because its file changed. See
+/// [`AssetServer::watch`].
+#[derive(Debug)]
+pub enum AssetEvent {
+    /// An asset loaded from this path was read again and updated in place.
+    Reloaded(PathBuf),
+    /// The changed file couldn't be loaded. Assets that had loaded before are
+    /// kept as they were.
+    ReloadFailed(Rc<AssetError>),
+}
+
+/// Modification times of the files an [`AssetServer`] has loaded, polled for
+/// changes.
+struct Watch {
+    interval: Duration,
+    last_poll: Instant,
+    modified: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+/// A load waiting on a worker. Reloads don't count towards [`LoadProgress`].
+struct PendingLoad {
+    load: Box<dyn Pending>,
+    path: PathBuf,
+    reload: bool,
+}
+
+/// Loads assets from files under a root directory and hands out [`Handle`]s to
+/// them.
+///
+/// Files are read and decoded on a pool of worker threads. Call
+/// [`AssetServer::update`] once a frame on the render thread to finish the
+/// loads that are ready, which is where textures and the like are uploaded to the
+/// GPU.
+///
+/// Loading a path that is already loaded or loading as the same type with the
+/// same settings gives another handle to the same asset rather than reading the
+/// file again. Once every handle to an asset is dropped the asset is freed and
+/// the next load reads the file afresh.
+///
+/// With [`AssetServer::watch`] on, files that change on disk are loaded again
+/// and the assets updated in place, so existing handles see the new versions.
+pub struct AssetServer {
+    root: PathBuf,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    mipmaps: MipmapGenerator,
+    entries: HashMap<(TypeId, PathBuf), Vec<Box<dyn Entry>>>,
+    pending: HashMap<u64, PendingLoad>,
+    next_id: u64,
+    progress: LoadProgress,
+    watch: Option<Watch>,
+    events: Vec<AssetEvent>,
+    workers: Workers,
+}
+
+impl AssetServer {
+    pub fn new(root: impl Into<PathBuf>, device: wgpu::Device, queue: wgpu::Queue) -> Self {
+        let workers = std::thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .min(MAX_WORKERS);
+        Self {
+            root: root.into(),
+            mipmaps: MipmapGenerator::new(&device),
+            device,
+            queue,
+            entries: HashMap::new(),
+            pending: HashMap::new(),
+            next_id: 0,
+            progress: LoadProgress::default(),
+            watch: None,
+            events: Vec::new(),
+            workers: Workers::new(workers),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The generator loaded textures get their mipmaps from, for sharing with
+    /// textures made by hand.
+    pub fn mipmaps(&self) -> &MipmapGenerator {
+        &self.mipmaps
+    }
+
+    /// Checks the files of every loaded asset for changes each `interval`, from
+    /// [`AssetServer::update`], and reloads the ones that changed. Polling
+    /// modification times is cheap enough at a few times a second and needs no
+    /// help from the platform.
+    pub fn watch(&mut self, interval: Duration) {
+        let mut modified = HashMap::new();
+        for (_, path) in self.entries.keys() {
+            modified.insert(path.clone(), modified_time(&self.root.join(path)));
+        }
+        self.watch = Some(Watch {
+            interval,
+            last_poll: Instant::now(),
+            modified,
+        });
+    }
+
+    pub fn load<T: Asset>(&mut self, path: impl AsRef<Path>) -> Handle<T> {
+        self.load_with(path, T::Settings::default())
+    }
+
+    /// Starts loading `path` on a worker, or finds it already loaded or loading.
+    /// An asset that failed to load is tried again.
+    pub fn load_with<T: Asset>(
+        &mut self,
+        path: impl AsRef<Path>,
+        settings: T::Settings,
+    ) -> Handle<T> {
+        let path = path.as_ref();
+        let key = (TypeId::of::<T>(), path.to_path_buf());
+        let existing = self
+            .entries
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .find_map(|entry| {
+                let loaded = entry.as_any().downcast_ref::<Loaded<T>>()?;
+                (loaded.settings == settings).then(|| loaded.slot.upgrade())?
+            });
+        if let Some(slot) =
+            existing.filter(|slot| !matches!(*slot.status.borrow(), Status::Failed(_)))
+        {
+            return Handle { slot };
+        }
+
+        let slot = Rc::new(Slot {
+            path: path.to_path_buf(),
+            status: RefCell::new(Status::Loading),
+            version: Cell::new(0),
+        });
+        let entries = self.entries.entry(key).or_default();
+        entries.retain(|entry| {
+            entry.is_alive()
+                && entry
+                    .as_any()
+                    .downcast_ref::<Loaded<T>>()
+                    .is_none_or(|loaded| loaded.settings != settings)
+        });
+        entries.push(Box::new(Loaded::<T> {
+            settings: settings.clone(),
+            slot: Rc::downgrade(&slot),
+        }));
+
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.progress.is_done() {
+            self.progress = LoadProgress::default();
+        }
+        self.progress.total += 1;
+        self.pending.insert(
+            id,
+            PendingLoad {
+                load: Box::new(Loaded::<T> {
+                    settings: settings.clone(),
+                    slot: Rc::downgrade(&slot),
+                }),
+                path: path.to_path_buf(),
+                reload: false,
+            },
+        );
+        if let Some(watch) = &mut self.watch {
+            // Recorded before the worker reads the file, so a change made while it
+            // loads is still picked up.
+            watch
+                .modified
+                .entry(path.to_path_buf())
+                .or_insert_with(|| modified_time(&self.root.join(path)));
+        }
+        self.workers
+            .send(decode_job::<T>(id, &self.root, path, settings));
+        Handle { slot }
+    }
+
+    /// Finishes every load the workers are done with, and when watching, starts
+    /// reloading any files that changed. Call once a frame.
+    ///
+    /// Returns what happened to reloaded assets since the last call.
+    pub fn update(&mut self) -> Vec<AssetEvent> {
+        self.poll();
+        while let Ok(decoded) = self.workers.decoded.try_recv() {
+            self.finish(decoded);
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    fn poll(&mut self) {
+        let Some(watch) = &mut self.watch else {
+            return;
+        };
+        if watch.last_poll.elapsed() < watch.interval {
+            return;
+        }
+        watch.last_poll = Instant::now();
+
+        let entries = &self.entries;
+        watch.modified.retain(|path, _| {
+            entries
+                .iter()
+                .any(|((_, key), entries)| key == path && entries.iter().any(|e| e.is_alive()))
+        });
+        let mut changed = Vec::new();
+        for (path, modified) in &mut watch.modified {
+            let now = modified_time(&self.root.join(path));
+            // A file that is briefly missing while an editor saves it isn't a change.
+            if now.is_some() && now != *modified {
+                changed.push(path.clone());
+            }
+            *modified = now;
+        }
+
+        for path in changed {
+            log::info!("reloading '{}'", path.display());
+            let reloads: Vec<_> = self
+                .entries
+                .iter()
+                .filter(|((_, key), _)| *key == path)
+                .flat_map(|(_, entries)| entries)
+                .filter_map(|entry| {
+                    let id = self.next_id;
+                    let reload = entry.reload(id, &self.root)?;
+                    self.next_id += 1;
+                    Some((id, reload))
+                })
+                .collect();
+            for (id, (load, job)) in reloads {
+                self.pending.insert(
+                    id,
+                    PendingLoad {
+                        load,
+                        path: path.clone(),
+                        reload: true,
+                    },
+                );
+                self.workers.send(job);
+            }
+        }
+    }
+
+    /// Blocks until every load started so far has finished, for assets that are
+    /// needed before anything can be drawn.
+    pub fn wait(&mut self) {
+        while !self.pending.is_empty() {
+            match self.workers.decoded.recv() {
+                Ok(decoded) => self.finish(decoded),
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn finish(&mut self, (id, decoded): Decoded) {
+        let Some(pending) = self.pending.remove(&id) else {
+            return;
+        };
+        let finished = pending
+            .load
+            .finish(decoded, &self.device, &self.queue, &self.mipmaps);
+        if pending.reload {
+            match finished {
+                Some(Ok(())) => self.events.push(AssetEvent::Reloaded(pending.path)),
+                Some(Err(error)) => self.events.push(AssetEvent::ReloadFailed(error)),
+                None => {}
+            }
+            return;
+        }
+        match finished {
+            Some(Ok(())) => self.progress.loaded += 1,
+            Some(Err(_)) => self.progress.failed += 1,
+            // Nobody is waiting for it, so it no longer counts.
+            None => self.progress.total -= 1,
+        }
+    }
+
+    /// Progress through the current batch of loads.
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    /// Number of assets alive, counting each once however many handles it has.
+    pub fn len(&self) -> usize {
+        self.entries
+            .values()
+            .flatten()
+            .filter(|entry| entry.is_alive())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn modified_time(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
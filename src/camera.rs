@@ -11,6 +11,12 @@ pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
 }
 
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CameraUniform {
     pub fn new() -> Self {
         use cgmath::SquareMatrix;
@@ -19,12 +25,21 @@ impl CameraUniform {
         }
     }
 
+    /// A uniform holding an already combined view-projection matrix, for
+    /// projections other than [`Camera`]'s perspective one.
+    pub fn from_view_proj(view_proj: cgmath::Matrix4<f32>) -> Self {
+        Self {
+            view_proj: view_proj.into(),
+        }
+    }
+
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = camera.projection_matrix().into();
         println!("{:?}", self.view_proj);
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct Camera {
     pub eye: cgmath::Point3<f32>,
     pub target: cgmath::Point3<f32>,
@@ -0,0 +1,275 @@
+use crate::vertex::ColoredVertex;
+
+/// Segments used to approximate a circle.
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[cfg(feature = "debug_draw")]
+static FRAME: std::sync::Mutex<DebugFrame> = std::sync::Mutex::new(DebugFrame::new());
+
+#[cfg_attr(not(feature = "debug_draw"), allow(dead_code))]
+struct DebugText {
+    position: [f32; 2],
+    text: String,
+    color: [f32; 4],
+}
+
+/// Everything drawn so far this frame.
+#[cfg_attr(not(feature = "debug_draw"), allow(dead_code))]
+struct DebugFrame {
+    /// Pairs of vertices, one pair per line.
+    lines: Vec<ColoredVertex>,
+    texts: Vec<DebugText>,
+}
+
+impl DebugFrame {
+    #[cfg_attr(not(feature = "debug_draw"), allow(dead_code))]
+    const fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            texts: Vec::new(),
+        }
+    }
+
+    fn line(&mut self, [x0, y0]: [f32; 2], [x1, y1]: [f32; 2], color: [f32; 4]) {
+        self.lines.extend([
+            ColoredVertex::new([x0, y0, 0.0], color),
+            ColoredVertex::new([x1, y1, 0.0], color),
+        ]);
+    }
+
+    fn polygon(&mut self, points: &[[f32; 2]], color: [f32; 4]) {
+        let next = points.iter().cycle().skip(1);
+        for (&a, &b) in points.iter().zip(next) {
+            self.line(a, b, color);
+        }
+    }
+}
+
+// Without the `debug_draw` feature every call below compiles to nothing, so they
+// can be left in shipping code.
+#[cfg(feature = "debug_draw")]
+fn with_frame(draw: impl FnOnce(&mut DebugFrame)) {
+    // A panic while the lock was held can't have left anything inconsistent that
+    // matters for debug output.
+    draw(
+        &mut FRAME
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner),
+    );
+}
+
+#[cfg(not(feature = "debug_draw"))]
+fn with_frame(_draw: impl FnOnce(&mut DebugFrame)) {}
+
+pub fn line(from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+    with_frame(|frame| frame.line(from, to, color));
+}
+
+/// A line with a head at `to`, e.g. for velocities and normals.
+pub fn arrow(from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+    with_frame(|frame| {
+        frame.line(from, to, color);
+        let back = [from[0] - to[0], from[1] - to[1]];
+        for angle in [-0.45f32, 0.45] {
+            let (sin, cos) = angle.sin_cos();
+            let head = [
+                to[0] + (back[0] * cos - back[1] * sin) * 0.25,
+                to[1] + (back[0] * sin + back[1] * cos) * 0.25,
+            ];
+            frame.line(to, head, color);
+        }
+    });
+}
+
+/// The outline of the axis-aligned box between two opposite corners.
+pub fn rect(min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+    with_frame(|frame| frame.polygon(&[min, [max[0], min[1]], max, [min[0], max[1]]], color));
+}
+
+pub fn circle(center: [f32; 2], radius: f32, color: [f32; 4]) {
+    with_frame(|frame| {
+        let points: Vec<[f32; 2]> = (0..CIRCLE_SEGMENTS)
+            .map(|i| {
+                let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                [
+                    center[0] + radius * angle.cos(),
+                    center[1] + radius * angle.sin(),
+                ]
+            })
+            .collect();
+        frame.polygon(&points, color);
+    });
+}
+
+/// The outline through `points`, closed back to the first.
+pub fn polygon(points: &[[f32; 2]], color: [f32; 4]) {
+    with_frame(|frame| frame.polygon(points, color));
+}
+
+/// An X marking a point, reaching `size` from it in each direction.
+pub fn cross(center: [f32; 2], size: f32, color: [f32; 4]) {
+    let [x, y] = center;
+    with_frame(|frame| {
+        frame.line([x - size, y - size], [x + size, y + size], color);
+        frame.line([x - size, y + size], [x + size, y - size], color);
+    });
+}
+
+/// A label with its top left corner at `position`.
+pub fn text(position: [f32; 2], text: &str, color: [f32; 4]) {
+    with_frame(|frame| {
+        frame.texts.push(DebugText {
+            position,
+            text: text.to_string(),
+            color,
+        })
+    });
+}
+
+#[cfg(feature = "debug_draw")]
+pub use renderer::DebugRenderer;
+
+#[cfg(feature = "debug_draw")]
+mod renderer {
+    use super::{DebugFrame, FRAME};
+    use crate::material::{Material, MaterialDescriptor, MaterialParams, COLORED_SHADER};
+    use crate::pipeline::{BlendMode, PipelineError, PipelineRegistry, TargetFormat};
+    use crate::text::{Font, TextLayout, TextOptions, TextSpan};
+    use crate::vertex::{ColoredVertex, Vertex};
+    use std::sync::PoisonError;
+
+    /// Draws the shapes and labels collected by the `debug_draw` functions.
+    ///
+    /// Call them from anywhere while updating a frame, with positions in world
+    /// space. [`DebugRenderer::render`] draws them over everything else and starts
+    /// the next frame empty.
+    pub struct DebugRenderer {
+        /// World units per font pixel for labels.
+        pub text_scale: f32,
+        font: Font,
+        line_material: Material,
+        text_material: Material,
+        line_buffer: wgpu::Buffer,
+    }
+
+    impl DebugRenderer {
+        /// A renderer drawing over targets of `format`, labelling with `font`.
+        pub fn new(
+            device: &wgpu::Device,
+            registry: &mut PipelineRegistry,
+            font: Font,
+            format: wgpu::TextureFormat,
+        ) -> Result<Self, PipelineError> {
+            let target = TargetFormat::color(format);
+            let line_material = Material::new(
+                device,
+                registry,
+                MaterialDescriptor {
+                    blend: BlendMode::Alpha,
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..MaterialDescriptor::new(
+                        COLORED_SHADER,
+                        vec![ColoredVertex::description()],
+                        target,
+                    )
+                },
+                Vec::new(),
+                MaterialParams::default(),
+            )?;
+            let text_material = Material::text(device, registry, &font, target)?;
+            Ok(Self {
+                text_scale: 0.003,
+                font,
+                line_material,
+                text_material,
+                line_buffer: Self::create_line_buffer(device, 1024),
+            })
+        }
+
+        fn create_line_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Debug Line Buffer"),
+                size: (capacity * std::mem::size_of::<ColoredVertex>()) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        }
+
+        /// The materials lines and text are drawn with.
+        pub fn materials(&self) -> [&Material; 2] {
+            [&self.line_material, &self.text_material]
+        }
+
+        /// Draws everything collected since the last call over `view` and clears it
+        /// for the next frame.
+        pub fn render(
+            &mut self,
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            encoder: &mut wgpu::CommandEncoder,
+            view: &wgpu::TextureView,
+            camera_bind_group: &wgpu::BindGroup,
+        ) {
+            let DebugFrame { lines, texts } = std::mem::replace(
+                &mut *FRAME.lock().unwrap_or_else(PoisonError::into_inner),
+                DebugFrame::new(),
+            );
+            if lines.is_empty() && texts.is_empty() {
+                return;
+            }
+
+            let needed = std::mem::size_of_val(lines.as_slice()) as wgpu::BufferAddress;
+            if needed > self.line_buffer.size() {
+                self.line_buffer =
+                    Self::create_line_buffer(device, lines.len().next_power_of_two());
+            }
+            queue.write_buffer(&self.line_buffer, 0, bytemuck::cast_slice(&lines));
+
+            // Every label goes into one mesh, as many as fit in 16 bit indices.
+            let mut labels = TextLayout::default();
+            for text in &texts {
+                let layout = self.font.layout(
+                    &[TextSpan::new(&text.text, text.color)],
+                    &TextOptions {
+                        position: text.position,
+                        scale: self.text_scale,
+                        ..Default::default()
+                    },
+                );
+                let first = labels.vertices.len();
+                if first + layout.vertices.len() > u16::MAX as usize {
+                    break;
+                }
+                labels.vertices.extend(layout.vertices);
+                labels
+                    .indices
+                    .extend(layout.indices.iter().map(|&i| i + first as u16));
+            }
+            self.font.upload(queue);
+            let label_mesh = labels.mesh(device, "Debug Text Mesh");
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug Draw Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            if !lines.is_empty() {
+                self.line_material.bind(&mut render_pass);
+                render_pass.set_vertex_buffer(0, self.line_buffer.slice(..needed));
+                render_pass.draw(0..lines.len() as u32, 0..1);
+            }
+            self.text_material.bind(&mut render_pass);
+            label_mesh.draw(&mut render_pass);
+        }
+    }
+}
@@ -0,0 +1,645 @@
+use std::any::{type_name, Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Most fixed steps run in one frame. Past this the simulation slows down rather
+/// than spending ever longer catching up.
+const MAX_FIXED_STEPS: u32 = 8;
+
+/// A game object: an id that components are attached to.
+///
+/// Ids are reused once an entity is despawned, but with a new generation, so a
+/// stale id never finds the entity that took its place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// Components of one type, packed densely with a sparse lookup from entity index.
+/// Only seen from outside as part of [`Fetch::Guard`].
+pub struct SparseSet<T> {
+    sparse: Vec<Option<usize>>,
+    entities: Vec<Entity>,
+    components: Vec<T>,
+}
+
+impl<T> SparseSet<T> {
+    fn new() -> Self {
+        Self {
+            sparse: Vec::new(),
+            entities: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+
+    fn dense_index(&self, entity: Entity) -> Option<usize> {
+        self.sparse
+            .get(entity.index as usize)
+            .copied()
+            .flatten()
+            .filter(|&i| self.entities[i] == entity)
+    }
+
+    fn insert(&mut self, entity: Entity, component: T) {
+        if let Some(i) = self.dense_index(entity) {
+            self.components[i] = component;
+            return;
+        }
+        let index = entity.index as usize;
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+        self.sparse[index] = Some(self.entities.len());
+        self.entities.push(entity);
+        self.components.push(component);
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let i = self.dense_index(entity)?;
+        self.sparse[entity.index as usize] = None;
+        self.entities.swap_remove(i);
+        let component = self.components.swap_remove(i);
+        // The last component was moved into the gap.
+        if let Some(moved) = self.entities.get(i) {
+            self.sparse[moved.index as usize] = Some(i);
+        }
+        Some(component)
+    }
+
+    fn get(&self, entity: Entity) -> Option<&T> {
+        self.dense_index(entity).map(|i| &self.components[i])
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.dense_index(entity).map(|i| &mut self.components[i])
+    }
+}
+
+/// A [`SparseSet`] of any component type.
+trait Storage {
+    fn contains(&self, entity: Entity) -> bool;
+    fn remove_entity(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> Storage for SparseSet<T> {
+    fn contains(&self, entity: Entity) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+type StorageCell = RefCell<Box<dyn Storage>>;
+
+fn downcast<T: 'static>(storage: &dyn Storage) -> &SparseSet<T> {
+    storage
+        .as_any()
+        .downcast_ref()
+        .expect("storages are keyed by their component type")
+}
+
+fn downcast_mut<T: 'static>(storage: &mut dyn Storage) -> &mut SparseSet<T> {
+    storage
+        .as_any_mut()
+        .downcast_mut()
+        .expect("storages are keyed by their component type")
+}
+
+#[derive(Clone, Copy, Debug)]
+struct EntitySlot {
+    generation: u32,
+    alive: bool,
+}
+
+/// Entities, their components and resources shared between systems.
+///
+/// Any `'static` type can be a component or a resource. Components and resources
+/// are borrowed through `&World`, like a `RefCell`, so systems can read some and
+/// write others at once. Borrowing the same type mutably twice at once panics.
+#[derive(Default)]
+pub struct World {
+    entities: Vec<EntitySlot>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, StorageCell>,
+    resources: HashMap<TypeId, RefCell<Box<dyn Any>>>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A new entity with the components in `bundle`, e.g. `(Position(..), Velocity(..))`.
+    pub fn spawn(&mut self, bundle: impl Bundle) -> Entity {
+        let entity = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.entities[index as usize];
+                slot.alive = true;
+                Entity {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.entities.push(EntitySlot {
+                    generation: 0,
+                    alive: true,
+                });
+                Entity {
+                    index: self.entities.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+        bundle.insert_into(self, entity);
+        entity
+    }
+
+    /// Removes `entity` and all its components. Returns false if it was already gone.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        for storage in self.storages.values_mut() {
+            storage.get_mut().remove_entity(entity);
+        }
+        let slot = &mut self.entities[entity.index as usize];
+        slot.alive = false;
+        slot.generation += 1;
+        self.free.push(entity.index);
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities
+            .get(entity.index as usize)
+            .is_some_and(|slot| slot.alive && slot.generation == entity.generation)
+    }
+
+    /// Attaches `component`, replacing any other of its type. Does nothing if
+    /// `entity` has been despawned.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        let storage = self
+            .storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| RefCell::new(Box::new(SparseSet::<T>::new())));
+        downcast_mut::<T>(storage.get_mut().as_mut()).insert(entity, component);
+    }
+
+    /// Detaches and returns `entity`'s component of type `T`.
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        let storage = self.storages.get_mut(&TypeId::of::<T>())?;
+        downcast_mut::<T>(storage.get_mut().as_mut()).remove(entity)
+    }
+
+    pub fn has<T: 'static>(&self, entity: Entity) -> bool {
+        self.has_type(TypeId::of::<T>(), entity)
+    }
+
+    fn has_type(&self, type_id: TypeId, entity: Entity) -> bool {
+        self.storages
+            .get(&type_id)
+            .is_some_and(|storage| storage.borrow().contains(entity))
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<Ref<'_, T>> {
+        let storage = self.storages.get(&TypeId::of::<T>())?.borrow();
+        Ref::filter_map(storage, |storage| {
+            downcast::<T>(storage.as_ref()).get(entity)
+        })
+        .ok()
+    }
+
+    pub fn get_mut<T: 'static>(&self, entity: Entity) -> Option<RefMut<'_, T>> {
+        let storage = self.storages.get(&TypeId::of::<T>())?.borrow_mut();
+        RefMut::filter_map(storage, |storage| {
+            downcast_mut::<T>(storage.as_mut()).get_mut(entity)
+        })
+        .ok()
+    }
+
+    /// Every entity with the components in `Q`, e.g. `(&mut Position, &Velocity)`.
+    pub fn query<Q: Fetch>(&self) -> Query<'_, Q> {
+        Query {
+            world: self,
+            with: Vec::new(),
+            without: Vec::new(),
+            fetch: PhantomData,
+        }
+    }
+
+    /// Adds `resource`, replacing any other of its type.
+    pub fn insert_resource<R: 'static>(&mut self, resource: R) {
+        self.resources
+            .insert(TypeId::of::<R>(), RefCell::new(Box::new(resource)));
+    }
+
+    pub fn remove_resource<R: 'static>(&mut self) -> Option<R> {
+        let resource = self.resources.remove(&TypeId::of::<R>())?;
+        resource
+            .into_inner()
+            .downcast()
+            .ok()
+            .map(|resource| *resource)
+    }
+
+    pub fn get_resource<R: 'static>(&self) -> Option<Ref<'_, R>> {
+        let resource = self.resources.get(&TypeId::of::<R>())?.borrow();
+        Ref::filter_map(resource, |resource| resource.downcast_ref()).ok()
+    }
+
+    pub fn get_resource_mut<R: 'static>(&self) -> Option<RefMut<'_, R>> {
+        let resource = self.resources.get(&TypeId::of::<R>())?.borrow_mut();
+        RefMut::filter_map(resource, |resource| resource.downcast_mut()).ok()
+    }
+
+    /// Panics if there is no `R`, for resources set up with the world.
+    pub fn resource<R: 'static>(&self) -> Ref<'_, R> {
+        self.get_resource()
+            .unwrap_or_else(|| panic!("no {} resource", type_name::<R>()))
+    }
+
+    /// Panics if there is no `R`, for resources set up with the world.
+    pub fn resource_mut<R: 'static>(&self) -> RefMut<'_, R> {
+        self.get_resource_mut()
+            .unwrap_or_else(|| panic!("no {} resource", type_name::<R>()))
+    }
+}
+
+/// Components spawned together. Implemented for tuples of up to eight components.
+pub trait Bundle {
+    fn insert_into(self, world: &mut World, entity: Entity);
+}
+
+macro_rules! impl_bundle {
+    ($($component:ident),*) => {
+        impl<$($component: 'static),*> Bundle for ($($component,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn insert_into(self, world: &mut World, entity: Entity) {
+                let ($($component,)*) = self;
+                $(world.insert(entity, $component);)*
+            }
+        }
+    };
+}
+
+impl_bundle!();
+impl_bundle!(A);
+impl_bundle!(A, B);
+impl_bundle!(A, B, C);
+impl_bundle!(A, B, C, D);
+impl_bundle!(A, B, C, D, E);
+impl_bundle!(A, B, C, D, E, F);
+impl_bundle!(A, B, C, D, E, F, G);
+impl_bundle!(A, B, C, D, E, F, G, H);
+
+/// What a [`Query`] borrows from each entity: `&T`, `&mut T`, or a tuple of up to
+/// six of those.
+pub trait Fetch {
+    /// The storages borrowed for the whole query.
+    type Guard<'w>;
+    type Item<'a>;
+
+    /// `None` when no entity has ever had one of the components.
+    fn borrow(world: &World) -> Option<Self::Guard<'_>>;
+    /// How many entities the first component's storage holds, which are the
+    /// candidates the query tries.
+    fn len(guard: &Self::Guard<'_>) -> usize;
+    fn entity_at(guard: &Self::Guard<'_>, i: usize) -> Entity;
+    fn contains(guard: &Self::Guard<'_>, entity: Entity) -> bool;
+    /// Only called for entities the guard [`Fetch::contains`].
+    fn fetch<'a>(guard: &'a mut Self::Guard<'_>, entity: Entity) -> Self::Item<'a>;
+}
+
+impl<T: 'static> Fetch for &T {
+    type Guard<'w> = Ref<'w, SparseSet<T>>;
+    type Item<'a> = &'a T;
+
+    fn borrow(world: &World) -> Option<Self::Guard<'_>> {
+        let storage = world.storages.get(&TypeId::of::<T>())?.borrow();
+        Some(Ref::map(storage, |storage| downcast::<T>(storage.as_ref())))
+    }
+
+    fn len(guard: &Self::Guard<'_>) -> usize {
+        guard.entities.len()
+    }
+
+    fn entity_at(guard: &Self::Guard<'_>, i: usize) -> Entity {
+        guard.entities[i]
+    }
+
+    fn contains(guard: &Self::Guard<'_>, entity: Entity) -> bool {
+        guard.dense_index(entity).is_some()
+    }
+
+    fn fetch<'a>(guard: &'a mut Self::Guard<'_>, entity: Entity) -> Self::Item<'a> {
+        guard.get(entity).expect("fetched entities are contained")
+    }
+}
+
+impl<T: 'static> Fetch for &mut T {
+    type Guard<'w> = RefMut<'w, SparseSet<T>>;
+    type Item<'a> = &'a mut T;
+
+    fn borrow(world: &World) -> Option<Self::Guard<'_>> {
+        let storage = world.storages.get(&TypeId::of::<T>())?.borrow_mut();
+        Some(RefMut::map(storage, |storage| {
+            downcast_mut::<T>(storage.as_mut())
+        }))
+    }
+
+    fn len(guard: &Self::Guard<'_>) -> usize {
+        guard.entities.len()
+    }
+
+    fn entity_at(guard: &Self::Guard<'_>, i: usize) -> Entity {
+        guard.entities[i]
+    }
+
+    fn contains(guard: &Self::Guard<'_>, entity: Entity) -> bool {
+        guard.dense_index(entity).is_some()
+    }
+
+    fn fetch<'a>(guard: &'a mut Self::Guard<'_>, entity: Entity) -> Self::Item<'a> {
+        guard
+            .get_mut(entity)
+            .expect("fetched entities are contained")
+    }
+}
+
+macro_rules! impl_fetch {
+    ($first:ident $(, $rest:ident)*) => {
+        impl<$first: Fetch, $($rest: Fetch),*> Fetch for ($first, $($rest,)*) {
+            type Guard<'w> = ($first::Guard<'w>, $($rest::Guard<'w>,)*);
+            type Item<'a> = ($first::Item<'a>, $($rest::Item<'a>,)*);
+
+            fn borrow(world: &World) -> Option<Self::Guard<'_>> {
+                Some(($first::borrow(world)?, $($rest::borrow(world)?,)*))
+            }
+
+            fn len(guard: &Self::Guard<'_>) -> usize {
+                $first::len(&guard.0)
+            }
+
+            fn entity_at(guard: &Self::Guard<'_>, i: usize) -> Entity {
+                $first::entity_at(&guard.0, i)
+            }
+
+            #[allow(non_snake_case)]
+            fn contains(guard: &Self::Guard<'_>, entity: Entity) -> bool {
+                let ($first, $($rest,)*) = guard;
+                $first::contains($first, entity) $(&& $rest::contains($rest, entity))*
+            }
+
+            #[allow(non_snake_case)]
+            fn fetch<'a>(guard: &'a mut Self::Guard<'_>, entity: Entity) -> Self::Item<'a> {
+                let ($first, $($rest,)*) = guard;
+                ($first::fetch($first, entity), $($rest::fetch($rest, entity),)*)
+            }
+        }
+    };
+}
+
+impl_fetch!(A);
+impl_fetch!(A, B);
+impl_fetch!(A, B, C);
+impl_fetch!(A, B, C, D);
+impl_fetch!(A, B, C, D, E);
+impl_fetch!(A, B, C, D, E, F);
+
+/// Entities with all the components in `Q`, narrowed down by filters.
+///
+/// Filtering on a component that is also fetched mutably panics, as that storage
+/// is already borrowed. Fetch it immutably instead.
+pub struct Query<'w, Q> {
+    world: &'w World,
+    with: Vec<TypeId>,
+    without: Vec<TypeId>,
+    fetch: PhantomData<Q>,
+}
+
+impl<Q: Fetch> Query<'_, Q> {
+    /// Only entities that also have a `T`.
+    pub fn with<T: 'static>(mut self) -> Self {
+        self.with.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Only entities that don't have a `T`.
+    pub fn without<T: 'static>(mut self) -> Self {
+        self.without.push(TypeId::of::<T>());
+        self
+    }
+
+    fn passes_filters(&self, entity: Entity) -> bool {
+        self.with
+            .iter()
+            .all(|&type_id| self.world.has_type(type_id, entity))
+            && !self
+                .without
+                .iter()
+                .any(|&type_id| self.world.has_type(type_id, entity))
+    }
+
+    /// Calls `f` with every matching entity and its components. Components can't be
+    /// added or removed meanwhile; queue that up on [`Commands`] instead.
+    pub fn for_each(self, mut f: impl FnMut(Entity, Q::Item<'_>)) {
+        let Some(mut guard) = Q::borrow(self.world) else {
+            return;
+        };
+        for i in 0..Q::len(&guard) {
+            let entity = Q::entity_at(&guard, i);
+            if Q::contains(&guard, entity) && self.passes_filters(entity) {
+                f(entity, Q::fetch(&mut guard, entity));
+            }
+        }
+    }
+
+    /// The matching entities, without borrowing their components any longer.
+    pub fn entities(self) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        let Some(guard) = Q::borrow(self.world) else {
+            return entities;
+        };
+        for i in 0..Q::len(&guard) {
+            let entity = Q::entity_at(&guard, i);
+            if Q::contains(&guard, entity) && self.passes_filters(entity) {
+                entities.push(entity);
+            }
+        }
+        entities
+    }
+}
+
+type Command = Box<dyn FnOnce(&mut World)>;
+
+/// Changes to the world queued up by systems, applied once each system returns.
+#[derive(Default)]
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, bundle: impl Bundle + 'static) {
+        self.add(move |world| {
+            world.spawn(bundle);
+        });
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn(entity);
+        });
+    }
+
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) {
+        self.add(move |world| world.insert(entity, component));
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.remove::<T>(entity);
+        });
+    }
+
+    pub fn insert_resource<R: 'static>(&mut self, resource: R) {
+        self.add(move |world| world.insert_resource(resource));
+    }
+
+    /// Queues any other change.
+    pub fn add(&mut self, command: impl FnOnce(&mut World) + 'static) {
+        self.queue.push(Box::new(command));
+    }
+
+    /// Makes every queued change, in the order they were queued.
+    pub fn apply(&mut self, world: &mut World) {
+        for command in self.queue.drain(..) {
+            command(world);
+        }
+    }
+}
+
+/// When a system runs in each frame. Stages run in the order listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Reacts to the input gathered since the last frame.
+    Input,
+    /// Runs zero or more times a frame, [`Schedule::fixed_step`] seconds apart,
+    /// e.g. for physics that must not depend on the frame rate.
+    FixedUpdate,
+    Update,
+    /// Gathers what the renderer draws this frame.
+    RenderPrep,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [
+        Stage::Input,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::RenderPrep,
+    ];
+}
+
+/// The game clock, kept up to date by [`Schedule::run`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Time {
+    /// Seconds since the last frame, or [`Schedule::fixed_step`] during
+    /// [`Stage::FixedUpdate`].
+    pub delta: f32,
+    /// Seconds since the schedule first ran.
+    pub elapsed: f32,
+}
+
+type System = Box<dyn FnMut(&World, &mut Commands)>;
+
+/// Systems in stages, run once a frame over a [`World`].
+///
+/// A system is any `FnMut(&World, &mut Commands)`. Systems in a stage run in the
+/// order they were added, and the commands each one queues are applied before the
+/// next one runs.
+pub struct Schedule {
+    /// Seconds between fixed updates.
+    pub fixed_step: f32,
+    stages: HashMap<Stage, Vec<System>>,
+    /// Time not yet simulated by fixed updates.
+    accumulator: f32,
+    commands: Commands,
+}
+
+impl Schedule {
+    pub fn new(fixed_step: f32) -> Self {
+        Self {
+            fixed_step,
+            stages: HashMap::new(),
+            accumulator: 0.0,
+            commands: Commands::new(),
+        }
+    }
+
+    pub fn add_system(
+        &mut self,
+        stage: Stage,
+        system: impl FnMut(&World, &mut Commands) + 'static,
+    ) -> &mut Self {
+        self.stages.entry(stage).or_default().push(Box::new(system));
+        self
+    }
+
+    /// Runs every stage for a frame `dt` seconds after the last one.
+    pub fn run(&mut self, world: &mut World, dt: f32) {
+        if world.get_resource::<Time>().is_none() {
+            world.insert_resource(Time::default());
+        }
+        world.resource_mut::<Time>().elapsed += dt;
+
+        self.accumulator += dt;
+        for stage in Stage::ALL {
+            if stage != Stage::FixedUpdate {
+                self.run_stage(world, stage, dt);
+                continue;
+            }
+            let mut steps = 0;
+            while self.accumulator >= self.fixed_step && steps < MAX_FIXED_STEPS {
+                self.run_stage(world, stage, self.fixed_step);
+                self.accumulator -= self.fixed_step;
+                steps += 1;
+            }
+            if steps == MAX_FIXED_STEPS {
+                self.accumulator = self.accumulator.min(self.fixed_step);
+            }
+        }
+    }
+
+    fn run_stage(&mut self, world: &mut World, stage: Stage, delta: f32) {
+        let Some(systems) = self.stages.get_mut(&stage) else {
+            return;
+        };
+        world.resource_mut::<Time>().delta = delta;
+        for system in systems {
+            system(world, &mut self.commands);
+            self.commands.apply(world);
+        }
+    }
+}
@@ -1,64 +1,215 @@
 use std::iter;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::assets::{
+    AssetEvent, AssetServer, FontSettings, Handle, LoadProgress, LoadState, SkeletonSettings,
+};
 use crate::camera::{Camera, CameraState};
-use crate::constants::WINDOW_TITLE;
-use crate::shapes::{Circle, Shape};
-use crate::texture::ImageTexture;
-use crate::vertex::{TexturedVertex, Vertex, INDICES, VERTICES};
-use cgmath::Vector3;
-use wgpu::{
-    util::DeviceExt, BlendComponent, Buffer, Features, Limits, PipelineLayoutDescriptor,
-    RenderPipeline, ShaderModuleDescriptor, ShaderSource,
+use crate::debug_draw;
+#[cfg(feature = "debug_draw")]
+use crate::debug_draw::DebugRenderer;
+use crate::ecs::{Commands, Schedule, Stage, Time, World};
+use crate::input_controller::Input;
+use crate::instancing::{InstanceBuffer, SpriteInstance};
+use crate::lighting::{normal_map_from_height, Light, LightKind, Lighting, LIGHTING_SHADER_FILES};
+use crate::material::{register_builtin_shaders, Material, BUILTIN_SHADER_FILES};
+use crate::mesh::Mesh;
+use crate::metaballs::{Blob, Metaballs, METABALLS_EFFECT, METABALLS_SHADER_FILES};
+use crate::particles::{
+    Curve, EmitterDescriptor, EmitterShape, ParticleStyle, ParticleSystem,
+    ParticleSystemDescriptor, SdfShape, SimulationBackend, PARTICLES_SIMULATE_FILE,
+    PARTICLE_SHADER_FILES,
 };
-use wgpu::{MemoryHints, PipelineCompilationOptions, Trace};
+use crate::pipeline::{BlendMode, PipelineError, PipelineRegistry, ShaderFiles, TargetFormat};
+use crate::post_process::{identity_lut, BuiltinEffect, PostProcessChain, POST_SHADER_FILES};
+use crate::render_queue::{DrawItem, LayerDepth, RenderQueue};
+use crate::scene::{Node, NodeId, Renderable, Scene, Transform};
+use crate::scene_file::{SceneAssets, SceneFile};
+use crate::shader::Shader;
+use crate::shadow::Occluder;
+use crate::shapes::{Circle, Shape, Square};
+use crate::skeleton::{Pose, Skeleton, SkeletonPlayer};
+use crate::sprite_animation::{SpritePlayer, SpriteSheet};
+use crate::text::{Font, ScreenSpace, TextOptions, TextSpan};
+use crate::texture::{DepthTexture, ImageTexture, MultisampleTexture, TextureOptions};
+use crate::tween::{Ease, Sequence, Tween, Tweens};
+use crate::vertex::{TexturedVertex, INDICES, VERTICES};
+use cgmath::{Vector2, Vector3};
+use wgpu::{Features, Limits};
+use wgpu::{MemoryHints, Trace};
 use winit::application::ApplicationHandler;
 use winit::event_loop::ActiveEventLoop;
 use winit::window::WindowId;
 use winit::{
-    event::{ElementState, Event, KeyEvent, WindowEvent},
+    event::{ElementState, KeyEvent, WindowEvent},
     event_loop::EventLoop,
     keyboard::{KeyCode, PhysicalKey},
     window::Window,
 };
 
 pub async fn run() {
+    run_with_settings(RenderSettings::default()).await
+}
+
+pub async fn run_with_settings(settings: RenderSettings) {
     env_logger::init();
     // Unwrap OK. Can only panic if not making event loop in the made thread.
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
-    let mut app = App::default();
+    let mut app = App {
+        settings,
+        ..Default::default()
+    };
     event_loop.run_app(&mut app).unwrap();
 }
 
+/// Environment variable naming the directory the demo loads its textures, fonts
+/// and level from. See [`resource_dir`] for where it looks otherwise.
+const ASSET_ROOT_VAR: &str = "ULTRADIUM_ASSETS";
+/// Environment variable naming the directory holding the builtin shaders' sources,
+/// which are watched so edits to them show up without a restart. See
+/// [`resource_dir`] for where it looks otherwise.
+const SHADER_ROOT_VAR: &str = "ULTRADIUM_SHADERS";
+/// Compiled in for the debug overlay, and for the HUD if its font fails to load.
+const FALLBACK_FONT: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+/// How often asset and shader files are checked for changes.
+const ASSET_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Backdrop layers loaded in the background after startup, back to front.
+const DEMO_BACKDROP: [&str; 5] = [
+    "background1.png",
+    "background2.png",
+    "background3.png",
+    "background4a.png",
+    "background4b.png",
+];
+/// Number of orbiting point lights in the lighting demo.
+const DEMO_RING_LIGHTS: usize = 200;
+/// Number of circles in the instancing demo, all drawn from one mesh.
+const DEMO_INSTANCES: usize = 200;
+/// Number of animated rocks along the bottom of the screen.
+const DEMO_ROCKS: usize = 5;
+/// World units per pixel of the rock sprite sheet.
+const ROCK_SCALE: f32 = 0.0015;
+/// Skeleton units per world unit in the skeletal animation demo.
+const SEAWEED_SCALE: f32 = 0.004;
+/// Number of blobs drifting through each other in the metaball demo.
+const DEMO_BLOBS: usize = 12;
+/// Number of fireflies alive at once in the ECS demo.
+const DEMO_FIREFLIES: usize = 60;
+/// How hard the arrow keys blow the fireflies about, in world units per second.
+const WIND_SPEED: f32 = 0.6;
+/// Post effects and the keys that toggle them.
+const EFFECT_KEYS: [(KeyCode, &str, &str); 7] = [
+    (KeyCode::Digit1, "1", "bloom"),
+    (KeyCode::Digit2, "2", "vignette"),
+    (KeyCode::Digit3, "3", "chromatic_aberration"),
+    (KeyCode::Digit4, "4", "crt"),
+    (KeyCode::Digit5, "5", "underwater"),
+    (KeyCode::Digit6, "6", "color_grading"),
+    (KeyCode::KeyM, "M", METABALLS_EFFECT),
+];
+
+/// Renderer options chosen when the window is created.
+#[derive(Clone, Copy, Debug)]
+pub struct RenderSettings {
+    /// Allocate a depth buffer so sort layers overlap correctly in any draw order.
+    pub depth_buffer: bool,
+    pub layer_depth: LayerDepth,
+    /// MSAA samples per pixel: 1, 2, 4 or 8. Lowered to the nearest count the
+    /// adapter supports for the surface and depth formats.
+    pub sample_count: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            depth_buffer: true,
+            layer_depth: LayerDepth::default(),
+            sample_count: 4,
+        }
+    }
+}
+
 struct State<'a> {
     surface: wgpu::Surface<'a>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
+    settings: RenderSettings,
+    depth_texture: Option<DepthTexture>,
+    sample_count: u32,
+    msaa_texture: Option<MultisampleTexture>,
     // The window must be declared after the surface so
     // it gets dropped after it as the surface contains
     // unsafe references to the window's resources.
-    render_pipeline: RenderPipeline,
-    camera_state: CameraState,
-    diffuse_bind_group: wgpu::BindGroup,
-    // Normal state
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
-    num_indices: u32,
-    // circle state
-    circle_vertex_buffer: Buffer,
-    circle_index_buffer: Buffer,
-    circle_num_indices: u32,
+    tree_material: Arc<Material>,
+    tree_lit_material: Arc<Material>,
+    circle_material: Material,
+    instanced_circle_material: Material,
+    rock_material: Arc<Material>,
+    seaweed_material: Arc<Material>,
+    wall_material: Material,
+    tree_mesh: Mesh,
+    circle_mesh: Mesh,
+    wall_mesh: Mesh,
+    circle_instances: InstanceBuffer<SpriteInstance>,
+    rock_mesh: Mesh,
+    rock_players: Vec<SpritePlayer>,
+    rock_instances: InstanceBuffer<SpriteInstance>,
+    seaweed: Skeleton,
+    seaweed_player: SkeletonPlayer,
+    seaweed_pose: Pose,
+    seaweed_mesh: Mesh,
+    world: World,
+    schedule: Schedule,
+    scene: Scene,
+    scene_orbit: NodeId,
+    scene_planet: NodeId,
+    bubbles: ParticleSystem,
+    metaballs: Metaballs,
+    lighting: Lighting,
+    post_process: PostProcessChain,
+    pipelines: PipelineRegistry,
+    target: TargetFormat,
+    assets: AssetServer,
+    /// Textures used by startup materials, kept so edits to them are reloaded.
+    textures: Vec<TextureBinding>,
+    shaders: AssetServer,
+    /// Sources of the watched shaders, by file under the shader root.
+    shader_files: Vec<(&'static str, Handle<String>)>,
+    /// Why the last edit to a shader couldn't be used, until one works.
+    shader_error: Option<String>,
+    /// Parallax layers that stream in after startup, back to front.
+    backdrop: Vec<BackdropLayer>,
+    backdrop_mesh: Mesh,
+    load_progress: LoadProgress,
+    font: Handle<Font>,
+    hud_material: Material,
+    hud_mesh: Mesh,
+    screen_space: ScreenSpace,
+    #[cfg(feature = "debug_draw")]
+    debug_renderer: DebugRenderer,
+    start_time: Instant,
+    last_frame: Instant,
 
     render_circle: bool,
+    render_instances: bool,
+    render_rocks: bool,
+    render_seaweed: bool,
+    render_scene: bool,
+    render_fireflies: bool,
+    lighting_enabled: bool,
+    debug_enabled: bool,
 }
 
 #[derive(Default)]
 pub struct App<'a> {
     window: Option<Arc<Window>>,
     state: Option<State<'a>>,
+    settings: RenderSettings,
 }
 
 
@@ -68,15 +219,19 @@ impl<'a> ApplicationHandler for App<'a> {
             let window = Arc::new(event_loop.create_window(Window::default_attributes()).unwrap());
             self.window = Some(window.clone());
 
-            let state = pollster::block_on(State::new(window.clone()));
+            let state = pollster::block_on(State::new(window.clone(), self.settings));
             self.state = Some(state);
         }
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         let state = self.state.as_mut().unwrap();
         let window = self.window.as_mut().unwrap();
 
+        if state.input(&event) {
+            return;
+        }
+
         match event {
             WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
@@ -109,7 +264,7 @@ impl<'a> ApplicationHandler for App<'a> {
 }
 
 impl<'a> State<'a> {
-    async fn new(window: Arc<Window>) -> State<'a> {
+    async fn new(window: Arc<Window>, settings: RenderSettings) -> State<'a> {
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -133,7 +288,9 @@ impl<'a> State<'a> {
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 label: None,
-                required_features: Features::empty(),
+                // Needed for sample counts other than 1 and 4.
+                required_features: adapter.features()
+                    & Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
                 required_limits: Limits::default(),
                 memory_hints: MemoryHints::default(),
                 trace: Trace::default(),
@@ -163,156 +320,681 @@ impl<'a> State<'a> {
         };
         surface.configure(&device, &config);
 
+        let mut formats = vec![config.format];
+        if settings.depth_buffer {
+            formats.push(DepthTexture::FORMAT);
+        }
+        let sample_count = supported_sample_count(&adapter, &device, &formats, settings.sample_count);
+        let depth_texture = settings.depth_buffer.then(|| {
+            DepthTexture::new(
+                &device,
+                config.width,
+                config.height,
+                sample_count,
+                "Depth Texture",
+            )
+        });
+        let msaa_texture = (sample_count > 1).then(|| {
+            MultisampleTexture::new(
+                &device,
+                config.format,
+                config.width,
+                config.height,
+                sample_count,
+            )
+        });
+        let target = TargetFormat {
+            color: config.format,
+            depth: depth_texture.as_ref().map(|_| DepthTexture::FORMAT),
+            sample_count,
+        };
+
         let camera = Camera {
             aspect: config.width as f32 / config.height as f32,
             ..Default::default()
         };
         let camera_state = CameraState::new(&device, camera);
 
-        let diffuse_texture_bytes = include_bytes!("../assets/happy-tree.png");
-        let diffuse_texture =
-            ImageTexture::from_bytes(&device, &queue, diffuse_texture_bytes, "My First Texture")
-                .unwrap();
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            });
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
+        // The camera and input live in the ECS world as resources, moved along by
+        // systems, as do the fireflies. The renderer is a resource too, so the
+        // RenderPrep systems can upload what they gather straight to the GPU.
+        let mut world = World::new();
+        world.insert_resource(camera);
+        world.insert_resource(Tweens::<Camera>::new());
+        world.insert_resource(Input::new());
+        world.insert_resource(Wind(Vector2::new(0.0, 0.0)));
+        world.insert_resource(Renderer {
+            device: device.clone(),
+            queue: queue.clone(),
         });
-
-        let shader = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("shader"),
-            source: ShaderSource::Wgsl(include_str!("shaders/textured.wgsl").into()),
+        world.insert_resource(FireflyInstances {
+            sprites: Vec::new(),
+            buffer: InstanceBuffer::new(&device),
         });
+        world.insert_resource(FireflySpawner(DEMO_FIREFLIES as u32));
+        for n in 0..DEMO_FIREFLIES as u32 {
+            world.spawn(firefly(n));
+        }
+        let mut schedule = Schedule::new(1.0 / 60.0);
+        schedule
+            .add_system(Stage::Input, steer_fireflies)
+            .add_system(Stage::FixedUpdate, move_fireflies)
+            .add_system(Stage::Update, age_fireflies)
+            .add_system(Stage::Update, animate_camera)
+            .add_system(Stage::Update, drift_camera)
+            .add_system(Stage::RenderPrep, upload_camera)
+            .add_system(Stage::RenderPrep, upload_fireflies);
 
-        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&texture_bind_group_layout, &camera_state.bind_group_layout],
-            push_constant_ranges: &[],
-        });
+        let mut pipelines = PipelineRegistry::new();
+        register_builtin_shaders(&device, &mut pipelines, &camera_state.bind_group_layout)
+            .unwrap_or_else(|e| panic!("{e}"));
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("My Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[TexturedVertex::description()],
-                compilation_options: PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: BlendComponent::REPLACE,
-                        alpha: BlendComponent::REPLACE,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                // A triangle is forwards if the verticies are counter clock wise.
-                // If they are backwards then the triangle is not rendered (culled)
-                // as per the cull_mode argument being set to Face::Back.
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_circle_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
+        // Everything needed to build the first frame is decoded in parallel on the
+        // asset workers, then waited for here.
+        let asset_root = resource_dir(ASSET_ROOT_VAR, "assets", "assets");
+        log::info!("loading assets from {}", asset_root.display());
+        let mut assets = AssetServer::new(asset_root, device.clone(), queue.clone());
+        let premultiplied = TextureOptions {
+            premultiply_alpha: true,
+            ..TextureOptions::smooth()
+        };
+        let diffuse_texture = assets.load_with::<ImageTexture>("happy-tree.png", premultiplied);
+        let tree_pixels = assets.load::<image::RgbaImage>("happy-tree.png");
+        let rock_texture = assets.load_with::<ImageTexture>("props1.png", premultiplied);
+        let font = assets.load_with::<Font>("DejaVuSansMono.ttf", FontSettings { size: 18.0 });
+        let level = assets.load::<SceneFile>("level.scene");
+        let rock_sheet = assets.load::<SpriteSheet>("props1.json");
+        let seaweed = assets.load_with::<Skeleton>(
+            "seaweed.json",
+            SkeletonSettings {
+                scale: SEAWEED_SCALE,
             },
-            multiview: None,
-            cache: None,
+        );
+        assets.wait();
+        assets.watch(ASSET_POLL_INTERVAL);
+        // Missing or broken files are logged and stood in for, so the demo still
+        // starts. Fixing a file while the demo runs loads it as usual.
+        let placeholder_texture = || {
+            ImageTexture::from_rgba(
+                &device,
+                &queue,
+                placeholder_image(),
+                Some("Placeholder"),
+                &premultiplied,
+                Some(assets.mipmaps()),
+            )
+        };
+        or_fallback(&diffuse_texture, placeholder_texture);
+        or_fallback(&rock_texture, placeholder_texture);
+        or_fallback(&tree_pixels, placeholder_image);
+        or_fallback(&font, || {
+            Font::from_ttf(&device, &queue, FALLBACK_FONT.to_vec(), 18.0)
+                .unwrap_or_else(|e| panic!("{e}"))
         });
+        or_fallback(&level, SceneFile::default);
+        or_fallback(&rock_sheet, SpriteSheet::default);
+        or_fallback(&seaweed, Skeleton::default);
+        // Every shader is compiled in. Their files are loaded too so edits to them
+        // can be swapped into the pipeline registry. The mipmap generator's blit
+        // shader is the one left out: mipmaps already made wouldn't pick edits up.
+        let shader_root = resource_dir(SHADER_ROOT_VAR, "shaders", "src/shaders");
+        log::info!("watching shaders in {}", shader_root.display());
+        let mut shaders = AssetServer::new(shader_root, device.clone(), queue.clone());
+        let mut shader_files: Vec<(&'static str, Handle<String>)> = Vec::new();
+        for file in watched_shaders()
+            .flat_map(|shader| shader.files.iter().copied())
+            .chain([PARTICLES_SIMULATE_FILE])
+        {
+            if shader_files.iter().all(|&(loaded, _)| loaded != file) {
+                shader_files.push((file, shaders.load::<String>(file)));
+            }
+        }
+        shaders.wait();
+        shaders.watch(ASSET_POLL_INTERVAL);
+        // The backdrop isn't needed straight away, so it loads while the demo runs.
+        let backdrop = DEMO_BACKDROP
+            .iter()
+            .map(|path| BackdropLayer {
+                texture: assets.load_with::<ImageTexture>(*path, premultiplied),
+                material: None,
+            })
+            .collect();
+        let backdrop_mesh = Mesh::new(
+            &device,
+            &[
+                TexturedVertex::new([-3.2, 1.6, -1.0], [0.0, 0.0]),
+                TexturedVertex::new([3.2, 1.6, -1.0], [1.0, 0.0]),
+                TexturedVertex::new([-3.2, -1.6, -1.0], [0.0, 1.0]),
+                TexturedVertex::new([3.2, -1.6, -1.0], [1.0, 1.0]),
+            ],
+            &[0, 2, 1, 1, 2, 3],
+            "Backdrop Mesh",
+        );
+        let tree_material = Arc::new(
+            Material::textured(
+                &device,
+                &mut pipelines,
+                &diffuse_texture.borrow(),
+                BlendMode::PremultipliedAlpha,
+                target,
+            )
+            .unwrap_or_else(|e| panic!("{e}")),
+        );
+
+        // Rocks cycling through the frames of an Aseprite export, out of step.
+        let rock_sheet = rock_sheet.borrow();
+        let rock_material = Arc::new(
+            Material::textured_instanced(
+                &device,
+                &mut pipelines,
+                &rock_texture.borrow(),
+                BlendMode::PremultipliedAlpha,
+                target,
+            )
+            .unwrap_or_else(|e| panic!("{e}")),
+        );
+        let rock_mesh = Mesh::new(
+            &device,
+            &[
+                TexturedVertex::new([-0.5, 0.5, 0.0], [0.0, 0.0]),
+                TexturedVertex::new([0.5, 0.5, 0.0], [1.0, 0.0]),
+                TexturedVertex::new([-0.5, -0.5, 0.0], [0.0, 1.0]),
+                TexturedVertex::new([0.5, -0.5, 0.0], [1.0, 1.0]),
+            ],
+            &[0, 2, 1, 1, 2, 3],
+            "Rock Mesh",
+        );
+        let rock_players = (0..DEMO_ROCKS)
+            .filter_map(|i| {
+                let name = if i % 2 == 0 { "cycle" } else { "wobble" };
+                let Some(clip) = rock_sheet.clip(name) else {
+                    log::error!("props1.json has no {name:?} tag");
+                    return None;
+                };
+                let mut player = SpritePlayer::new(clip.clone());
+                player.speed = 0.6 + 0.2 * i as f32;
+                Some(player)
+            })
+            .collect();
+        let rock_instances = InstanceBuffer::new(&device);
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
+        // A frond skinned over three bones, with the tree as its texture.
+        let mut seaweed = seaweed.borrow().clone();
+        if let Some(root) = seaweed.bones.first_mut() {
+            root.setup.translation = Vector2::new(1.1, -1.0);
+        }
+        let sway = seaweed.clip("sway").cloned().unwrap_or_else(|| {
+            log::error!("seaweed.json has no sway animation");
+            Default::default()
         });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(INDICES),
-            usage: wgpu::BufferUsages::INDEX,
+        let seaweed_player = SkeletonPlayer::new(sway);
+        let seaweed_pose = Pose::new(&seaweed);
+        let (vertices, indices) = seaweed_pose.skin(&seaweed);
+        let seaweed_mesh = Mesh::new(&device, &vertices, &indices, "Seaweed Mesh");
+        let seaweed_material = Arc::new(
+            Material::textured(
+                &device,
+                &mut pipelines,
+                &diffuse_texture.borrow(),
+                BlendMode::PremultipliedAlpha,
+                target,
+            )
+            .unwrap_or_else(|e| panic!("{e}")),
+        );
+
+        let mut lighting = Lighting::new(
+            &device,
+            &mut pipelines,
+            &camera_state.bind_group_layout,
+            config.format,
+            config.width,
+            config.height,
+        )
+        .unwrap_or_else(|e| panic!("{e}"));
+        let tree_normals = normal_map_from_height(
+            &tree_pixels.borrow(),
+            8.0,
+        );
+        let tree_normal_texture = ImageTexture::from_rgba(
+            &device,
+            &queue,
+            tree_normals,
+            Some("Tree Normal Map"),
+            &TextureOptions {
+                srgb: false,
+                ..TextureOptions::smooth()
+            },
+            Some(assets.mipmaps()),
+        );
+        let tree_lit_material = Arc::new(
+            Material::textured_lit(
+                &device,
+                &mut pipelines,
+                &diffuse_texture.borrow(),
+                &tree_normal_texture,
+                lighting.normal_target().target_format(),
+            )
+            .unwrap_or_else(|e| panic!("{e}")),
+        );
+        lighting.lights.push(Light {
+            shadow_softness: 0.1,
+            ..Light::spot([-1.0, 1.0], [1.0, -1.0], 0.5, [1.0, 0.85, 0.6], 3.0)
         });
-        let num_indices = INDICES.len() as u32;
+        let wall = Square::new([0.8, 0.3], 0.3);
+        lighting.occluders.push(Occluder::from_shape(&wall));
+        // A ring of small coloured lights, moved around in `update`.
+        for i in 0..DEMO_RING_LIGHTS {
+            let hue = i as f32 / DEMO_RING_LIGHTS as f32;
+            let color = [
+                0.5 + 0.5 * (hue * std::f32::consts::TAU).cos(),
+                0.5 + 0.5 * ((hue + 1.0 / 3.0) * std::f32::consts::TAU).cos(),
+                0.5 + 0.5 * ((hue + 2.0 / 3.0) * std::f32::consts::TAU).cos(),
+            ];
+            lighting.lights.push(Light::point([0.0, 0.0], color, 0.3));
+        }
+
+        let circle_material =
+            Material::colored(&device, &mut pipelines, BlendMode::Alpha, target)
+                .unwrap_or_else(|e| panic!("{e}"));
+        let instanced_circle_material =
+            Material::colored_instanced(&device, &mut pipelines, BlendMode::Alpha, target)
+                .unwrap_or_else(|e| panic!("{e}"));
+        let circle_instances = InstanceBuffer::new(&device);
 
+        let wall_material =
+            Material::colored(&device, &mut pipelines, BlendMode::Replace, target)
+                .unwrap_or_else(|e| panic!("{e}"));
+        let wall_mesh = Mesh::new(
+            &device,
+            &wall.col_vertices([0.15, 0.15, 0.2, 1.0]),
+            &wall.indices(),
+            "Wall Mesh",
+        );
+
+        let bubbles = ParticleSystem::new(
+            &device,
+            &mut pipelines,
+            &camera_state.bind_group_layout,
+            ParticleSystemDescriptor {
+                emitter: EmitterDescriptor {
+                    shape: EmitterShape::Line {
+                        start: [-1.0, 0.0],
+                        end: [1.0, 0.0],
+                    },
+                    position: [0.0, -1.2],
+                    rate: 15.0,
+                    max_particles: 512,
+                    lifetime: [3.0, 5.0],
+                    speed: [0.05, 0.15],
+                    gravity: [0.0, 0.1],
+                    drag: 0.3,
+                    color: Curve::new(vec![
+                        (0.0, [0.7, 0.9, 1.0, 0.0]),
+                        (0.1, [0.7, 0.9, 1.0, 0.8]),
+                        (0.8, [0.7, 0.9, 1.0, 0.8]),
+                        (1.0, [0.7, 0.9, 1.0, 0.0]),
+                    ]),
+                    size: Curve::linear(0.04, 0.1),
+                    ..Default::default()
+                },
+                style: ParticleStyle::Sdf {
+                    shape: SdfShape::Bubble,
+                    softness: 0.1,
+                },
+                blend: BlendMode::Alpha,
+                target,
+                backend: SimulationBackend::detect(&adapter),
+                seed: 1,
+            },
+        )
+        .unwrap_or_else(|e| panic!("{e}"));
+
+        let tree_mesh = Mesh::new(&device, VERTICES, INDICES, "Tree Mesh");
         let circle = Circle::new([0.0, 0.0], 50, 0.5);
-        let circle_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&circle.col_vertices([0.9, 0.5, 0.7, 1.0])),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let circle_indices = circle.indices();
-        let circle_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&circle_indices),
-            usage: wgpu::BufferUsages::INDEX,
+        let circle_mesh = Mesh::new(
+            &device,
+            &circle.col_vertices([0.9, 0.5, 0.7, 0.6]),
+            &circle.indices(),
+            "Circle Mesh",
+        );
+
+        // Every effect starts disabled. The number keys toggle them in `input`.
+        let mut post_process =
+            PostProcessChain::new(&device, config.format, config.width, config.height);
+        // Added first so the other effects apply on top of the bubbles.
+        let mut metaballs = Metaballs::new(
+            &device,
+            &mut pipelines,
+            &camera_state.bind_group_layout,
+            &mut post_process,
+            config.width,
+            config.height,
+        )
+        .unwrap_or_else(|e| panic!("{e}"));
+        metaballs.blobs = (0..DEMO_BLOBS)
+            .map(|i| {
+                let hue = i as f32 / DEMO_BLOBS as f32 * std::f32::consts::TAU;
+                let color = [0.0, 2.1, 4.2].map(|offset| 0.6 + 0.4 * (hue + offset).cos());
+                Blob::new([0.0, 0.0], 0.12 + 0.04 * (i % 3) as f32, color)
+            })
+            .collect();
+        post_process
+            .effect_mut(METABALLS_EFFECT)
+            .expect("metaballs effect was just added")
+            .enabled = false;
+        for effect in [
+            BuiltinEffect::Bloom,
+            BuiltinEffect::Vignette,
+            BuiltinEffect::ChromaticAberration,
+            BuiltinEffect::Crt,
+            BuiltinEffect::Underwater,
+        ] {
+            post_process
+                .add_builtin(&device, &mut pipelines, effect)
+                .unwrap_or_else(|e| panic!("{e}"))
+                .enabled = false;
+        }
+        let lut = ImageTexture::from_rgba(
+            &device,
+            &queue,
+            identity_lut(16),
+            Some("Identity LUT"),
+            &TextureOptions {
+                srgb: false,
+                generate_mipmaps: false,
+                ..TextureOptions::smooth()
+            },
+            None,
+        );
+        post_process
+            .add_color_grading(&device, &mut pipelines, &lut, 1.0)
+            .unwrap_or_else(|e| panic!("{e}"))
+            .enabled = false;
+
+        // Drawn straight onto the swapchain image after post-processing.
+        let hud_material = Material::text(
+            &device,
+            &mut pipelines,
+            &font.borrow(),
+            TargetFormat::color(config.format),
+        )
+        .unwrap_or_else(|e| panic!("{e}"));
+        let screen_space = ScreenSpace::new(
+            &device,
+            &camera_state.bind_group_layout,
+            config.width,
+            config.height,
+        );
+        let hud_mesh = font
+            .borrow_mut()
+            .mesh(&device, &queue, &[], &TextOptions::default());
+
+        // A sun with a planet in orbit, which has a moon and a tree of its own.
+        let shape_material = Arc::new(
+            Material::colored(&device, &mut pipelines, BlendMode::Alpha, target)
+                .unwrap_or_else(|e| panic!("{e}")),
+        );
+        let scene_tree_material = Arc::new(
+            Material::textured(
+                &device,
+                &mut pipelines,
+                &diffuse_texture.borrow(),
+                BlendMode::PremultipliedAlpha,
+                target,
+            )
+            .unwrap_or_else(|e| panic!("{e}")),
+        );
+        let mut scene = Scene::new();
+        let sun = scene.add(
+            Node::new("sun")
+                .with_transform(Transform::new(Vector3::new(-0.9, 0.5, 0.0)))
+                .with_layer(1)
+                .with_renderable(
+                    Renderable::shape(&Circle::new([0.0, 0.0], 24, 0.18), [1.0, 0.8, 0.2, 1.0]),
+                    shape_material.clone(),
+                ),
+        );
+        let orbit = scene
+            .add_child(sun, Node::new("orbit"))
+            .unwrap_or_else(|e| panic!("{e}"));
+        let planet = scene
+            .add_child(
+                orbit,
+                Node::new("planet")
+                    .with_transform(Transform::new(Vector3::new(0.55, 0.0, 0.0)))
+                    .with_layer(1)
+                    .with_renderable(
+                        Renderable::shape(&Circle::new([0.0, 0.0], 20, 0.08), [0.3, 0.6, 1.0, 1.0]),
+                        shape_material.clone(),
+                    ),
+            )
+            .unwrap_or_else(|e| panic!("{e}"));
+        scene
+            .add_child(
+                planet,
+                Node::new("moon")
+                    .with_transform(Transform::new(Vector3::new(0.15, 0.0, 0.0)))
+                    .with_layer(1)
+                    .with_renderable(
+                        Renderable::shape(&Circle::new([0.0, 0.0], 12, 0.03), [0.8, 0.8, 0.8, 1.0]),
+                        shape_material.clone(),
+                    ),
+            )
+            .unwrap_or_else(|e| panic!("{e}"));
+        scene
+            .add_child(
+                planet,
+                Node::new("tree")
+                    .with_transform(Transform::new(Vector3::new(0.0, 0.12, 0.0)))
+                    .with_layer(1)
+                    .with_renderable(
+                        Renderable::sprite([0.12, 0.12]),
+                        scene_tree_material.clone(),
+                    ),
+            )
+            .unwrap_or_else(|e| panic!("{e}"));
+        let label = font.borrow_mut().layout(
+            &[TextSpan::new("scene graph", [1.0, 1.0, 1.0, 1.0])],
+            &TextOptions {
+                position: [-0.2, -0.22],
+                scale: 0.002,
+                ..Default::default()
+            },
+        );
+        font.borrow_mut().upload(&queue);
+        scene
+            .add_child(
+                sun,
+                Node::new("label").with_layer(1).with_renderable(
+                    Renderable::Text(label),
+                    Arc::new(
+                        Material::text(&device, &mut pipelines, &font.borrow(), target)
+                            .unwrap_or_else(|e| panic!("{e}")),
+                    ),
+                ),
+            )
+            .unwrap_or_else(|e| panic!("{e}"));
+        // A level authored as data next to them, whose lanterns join the lighting.
+        let level = level.borrow().resolve().unwrap_or_else(|e| {
+            log::error!("{e}");
+            Vec::new()
         });
-        let circle_num_indices = circle_indices.len() as u32;
+        let mut level_assets = DemoSceneAssets {
+            tree: scene_tree_material,
+            shapes: shape_material,
+        };
+        let mut level_lights = Vec::new();
+        for template in &level {
+            let nodes = match template.instantiate(&mut scene, None, &mut level_assets) {
+                Ok(nodes) => nodes,
+                Err(e) => {
+                    log::error!("{e}");
+                    continue;
+                }
+            };
+            level_lights.extend(
+                nodes
+                    .into_iter()
+                    .filter_map(|(id, node)| Some((id, node.light?))),
+            );
+        }
+        scene.update_world();
+        for (id, mut light) in level_lights {
+            let world = scene.get(id).map(|node| node.world_matrix()).unwrap();
+            light.position = [world.w.x, world.w.y];
+            lighting.lights.push(light);
+        }
+        #[cfg(feature = "debug_draw")]
+        let debug_renderer = DebugRenderer::new(
+            &device,
+            &mut pipelines,
+            Font::from_ttf(&device, &queue, FALLBACK_FONT.to_vec(), 14.0)
+                .unwrap_or_else(|e| panic!("{e}")),
+            config.format,
+        )
+        .unwrap_or_else(|e| panic!("{e}"));
+        let textures = vec![
+            TextureBinding::new(
+                diffuse_texture,
+                vec![
+                    tree_material.clone(),
+                    tree_lit_material.clone(),
+                    seaweed_material.clone(),
+                    level_assets.tree.clone(),
+                ],
+            ),
+            TextureBinding::new(rock_texture, vec![rock_material.clone()]),
+        ];
 
-        Self {
+        world.insert_resource(camera_state);
+
+        let mut state = Self {
             surface,
             device,
             queue,
             config,
             size,
-            render_pipeline,
-            camera_state,
-            diffuse_bind_group,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
-            circle_vertex_buffer,
-            circle_index_buffer,
-            circle_num_indices,
+            settings,
+            depth_texture,
+            sample_count,
+            msaa_texture,
+            tree_material,
+            tree_lit_material,
+            circle_material,
+            instanced_circle_material,
+            rock_material,
+            seaweed_material,
+            wall_material,
+            tree_mesh,
+            circle_mesh,
+            wall_mesh,
+            circle_instances,
+            rock_mesh,
+            rock_players,
+            rock_instances,
+            seaweed,
+            seaweed_player,
+            seaweed_pose,
+            seaweed_mesh,
+            world,
+            schedule,
+            scene,
+            scene_orbit: orbit,
+            scene_planet: planet,
+            bubbles,
+            metaballs,
+            lighting,
+            post_process,
+            start_time: Instant::now(),
+            last_frame: Instant::now(),
             render_circle: false,
+            render_instances: false,
+            render_rocks: false,
+            render_seaweed: false,
+            render_scene: false,
+            render_fireflies: false,
+            lighting_enabled: false,
+            debug_enabled: false,
+            pipelines,
+            target,
+            assets,
+            textures,
+            shaders,
+            shader_files,
+            shader_error: None,
+            backdrop,
+            backdrop_mesh,
+            load_progress: LoadProgress::default(),
+            font,
+            hud_material,
+            hud_mesh,
+            screen_space,
+            #[cfg(feature = "debug_draw")]
+            debug_renderer,
+        };
+        state.update_hud();
+        state
+    }
+
+    /// Rebuilds the key help in the top left corner, highlighting whatever is on.
+    fn update_hud(&mut self) {
+        let mut toggles = vec![
+            ("Space", "circle", self.render_circle),
+            ("I", "instanced circles", self.render_instances),
+            ("R", "animated rocks", self.render_rocks),
+            ("K", "skeletal seaweed", self.render_seaweed),
+            ("N", "scene graph", self.render_scene),
+            ("F", "ECS fireflies", self.render_fireflies),
+            ("L", "lighting", self.lighting_enabled),
+            ("G", "debug draw", self.debug_enabled),
+        ];
+        toggles.extend(EFFECT_KEYS.iter().map(|&(_, key, effect)| {
+            let enabled = self
+                .post_process
+                .effect(effect)
+                .is_some_and(|effect| effect.enabled);
+            (key, effect, enabled)
+        }));
+        let lines: Vec<(String, String, bool)> = toggles
+            .into_iter()
+            .map(|(key, label, enabled)| (format!("{key:>5} "), format!("{label}\n"), enabled))
+            .collect();
+        let key_color = [0.6, 0.6, 0.7, 1.0];
+        let mut spans = Vec::new();
+        for (key, label, enabled) in &lines {
+            let color = if *enabled {
+                [1.0, 0.85, 0.3, 1.0]
+            } else {
+                [0.9, 0.9, 0.9, 0.8]
+            };
+            spans.push(TextSpan::new(key, key_color));
+            spans.push(TextSpan::new(label, color));
         }
+        spans.push(TextSpan::new("    B ", key_color));
+        spans.push(TextSpan::new("burst bubbles", [0.9, 0.9, 0.9, 0.8]));
+        let progress = self.assets.progress();
+        let loading = if !progress.is_done() {
+            format!("\nloading {:.0}%", progress.fraction() * 100.0)
+        } else if progress.failed > 0 {
+            format!("\n{} assets failed to load", progress.failed)
+        } else {
+            String::new()
+        };
+        spans.push(TextSpan::new(&loading, [0.5, 0.8, 1.0, 1.0]));
+        let shader_error = self
+            .shader_error
+            .as_ref()
+            .map_or_else(String::new, |error| format!("\n{error}"));
+        spans.push(TextSpan::new(&shader_error, [1.0, 0.35, 0.3, 1.0]));
+        self.hud_mesh = self.font.borrow_mut().mesh(
+            &self.device,
+            &self.queue,
+            &spans,
+            &TextOptions {
+                position: [10.0, self.config.height as f32 - 10.0],
+                ..Default::default()
+            },
+        );
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -321,12 +1003,44 @@ impl<'a> State<'a> {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+            if self.depth_texture.is_some() {
+                self.depth_texture = Some(DepthTexture::new(
+                    &self.device,
+                    self.config.width,
+                    self.config.height,
+                    self.sample_count,
+                    "Depth Texture",
+                ));
+            }
+            if self.msaa_texture.is_some() {
+                self.msaa_texture = Some(MultisampleTexture::new(
+                    &self.device,
+                    self.config.format,
+                    self.config.width,
+                    self.config.height,
+                    self.sample_count,
+                ));
+            }
+            self.lighting
+                .resize(&self.device, self.config.width, self.config.height);
+            self.post_process
+                .resize(&self.device, self.config.width, self.config.height);
+            self.metaballs.resize(
+                &self.device,
+                &mut self.post_process,
+                self.config.width,
+                self.config.height,
+            );
+            self.screen_space
+                .resize(&self.queue, self.config.width, self.config.height);
+            self.update_hud();
         }
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
         // Returns a bool denoting if the event has been handled.
         // If it has been handled then we don't need to continue processing this event.
+        self.world.resource_mut::<Input>().handle_event(event);
         match event {
             WindowEvent::KeyboardInput {
                 event:
@@ -338,13 +1052,401 @@ impl<'a> State<'a> {
                 ..
             } => {
                 self.render_circle = *state == ElementState::Pressed;
+                self.update_hud();
+                true
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(key),
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                if *key == KeyCode::KeyB {
+                    self.bubbles.emitter.burst(40);
+                    self.punch_camera();
+                    return true;
+                }
+                if *key == KeyCode::KeyI {
+                    self.render_instances = !self.render_instances;
+                    self.update_hud();
+                    return true;
+                }
+                if *key == KeyCode::KeyR {
+                    self.render_rocks = !self.render_rocks;
+                    self.update_hud();
+                    return true;
+                }
+                if *key == KeyCode::KeyK {
+                    self.render_seaweed = !self.render_seaweed;
+                    self.update_hud();
+                    return true;
+                }
+                if *key == KeyCode::KeyN {
+                    self.render_scene = !self.render_scene;
+                    self.update_hud();
+                    return true;
+                }
+                if *key == KeyCode::KeyF {
+                    self.render_fireflies = !self.render_fireflies;
+                    self.update_hud();
+                    return true;
+                }
+                if *key == KeyCode::KeyL {
+                    self.lighting_enabled = !self.lighting_enabled;
+                    self.update_hud();
+                    return true;
+                }
+                if *key == KeyCode::KeyG {
+                    self.debug_enabled = !self.debug_enabled;
+                    self.update_hud();
+                    return true;
+                }
+                let Some(&(_, _, effect)) = EFFECT_KEYS.iter().find(|(code, ..)| code == key)
+                else {
+                    return false;
+                };
+                if let Some(enabled) = self.post_process.toggle(effect) {
+                    log::info!("{effect}: {}", if enabled { "on" } else { "off" });
+                }
+                self.update_hud();
                 true
             }
             _ => false,
         }
     }
 
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        self.update_assets();
+        let time = self.start_time.elapsed().as_secs_f32();
+        // Light 0 is the spot light, then the ring, then the level's lanterns.
+        let ring = self.lighting.lights[1..=DEMO_RING_LIGHTS].iter_mut();
+        for (i, light) in ring.enumerate() {
+            let angle = i as f32 / DEMO_RING_LIGHTS as f32 * std::f32::consts::TAU + time * 0.5;
+            let radius = 1.5 + 0.3 * (time * 2.0 + i as f32 * 0.7).sin();
+            light.position = [angle.cos() * radius, angle.sin() * radius];
+        }
+        // Lissajous paths with different frequencies keep the blobs meeting,
+        // merging and tearing apart again.
+        for (i, blob) in self.metaballs.blobs.iter_mut().enumerate() {
+            let i = i as f32;
+            let x = (time * (0.3 + 0.07 * i) + i * 1.3).sin();
+            let y = (time * (0.4 + 0.05 * i) + i * 2.1).cos();
+            blob.position = [x * 0.8, y * 0.6];
+        }
+        if self.render_instances {
+            // A slowly turning spiral, each circle pulsing a little out of step.
+            let instances: Vec<SpriteInstance> = (0..DEMO_INSTANCES)
+                .map(|i| {
+                    let t = i as f32 / DEMO_INSTANCES as f32;
+                    let angle = t * 6.0 * std::f32::consts::TAU + time * 0.3;
+                    let radius = 0.1 + 1.4 * t;
+                    let size = 0.1 + 0.03 * (time * 3.0 + i as f32).sin();
+                    SpriteInstance::new(
+                        [angle.cos() * radius, angle.sin() * radius],
+                        0.0,
+                        [size, size],
+                    )
+                    .with_tint([1.0 - t, 0.6, t, 1.0])
+                })
+                .collect();
+            self.circle_instances
+                .write(&self.device, &self.queue, &instances);
+        }
+
+        if self.debug_enabled {
+            self.draw_debug();
+        }
+    }
+
+    /// Advances the rocks' animations by `dt` seconds and streams their frames.
+    fn update_rocks(&mut self, dt: f32) {
+        let instances: Vec<SpriteInstance> = self
+            .rock_players
+            .iter_mut()
+            .enumerate()
+            .map(|(i, player)| {
+                player.update(dt);
+                let [_, _, width, height] = player.frame().map_or([0; 4], |frame| frame.rect);
+                let position = [-1.2 + 0.6 * i as f32, -0.9];
+                let scale = [width as f32 * ROCK_SCALE, height as f32 * ROCK_SCALE];
+                player.apply(SpriteInstance::new(position, 0.0, scale))
+            })
+            .collect();
+        self.rock_instances
+            .write(&self.device, &self.queue, &instances);
+    }
+
+    /// Poses the seaweed `dt` seconds on and skins its mesh, crossfading between
+    /// swaying and curling every few seconds.
+    fn update_seaweed(&mut self, dt: f32) {
+        let cycle = self.start_time.elapsed().as_secs_f32() % 8.0;
+        let name = if cycle < 4.0 { "sway" } else { "curl" };
+        if let Some(clip) = self.seaweed.clip(name) {
+            self.seaweed_player.play(clip, 0.5);
+        }
+        self.seaweed_player.update(dt);
+        self.seaweed_player
+            .apply(&self.seaweed, &mut self.seaweed_pose);
+        let (vertices, indices) = self.seaweed_pose.skin(&self.seaweed);
+        self.seaweed_mesh
+            .write(&self.device, &self.queue, &vertices, &indices);
+    }
+
+    /// Outlines occluders and marks lights and blobs.
+    fn draw_debug(&self) {
+        let occluder_color = [1.0, 0.9, 0.2, 1.0];
+        for occluder in &self.lighting.occluders {
+            debug_draw::polygon(occluder.points(), occluder_color);
+        }
+        let light_color = [1.0, 0.5, 0.2, 1.0];
+        for light in &self.lighting.lights {
+            if let LightKind::Spot { direction, .. } = light.kind {
+                let [x, y] = light.position;
+                let tip = [x + direction[0] * 0.5, y + direction[1] * 0.5];
+                debug_draw::arrow(light.position, tip, light_color);
+                debug_draw::circle(light.position, light.radius, [1.0, 0.5, 0.2, 0.3]);
+                debug_draw::text(light.position, "spot", light_color);
+            }
+        }
+        for blob in &self.metaballs.blobs {
+            debug_draw::cross(blob.position, 0.03, [0.3, 1.0, 0.6, 1.0]);
+        }
+    }
+
+    /// Spins the planet around the sun and the moon around the planet, then
+    /// brings the scene's meshes up to date.
+    fn update_scene(&mut self, dt: f32) {
+        for (id, speed) in [(self.scene_orbit, 0.8), (self.scene_planet, 2.5)] {
+            if let Some(node) = self.scene.get_mut(id) {
+                node.transform_mut().rotation += speed * dt;
+            }
+        }
+        self.scene.prepare(&self.device, &self.queue);
+    }
+
+    /// Finishes whatever the asset workers have decoded, and gives each backdrop
+    /// layer a material once its texture is in. Also applies edits to asset and
+    /// shader files.
+    fn update_assets(&mut self) {
+        for event in self.assets.update() {
+            match event {
+                AssetEvent::Reloaded(path) => self.asset_reloaded(&path),
+                AssetEvent::ReloadFailed(error) => log::error!("{error}"),
+            }
+        }
+        for event in self.shaders.update() {
+            match event {
+                AssetEvent::Reloaded(path) => self.shader_reloaded(&path),
+                AssetEvent::ReloadFailed(error) => {
+                    log::error!("{error}");
+                    self.shader_error = Some(error.to_string());
+                    self.update_hud();
+                }
+            }
+        }
+        let progress = self.assets.progress();
+        if progress == self.load_progress {
+            return;
+        }
+        self.load_progress = progress;
+        for layer in &mut self.backdrop {
+            if layer.material.is_some() {
+                continue;
+            }
+            match layer.texture.state() {
+                LoadState::Loading => {}
+                LoadState::Loaded => {
+                    layer.material = Some(
+                        Material::textured(
+                            &self.device,
+                            &mut self.pipelines,
+                            &layer.texture.borrow(),
+                            BlendMode::PremultipliedAlpha,
+                            self.target,
+                        )
+                        .unwrap_or_else(|e| panic!("{e}")),
+                    );
+                }
+                LoadState::Failed => {
+                    if let Some(error) = layer.texture.error() {
+                        log::warn!("{error}");
+                    }
+                }
+            }
+        }
+        self.update_hud();
+    }
+
+    /// Remakes whatever was built from a reloaded file. Materials sampling a
+    /// startup texture are rebound whenever its version has moved on since they
+    /// were last bound.
+    fn asset_reloaded(&mut self, path: &std::path::Path) {
+        log::info!("reloaded '{}'", path.display());
+        for binding in &mut self.textures {
+            // Every reload bumps the version. One at a new size makes a new texture,
+            // so rebind on any bump rather than working out which kind it was.
+            let version = binding.texture.version();
+            if binding.texture.path() != path || version == binding.version {
+                continue;
+            }
+            binding.version = version;
+            let texture = binding.texture.borrow();
+            for material in &binding.materials {
+                if let Err(e) = material.set_texture(&self.device, &self.pipelines, 1, &texture) {
+                    log::error!("{e}");
+                }
+            }
+        }
+        for layer in &mut self.backdrop {
+            if layer.texture.path() != path || layer.texture.state() != LoadState::Loaded {
+                continue;
+            }
+            // If the material can't be made the old one, if any, keeps drawing.
+            match Material::textured(
+                &self.device,
+                &mut self.pipelines,
+                &layer.texture.borrow(),
+                BlendMode::PremultipliedAlpha,
+                self.target,
+            ) {
+                Ok(material) => layer.material = Some(material),
+                Err(e) => log::error!("{e}"),
+            }
+        }
+        if self.font.path() == path {
+            // A new font has a new glyph atlas.
+            match Material::text(
+                &self.device,
+                &mut self.pipelines,
+                &self.font.borrow(),
+                TargetFormat::color(self.config.format),
+            ) {
+                Ok(material) => self.hud_material = material,
+                Err(e) => log::error!("{e}"),
+            }
+            self.update_hud();
+        }
+    }
+
+    /// Swaps every shader built from the edited file into the registry and points
+    /// everything drawing with them at the new pipelines. If wgpu rejects a shader
+    /// the old one stays.
+    fn shader_reloaded(&mut self, path: &Path) {
+        let Some(&(file, _)) = self
+            .shader_files
+            .iter()
+            .find(|(_, source)| source.path() == path)
+        else {
+            return;
+        };
+        let reloaded = if file == PARTICLES_SIMULATE_FILE {
+            self.reload_simulation()
+        } else {
+            self.reload_shaders(file)
+        };
+        match reloaded {
+            Ok(()) => {
+                log::info!("reloaded shader file '{file}'");
+                self.shader_error = None;
+            }
+            Err(e) => {
+                log::error!("{e}");
+                self.shader_error = Some(e.to_string());
+            }
+        }
+        self.update_hud();
+    }
+
+    /// Puts the shaders using `file` back together from their files and swaps them
+    /// into the registry, then reloads the pipelines made from them.
+    fn reload_shaders(&mut self, file: &str) -> Result<(), PipelineError> {
+        let mut replaced = Vec::new();
+        for shader in watched_shaders() {
+            // Effects the demo didn't add aren't registered.
+            if !shader.files.contains(&file) || self.pipelines.shader(shader.name).is_none() {
+                continue;
+            }
+            let Some(source) = self.shader_source(shader.files) else {
+                log::warn!("not all files of shader '{}' are loaded", shader.name);
+                continue;
+            };
+            let module = Shader::from_wgsl(&self.device, shader.name, &source)?;
+            self.pipelines
+                .replace_shader(&self.device, shader.name, module)?;
+            replaced.push(shader.name);
+        }
+
+        let mut materials = vec![
+            &self.tree_material,
+            &self.tree_lit_material,
+            &self.circle_material,
+            &self.instanced_circle_material,
+            &self.rock_material,
+            &self.seaweed_material,
+            &self.wall_material,
+            &self.hud_material,
+            self.bubbles.material(),
+        ];
+        materials.extend(
+            self.backdrop
+                .iter()
+                .filter_map(|layer| layer.material.as_ref()),
+        );
+        materials.extend(self.scene.materials().map(|material| &**material));
+        #[cfg(feature = "debug_draw")]
+        materials.extend(self.debug_renderer.materials());
+        materials
+            .into_iter()
+            .filter(|material| replaced.contains(&material.descriptor.shader.as_str()))
+            .try_for_each(|material| material.reload_pipeline(&self.device, &mut self.pipelines))?;
+        self.lighting
+            .reload_pipelines(&self.device, &mut self.pipelines)?;
+        self.metaballs
+            .reload_pipelines(&self.device, &mut self.pipelines)?;
+        self.post_process
+            .reload_pipelines(&self.device, &mut self.pipelines)
+    }
+
+    /// The watched `files` joined the way their shader was first built, if they are
+    /// all loaded.
+    fn shader_source(&self, files: &[&str]) -> Option<String> {
+        let sources = files
+            .iter()
+            .map(|file| {
+                let (_, source) = self
+                    .shader_files
+                    .iter()
+                    .find(|(loaded, _)| loaded == file)?;
+                Some(source.get()?.clone())
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(sources.join("\n"))
+    }
+
+    /// Swaps the edited compute shader into the bubbles' GPU simulation.
+    fn reload_simulation(&mut self) -> Result<(), PipelineError> {
+        let Some(source) = self.shader_source(&[PARTICLES_SIMULATE_FILE]) else {
+            return Ok(());
+        };
+        let shader = Shader::from_wgsl(&self.device, PARTICLES_SIMULATE_FILE, &source)?;
+        self.bubbles.reload_simulation(&self.device, &shader)
+    }
+
+    /// Briefly zooms the camera in and springs it back out.
+    fn punch_camera(&mut self) {
+        let fovy: fn(&mut Camera) -> &mut f32 = |camera| &mut camera.fovy;
+        let mut camera_tweens = self.world.resource_mut::<Tweens<Camera>>();
+        camera_tweens.clear();
+        camera_tweens.add(
+            Sequence::new()
+                .then(Tween::new(fovy, 40.0, 0.08).with_ease(Ease::QuadOut))
+                .then(Tween::new(fovy, 45.0, 0.8).with_ease(Ease::ElasticOut)),
+        );
+    }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         let output = self.surface.get_current_texture()?;
@@ -352,14 +1454,14 @@ impl<'a> State<'a> {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        self.camera_state.camera.eye += Vector3::new(-0.01, 0.01, 0.0);
-        self.camera_state.camera.target += Vector3::new(-0.01, 0.01, 0.0);
-        self.camera_state.update();
-        self.queue.write_buffer(
-            &self.camera_state.buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_state.uniform]),
-        );
+        let now = Instant::now();
+        // Clamped so a long stall doesn't fling every particle across the screen.
+        let dt = (now - self.last_frame).as_secs_f32().min(0.1);
+        self.last_frame = now;
+
+        self.schedule.run(&mut self.world, dt);
+        self.world.resource_mut::<Input>().end_frame();
+        let camera_bind_group = self.world.resource::<CameraState>().bind_group.clone();
 
         let mut encoder = self
             .device
@@ -367,19 +1469,186 @@ impl<'a> State<'a> {
                 label: Some("Render Encoder"),
             });
 
+        self.bubbles.update(&self.queue, &mut encoder, dt);
+        if self.render_rocks {
+            self.update_rocks(dt);
+        }
+        if self.render_seaweed {
+            self.update_seaweed(dt);
+        }
+        if self.render_scene {
+            self.update_scene(dt);
+        }
+
         {
+            let clear_color = wgpu::Color {
+                r: 0.6,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            };
+            // With post-processing enabled the scene goes into the chain's first
+            // target rather than straight to the swapchain image.
+            let scene_view = if self.post_process.is_active() {
+                &self.post_process.scene_target().color.view
+            } else {
+                &view
+            };
+            // With MSAA we draw into the multisampled texture and let the pass
+            // resolve it into the scene view.
+            let color_attachment = match &self.msaa_texture {
+                Some(msaa_texture) => msaa_texture.attachment(scene_view, clear_color),
+                None => wgpu::RenderPassColorAttachment {
+                    view: scene_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                },
+            };
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
+                color_attachments: &[Some(color_attachment)],
+                depth_stencil_attachment: self.depth_texture.as_ref().map(DepthTexture::attachment),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_bind_group(0, &camera_bind_group, &[]);
+            let mut draws = match self.depth_texture {
+                Some(_) => RenderQueue::with_layer_depth(
+                    self.settings.layer_depth,
+                    self.config.width,
+                    self.config.height,
+                ),
+                None => RenderQueue::new(),
+            };
+            for (i, layer) in self.backdrop.iter().enumerate() {
+                if let Some(material) = &layer.material {
+                    draws.push(DrawItem {
+                        material,
+                        mesh: &self.backdrop_mesh,
+                        layer: i as i32 - self.backdrop.len() as i32,
+                        depth: 0.0,
+                    });
+                }
+            }
+            draws.push(DrawItem {
+                material: &self.tree_material,
+                mesh: &self.tree_mesh,
+                layer: 0,
+                depth: 0.0,
+            });
+            draws.push(DrawItem {
+                material: &self.wall_material,
+                mesh: &self.wall_mesh,
+                layer: 0,
+                depth: 0.0,
+            });
+            if self.render_circle {
+                draws.push(DrawItem {
+                    material: &self.circle_material,
+                    mesh: &self.circle_mesh,
+                    layer: 1,
+                    depth: 0.0,
+                });
+            }
+            if self.render_scene {
+                self.scene.queue_draws(&mut draws);
+            }
+            draws.draw(&mut render_pass);
+
+            // Bubbles float in front of everything else.
+            if self.depth_texture.is_some() {
+                let (min_depth, max_depth) = self.settings.layer_depth.range(2);
+                render_pass.set_viewport(
+                    0.0,
+                    0.0,
+                    self.config.width as f32,
+                    self.config.height as f32,
+                    min_depth,
+                    max_depth,
+                );
+            }
+            self.bubbles.draw(&mut render_pass);
+            if self.render_instances {
+                self.instanced_circle_material.bind(&mut render_pass);
+                self.circle_mesh
+                    .draw_instanced(&mut render_pass, &self.circle_instances);
+            }
+            if self.render_fireflies {
+                self.instanced_circle_material.bind(&mut render_pass);
+                let fireflies = self.world.resource::<FireflyInstances>();
+                self.circle_mesh
+                    .draw_instanced(&mut render_pass, &fireflies.buffer);
+            }
+            if self.render_rocks {
+                self.rock_material.bind(&mut render_pass);
+                self.rock_mesh
+                    .draw_instanced(&mut render_pass, &self.rock_instances);
+            }
+            if self.render_seaweed {
+                self.seaweed_material.bind(&mut render_pass);
+                self.seaweed_mesh.draw(&mut render_pass);
+            }
+        }
+
+        if self.lighting_enabled {
+            {
+                let mut render_pass = self.lighting.begin_normal_pass(&mut encoder);
+                render_pass.set_bind_group(0, &camera_bind_group, &[]);
+                self.tree_lit_material.bind(&mut render_pass);
+                self.tree_mesh.draw(&mut render_pass);
+            }
+            let scene_view = if self.post_process.is_active() {
+                &self.post_process.scene_target().color.view
+            } else {
+                &view
+            };
+            self.lighting.render(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &camera_bind_group,
+                scene_view,
+            );
+        }
+
+        if self.metaballs.is_enabled(&self.post_process) {
+            self.metaballs.render_field(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &camera_bind_group,
+            );
+        }
+
+        if self.post_process.is_active() {
+            self.post_process.run(
+                &self.queue,
+                &mut encoder,
+                &view,
+                self.start_time.elapsed().as_secs_f32(),
+            );
+        }
+
+        #[cfg(feature = "debug_draw")]
+        self.debug_renderer.render(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &view,
+            &camera_bind_group,
+        );
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("HUD Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.6,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Load,
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -387,25 +1656,9 @@ impl<'a> State<'a> {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(1, &self.camera_state.bind_group, &[]);
-            // render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            if self.render_circle {
-                render_pass.set_vertex_buffer(0, self.circle_vertex_buffer.slice(..));
-                render_pass.set_index_buffer(
-                    self.circle_index_buffer.slice(..),
-                    wgpu::IndexFormat::Uint16,
-                );
-                render_pass.draw_indexed(0..self.circle_num_indices, 0, 0..1);
-            } else {
-                render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-                // slice(..) specifies what part of the buffer to use (all of it).
-                // If we wanted to only use part of a buffer we could provide another slice object.
-                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-                render_pass
-                    .set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
-            }
+            render_pass.set_bind_group(0, &self.screen_space.bind_group, &[]);
+            self.hud_material.bind(&mut render_pass);
+            self.hud_mesh.draw(&mut render_pass);
         }
 
         self.queue.submit(iter::once(encoder.finish()));
@@ -414,3 +1667,252 @@ impl<'a> State<'a> {
         Ok(())
     }
 }
+
+/// One layer of the backdrop, drawn once its texture has loaded.
+struct BackdropLayer {
+    texture: Handle<ImageTexture>,
+    material: Option<Material>,
+}
+
+/// A texture and the materials that sample it as their first texture.
+struct TextureBinding {
+    texture: Handle<ImageTexture>,
+    /// The texture's version when the materials were last pointed at it.
+    version: u64,
+    materials: Vec<Arc<Material>>,
+}
+
+impl TextureBinding {
+    fn new(texture: Handle<ImageTexture>, materials: Vec<Arc<Material>>) -> Self {
+        Self {
+            version: texture.version(),
+            texture,
+            materials,
+        }
+    }
+}
+
+/// Materials for the demo level. Every sprite in it is the happy tree.
+struct DemoSceneAssets {
+    tree: Arc<Material>,
+    shapes: Arc<Material>,
+}
+
+impl SceneAssets for DemoSceneAssets {
+    fn sprite_material(&mut self, path: &str) -> Option<Arc<Material>> {
+        (path == "happy-tree.png").then(|| self.tree.clone())
+    }
+
+    fn shape_material(&mut self) -> Arc<Material> {
+        self.shapes.clone()
+    }
+}
+
+/// Picks the highest sample count no greater than `requested` that every format in
+/// `formats` can be rendered with on this adapter.
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    formats: &[wgpu::TextureFormat],
+    requested: u32,
+) -> u32 {
+    // Without adapter specific format features only the WebGPU guaranteed counts are usable.
+    let adapter_specific = device
+        .features()
+        .contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    let supported = |count: u32| {
+        formats.iter().all(|&format| {
+            if adapter_specific {
+                adapter
+                    .get_texture_format_features(format)
+                    .flags
+                    .sample_count_supported(count)
+            } else {
+                count == 1 || count == 4
+            }
+        })
+    };
+    let sample_count = [8, 4, 2, 1]
+        .into_iter()
+        .find(|&count| count <= requested && supported(count))
+        .unwrap_or(1);
+    if sample_count != requested {
+        log::warn!("MSAA x{requested} is not supported, falling back to x{sample_count}");
+    }
+    sample_count
+}
+
+/// Logs why `handle` failed to load, if it did, and stands `fallback` in for the
+/// asset so code that needs it can carry on.
+fn or_fallback<T>(handle: &Handle<T>, fallback: impl FnOnce() -> T) {
+    let Some(error) = handle.error().map(|error| error.to_string()) else {
+        return;
+    };
+    log::error!("{error}; using a placeholder instead");
+    handle.set(fallback());
+}
+
+/// Drawn in place of an image that failed to load, in a colour that stands out.
+fn placeholder_image() -> image::RgbaImage {
+    image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 255, 255]))
+}
+
+/// The directory named by the environment variable `var`, or failing that the
+/// `name` directory next to the executable. When run with `cargo run`, which leaves
+/// the executable in the target directory, `package_path` inside the package is
+/// used if there is nothing next to the executable.
+fn resource_dir(var: &str, name: &str, package_path: &str) -> PathBuf {
+    if let Some(dir) = std::env::var_os(var) {
+        return dir.into();
+    }
+    let next_to_exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(name)))
+        .unwrap_or_else(|| name.into());
+    if next_to_exe.is_dir() {
+        return next_to_exe;
+    }
+    match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(package) => Path::new(&package).join(package_path),
+        None => next_to_exe,
+    }
+}
+
+/// The registered shaders rebuilt when one of their files changes.
+fn watched_shaders() -> impl Iterator<Item = ShaderFiles> {
+    BUILTIN_SHADER_FILES
+        .into_iter()
+        .chain(LIGHTING_SHADER_FILES)
+        .chain(PARTICLE_SHADER_FILES)
+        .chain(POST_SHADER_FILES)
+        .chain(METABALLS_SHADER_FILES)
+}
+
+struct Position(Vector2<f32>);
+
+struct Velocity(Vector2<f32>);
+
+/// Seconds a firefly has left out of the `total` it was spawned with.
+struct Lifetime {
+    remaining: f32,
+    total: f32,
+}
+
+struct Glow([f32; 4]);
+
+/// Pushes every firefly along, steered with the arrow keys.
+struct Wind(Vector2<f32>);
+
+/// The GPU handles the RenderPrep systems upload through.
+struct Renderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+/// The fireflies gathered for this frame and the buffer they are drawn from.
+struct FireflyInstances {
+    sprites: Vec<SpriteInstance>,
+    buffer: InstanceBuffer<SpriteInstance>,
+}
+
+/// Fireflies spawned so far, so each new one starts somewhere different.
+struct FireflySpawner(u32);
+
+/// The components of the `n`th firefly spawned.
+fn firefly(n: u32) -> (Position, Velocity, Lifetime, Glow) {
+    // Stepping by irrational fractions spreads successive fireflies out evenly
+    // without needing a random number generator.
+    let n = n as f32;
+    let [a, b, c] = [0.618_034, 0.754_878, 0.569_840].map(|step: f32| (n * step).fract());
+    let angle = b * std::f32::consts::TAU;
+    let total = 3.0 + 4.0 * c;
+    (
+        Position(Vector2::new(-1.5 + 3.0 * a, -1.0 + 2.0 * b)),
+        Velocity(Vector2::new(angle.cos(), angle.sin()) * 0.15),
+        Lifetime {
+            remaining: total,
+            total,
+        },
+        Glow([1.0, 0.9, 0.3 + 0.5 * a, 1.0]),
+    )
+}
+
+fn steer_fireflies(world: &World, _commands: &mut Commands) {
+    let input = world.resource::<Input>();
+    let direction = Vector2::new(
+        input.axis(KeyCode::ArrowLeft, KeyCode::ArrowRight),
+        input.axis(KeyCode::ArrowDown, KeyCode::ArrowUp),
+    );
+    world.resource_mut::<Wind>().0 = direction * WIND_SPEED;
+}
+
+fn move_fireflies(world: &World, _commands: &mut Commands) {
+    let dt = world.resource::<Time>().delta;
+    let wind = world.resource::<Wind>().0;
+    world
+        .query::<(&mut Position, &Velocity)>()
+        .for_each(|_, (position, velocity)| {
+            position.0 += (velocity.0 + wind) * dt;
+            // Wrap around the edges of the demo area.
+            position.0.x = (position.0.x + 1.5).rem_euclid(3.0) - 1.5;
+            position.0.y = (position.0.y + 1.0).rem_euclid(2.0) - 1.0;
+        });
+}
+
+/// Replaces every firefly whose time is up with a new one.
+fn age_fireflies(world: &World, commands: &mut Commands) {
+    let dt = world.resource::<Time>().delta;
+    let mut spawner = world.resource_mut::<FireflySpawner>();
+    world.query::<&mut Lifetime>().for_each(|entity, lifetime| {
+        lifetime.remaining -= dt;
+        if lifetime.remaining <= 0.0 {
+            commands.despawn(entity);
+            commands.spawn(firefly(spawner.0));
+            spawner.0 += 1;
+        }
+    });
+}
+
+fn upload_camera(world: &World, _commands: &mut Commands) {
+    let renderer = world.resource::<Renderer>();
+    let mut camera_state = world.resource_mut::<CameraState>();
+    camera_state.camera = *world.resource::<Camera>();
+    camera_state.update();
+    renderer.queue.write_buffer(
+        &camera_state.buffer,
+        0,
+        bytemuck::cast_slice(&[camera_state.uniform]),
+    );
+}
+
+fn upload_fireflies(world: &World, _commands: &mut Commands) {
+    let renderer = world.resource::<Renderer>();
+    let mut fireflies = world.resource_mut::<FireflyInstances>();
+    let FireflyInstances { sprites, buffer } = &mut *fireflies;
+    sprites.clear();
+    world
+        .query::<(&Position, &Lifetime, &Glow)>()
+        .for_each(|_, (position, lifetime, glow)| {
+            // Fades in and out over the first and last half second.
+            let age = lifetime.total - lifetime.remaining;
+            let fade = (lifetime.remaining.min(age) * 2.0).clamp(0.0, 1.0);
+            let [r, g, b, a] = glow.0;
+            let size = 0.03 + 0.02 * fade;
+            let sprite = SpriteInstance::new(position.0.into(), 0.0, [size, size]);
+            sprites.push(sprite.with_tint([r, g, b, a * fade]));
+        });
+    buffer.write(&renderer.device, &renderer.queue, sprites);
+}
+
+fn animate_camera(world: &World, _commands: &mut Commands) {
+    let dt = world.resource::<Time>().delta;
+    world
+        .resource_mut::<Tweens<Camera>>()
+        .update(&mut world.resource_mut::<Camera>(), dt);
+}
+
+fn drift_camera(world: &World, _commands: &mut Commands) {
+    let mut camera = world.resource_mut::<Camera>();
+    camera.eye += Vector3::new(-0.01, 0.01, 0.0);
+    camera.target += Vector3::new(-0.01, 0.01, 0.0);
+}
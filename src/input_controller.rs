@@ -1,10 +1,74 @@
-use std::collections::HashMap;
-
-use cgmath::{Vector2, Vector3, Zero};
-use winit::{
-    event::{ElementState, KeyEvent},
-    keyboard::Key,
-};
+use std::collections::HashSet;
+
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+/// Which keys are down, and which went down or up since the last frame, e.g. as
+/// an [`ecs`](crate::ecs) resource for input systems to read.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    held: HashSet<KeyCode>,
+    pressed: HashSet<KeyCode>,
+    released: HashSet<KeyCode>,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes note of keyboard events and ignores the rest.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    state,
+                    physical_key: PhysicalKey::Code(key),
+                    repeat: false,
+                    ..
+                },
+            ..
+        } = event
+        else {
+            return;
+        };
+        match state {
+            ElementState::Pressed => {
+                self.held.insert(*key);
+                self.pressed.insert(*key);
+            }
+            ElementState::Released => {
+                self.held.remove(key);
+                self.released.insert(*key);
+            }
+        }
+    }
+
+    pub fn is_held(&self, key: KeyCode) -> bool {
+        self.held.contains(&key)
+    }
+
+    /// Whether `key` went down this frame.
+    pub fn was_pressed(&self, key: KeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    /// Whether `key` came up this frame.
+    pub fn was_released(&self, key: KeyCode) -> bool {
+        self.released.contains(&key)
+    }
+
+    /// -1, 0 or 1 depending on which of two opposing keys are held.
+    pub fn axis(&self, negative: KeyCode, positive: KeyCode) -> f32 {
+        self.is_held(positive) as i32 as f32 - self.is_held(negative) as i32 as f32
+    }
+
+    /// Forgets what went down and up, ready for the next frame's events.
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+}
 
 // pub enum GenericValue<T> {
 //     Float(f32),
@@ -0,0 +1,96 @@
+use std::marker::PhantomData;
+
+use crate::vertex::Instance;
+
+crate::instance! {
+    /// One copy of a mesh in an instanced draw, placed by a 2D transform.
+    ///
+    /// The mesh is scaled, then rotated counter-clockwise by `rotation` radians
+    /// about its origin and moved to `position`.
+    /// Its colour is multiplied by `tint`, and texture coordinates are mapped into
+    /// `uv_rect`, given as the offset and size of a region of the texture, so
+    /// instances can show different frames of one sheet.
+    pub struct SpriteInstance {
+        pub position: [f32; 2],
+        pub rotation: f32,
+        pub scale: [f32; 2],
+        pub tint: [f32; 4],
+        pub uv_rect: [f32; 4],
+    }
+}
+
+impl SpriteInstance {
+    /// An untinted instance showing the whole texture.
+    pub fn new(position: [f32; 2], rotation: f32, scale: [f32; 2]) -> Self {
+        Self {
+            position,
+            rotation,
+            scale,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+        }
+    }
+
+    pub fn with_tint(self, tint: [f32; 4]) -> Self {
+        Self { tint, ..self }
+    }
+
+    pub fn with_uv_rect(self, uv_rect: [f32; 4]) -> Self {
+        Self { uv_rect, ..self }
+    }
+}
+
+/// Per-instance data for [`Mesh::draw_instanced`](crate::mesh::Mesh::draw_instanced),
+/// streamed to the GPU every frame.
+///
+/// Writes land before the frame's commands run, so one buffer holds the instances
+/// of one draw per frame. Use a buffer for each batch that changes independently.
+pub struct InstanceBuffer<I: Instance> {
+    buffer: wgpu::Buffer,
+    len: u32,
+    _instance: PhantomData<I>,
+}
+
+impl<I: Instance> InstanceBuffer<I> {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            buffer: Self::create_buffer(device, 64),
+            len: 0,
+            _instance: PhantomData,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * std::mem::size_of::<I>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Replaces the instances, growing the buffer if they no longer fit.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, instances: &[I]) {
+        let needed = std::mem::size_of_val(instances) as wgpu::BufferAddress;
+        if needed > self.buffer.size() {
+            self.buffer = Self::create_buffer(device, instances.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        self.len = instances.len() as u32;
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The part of the buffer holding the current instances.
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        let size =
+            self.len as wgpu::BufferAddress * std::mem::size_of::<I>() as wgpu::BufferAddress;
+        self.buffer.slice(..size)
+    }
+}
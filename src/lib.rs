@@ -1,7 +1,30 @@
+pub mod assets;
 pub mod camera;
 pub mod constants;
+pub mod debug_draw;
+pub mod ecs;
 pub mod engine;
 pub mod input_controller;
+pub mod instancing;
+pub mod lerp;
+pub mod lighting;
+pub mod material;
+pub mod mesh;
+pub mod metaballs;
+pub mod mipmap;
+pub mod particles;
+pub mod pipeline;
+pub mod post_process;
+pub mod render_queue;
+pub mod render_target;
+pub mod scene;
+pub mod scene_file;
+pub mod shader;
+pub mod shadow;
 pub mod shapes;
+pub mod skeleton;
+pub mod sprite_animation;
+pub mod text;
 pub mod texture;
+pub mod tween;
 pub mod vertex;
@@ -0,0 +1,623 @@
+use crate::pipeline::{
+    BlendMode, PipelineDescriptor, PipelineError, PipelineRegistry, ShaderFiles,
+};
+use crate::render_target::{RenderTarget, RenderTargetDescriptor};
+use crate::shader::Shader;
+use crate::shadow::{visibility_polygon, Occluder};
+use crate::texture::ImageTexture;
+use crate::vertex::Vertex;
+
+const LIGHTS_SHADER: &str = "lighting/lights";
+const SHADOWED_LIGHTS_SHADER: &str = "lighting/shadowed_lights";
+const COMPOSITE_SHADER: &str = "lighting/composite";
+
+/// The files under `src/shaders` each lighting shader is put together from. Swap
+/// edits in with [`PipelineRegistry::replace_shader`], then call
+/// [`Lighting::reload_pipelines`].
+pub const LIGHTING_SHADER_FILES: [ShaderFiles; 3] = [
+    ShaderFiles {
+        name: LIGHTS_SHADER,
+        files: &["lighting/common.wgsl", "lighting/lights.wgsl"],
+    },
+    ShaderFiles {
+        name: SHADOWED_LIGHTS_SHADER,
+        files: &["lighting/common.wgsl", "lighting/shadowed_lights.wgsl"],
+    },
+    ShaderFiles {
+        name: COMPOSITE_SHADER,
+        files: &["lighting/composite.wgsl"],
+    },
+];
+
+/// Light buffer format. Floating point so overlapping lights can add up past 1.
+const LIGHT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Positions a soft shadow casting light is sampled from.
+const SOFT_SHADOW_SAMPLES: usize = 8;
+
+/// An encoded normal pointing straight out of the screen.
+const FLAT_NORMAL: wgpu::Color = wgpu::Color {
+    r: 0.5,
+    g: 0.5,
+    b: 1.0,
+    a: 1.0,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Shines equally in every direction.
+    Point,
+    /// Shines in a cone around `direction`. Angles are half-angles in radians, with
+    /// the light fading out between `inner_angle` and `outer_angle`.
+    Spot {
+        direction: [f32; 2],
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+    /// Lights the whole screen from `direction`, like the sun. Ignores position,
+    /// radius and falloff.
+    Directional { direction: [f32; 2] },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    /// World position of the light.
+    pub position: [f32; 2],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely.
+    pub radius: f32,
+    /// Exponent of the fade towards `radius`. 1 is linear, higher fades faster.
+    pub falloff: f32,
+    /// How far above the scene the light sits. Lower lights graze normal maps
+    /// and bring out more relief.
+    pub height: f32,
+    /// Whether [`Lighting::occluders`] block this light. Directional lights never
+    /// cast shadows.
+    pub shadows: bool,
+    /// Size of the light source in world units. 0 casts hard shadows, anything
+    /// larger blurs their edges into a penumbra.
+    pub shadow_softness: f32,
+}
+
+impl Light {
+    pub fn point(position: [f32; 2], color: [f32; 3], radius: f32) -> Self {
+        Self {
+            kind: LightKind::Point,
+            position,
+            color,
+            intensity: 1.0,
+            radius,
+            falloff: 2.0,
+            height: radius * 0.25,
+            shadows: true,
+            shadow_softness: 0.0,
+        }
+    }
+
+    /// A spot light pointing along `direction` with a cone `angle` radians wide on
+    /// each side.
+    pub fn spot(
+        position: [f32; 2],
+        direction: [f32; 2],
+        angle: f32,
+        color: [f32; 3],
+        radius: f32,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                direction,
+                inner_angle: angle * 0.75,
+                outer_angle: angle,
+            },
+            ..Self::point(position, color, radius)
+        }
+    }
+
+    /// A directional light travelling along `direction`.
+    pub fn directional(direction: [f32; 2], color: [f32; 3]) -> Self {
+        Self {
+            kind: LightKind::Directional { direction },
+            position: [0.0, 0.0],
+            color,
+            intensity: 1.0,
+            radius: 0.0,
+            falloff: 1.0,
+            height: 1.0,
+            shadows: false,
+            shadow_softness: 0.0,
+        }
+    }
+
+    /// The areas this light can see past `occluders`, as triangle fans around the
+    /// positions it is sampled from. `None` if nothing blocks it.
+    fn visible_areas(&self, occluders: &[Occluder]) -> Option<Vec<VisibleArea>> {
+        if !self.shadows || matches!(self.kind, LightKind::Directional { .. }) {
+            return None;
+        }
+        // Reach far enough that every sample still covers the whole radius.
+        let reach = self.radius + self.shadow_softness;
+        let polygon = visibility_polygon(self.position, reach, occluders)?;
+        if self.shadow_softness <= 0.0 {
+            return Some(vec![VisibleArea {
+                origin: self.position,
+                polygon,
+            }]);
+        }
+
+        // Soft shadows average what several points around the edge of the source see.
+        let [x, y] = self.position;
+        let areas = (0..SOFT_SHADOW_SAMPLES)
+            .map(|i| {
+                let angle = i as f32 / SOFT_SHADOW_SAMPLES as f32 * std::f32::consts::TAU;
+                let origin = [
+                    x + angle.cos() * self.shadow_softness,
+                    y + angle.sin() * self.shadow_softness,
+                ];
+                let polygon = visibility_polygon(origin, reach, occluders).unwrap_or_else(|| {
+                    let [x, y] = origin;
+                    vec![
+                        [x - reach, y - reach],
+                        [x + reach, y - reach],
+                        [x + reach, y + reach],
+                        [x - reach, y + reach],
+                    ]
+                });
+                VisibleArea { origin, polygon }
+            })
+            .collect();
+        Some(areas)
+    }
+
+    fn to_raw(self) -> LightRaw {
+        let normalize = |[x, y]: [f32; 2]| {
+            let length = (x * x + y * y).sqrt().max(f32::EPSILON);
+            [x / length, y / length]
+        };
+        let (kind, direction, cos_inner, cos_outer) = match self.kind {
+            LightKind::Point => (0, [0.0, 0.0], -1.0, -1.0),
+            LightKind::Spot {
+                direction,
+                inner_angle,
+                outer_angle,
+            } => (
+                1,
+                normalize(direction),
+                inner_angle.cos(),
+                outer_angle.cos(),
+            ),
+            LightKind::Directional { direction } => (2, normalize(direction), -1.0, -1.0),
+        };
+        LightRaw {
+            position: self.position,
+            direction,
+            color: self.color,
+            intensity: self.intensity,
+            radius: self.radius.max(f32::EPSILON),
+            falloff: self.falloff,
+            cos_inner,
+            cos_outer,
+            kind,
+            height: self.height,
+            _pad: [0.0; 2],
+        }
+    }
+}
+
+/// Matches `struct Light` in `shaders/lighting/lights.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightRaw {
+    position: [f32; 2],
+    direction: [f32; 2],
+    color: [f32; 3],
+    intensity: f32,
+    radius: f32,
+    falloff: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+    kind: u32,
+    height: f32,
+    _pad: [f32; 2],
+}
+
+/// What a shadow casting light sees from one of its sample positions.
+struct VisibleArea {
+    origin: [f32; 2],
+    /// Counter clockwise around `origin`, drawn as a triangle fan from it.
+    polygon: Vec<[f32; 2]>,
+}
+
+crate::vertex! {
+    /// A corner of a shadow casting light's visible area.
+    struct ShadowVertex {
+        position: [f32; 2],
+        light: u32,
+        weight: f32,
+    }
+}
+
+/// Deferred 2D lighting.
+///
+/// Each frame, after the scene has been drawn:
+/// 1. normal-mapped sprites are drawn into the normal buffer with
+///    [`Lighting::begin_normal_pass`] and `Material::textured_lit`,
+/// 2. [`Lighting::render`] adds every light into a light buffer cleared to the
+///    ambient colour, one instanced quad per light, and multiplies the result over
+///    the scene.
+///
+/// Sprites without a normal map are lit as if they faced the camera.
+///
+/// Lights with shadows that have an occluder within reach are drawn as the polygon
+/// they can see instead of a quad. That polygon is worked out on the CPU each
+/// frame, so keep shadow casting lights and occluders in the dozens rather than
+/// the hundreds.
+pub struct Lighting {
+    pub ambient: [f32; 3],
+    pub lights: Vec<Light>,
+    /// Geometry that blocks lights with [`Light::shadows`] enabled.
+    pub occluders: Vec<Occluder>,
+    normal_target: RenderTarget,
+    light_target: RenderTarget,
+    lights_pipeline: wgpu::RenderPipeline,
+    shadowed_lights_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    format: wgpu::TextureFormat,
+    texture_layout: wgpu::BindGroupLayout,
+    lights_layout: wgpu::BindGroupLayout,
+    normal_bind_group: wgpu::BindGroup,
+    light_bind_group: wgpu::BindGroup,
+    lights_buffer: wgpu::Buffer,
+    lights_bind_group: wgpu::BindGroup,
+    shadow_vertex_buffer: wgpu::Buffer,
+}
+
+impl Lighting {
+    /// `format` is the colour format of whatever the scene is rendered into.
+    pub fn new(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        camera_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, PipelineError> {
+        let texture_layout = ImageTexture::bind_group_layout(device);
+        let lights_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Lights Bind Group Layout"),
+        });
+
+        let common = include_str!("shaders/lighting/common.wgsl");
+        for (name, source) in [
+            (LIGHTS_SHADER, include_str!("shaders/lighting/lights.wgsl")),
+            (
+                SHADOWED_LIGHTS_SHADER,
+                include_str!("shaders/lighting/shadowed_lights.wgsl"),
+            ),
+        ] {
+            let shader = Shader::from_wgsl(device, name, &format!("{common}\n{source}"))?;
+            registry.add_shader(
+                device,
+                name,
+                shader,
+                vec![
+                    camera_layout.clone(),
+                    texture_layout.clone(),
+                    lights_layout.clone(),
+                ],
+            );
+        }
+        let shader = Shader::from_wgsl(
+            device,
+            COMPOSITE_SHADER,
+            include_str!("shaders/lighting/composite.wgsl"),
+        )?;
+        registry.add_shader(
+            device,
+            COMPOSITE_SHADER,
+            shader,
+            vec![texture_layout.clone()],
+        );
+
+        let [lights_pipeline, shadowed_lights_pipeline, composite_pipeline] =
+            Self::create_pipelines(device, registry, format)?;
+
+        let (normal_target, light_target) = Self::create_targets(device, width, height);
+        let lights_buffer = Self::create_lights_buffer(device, 64);
+        Ok(Self {
+            ambient: [0.1, 0.1, 0.15],
+            lights: Vec::new(),
+            occluders: Vec::new(),
+            normal_bind_group: normal_target
+                .color
+                .create_bind_group(device, &texture_layout),
+            light_bind_group: light_target
+                .color
+                .create_bind_group(device, &texture_layout),
+            lights_bind_group: Self::create_lights_bind_group(
+                device,
+                &lights_layout,
+                &lights_buffer,
+            ),
+            normal_target,
+            light_target,
+            lights_pipeline,
+            shadowed_lights_pipeline,
+            composite_pipeline,
+            format,
+            texture_layout,
+            lights_layout,
+            lights_buffer,
+            shadow_vertex_buffer: Self::create_shadow_vertex_buffer(device, 1024),
+        })
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        format: wgpu::TextureFormat,
+    ) -> Result<[wgpu::RenderPipeline; 3], PipelineError> {
+        let shadow_layouts = [ShadowVertex::description()];
+        let fullscreen = |shader, vertex_layouts, blend, format| PipelineDescriptor {
+            shader,
+            vertex_layouts,
+            blend,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            depth: None,
+            sample_count: 1,
+            format,
+        };
+        let lights_pipeline = registry.get_or_create(
+            device,
+            &fullscreen(LIGHTS_SHADER, &[], BlendMode::Additive, LIGHT_FORMAT),
+        )?;
+        let shadowed_lights_pipeline = registry.get_or_create(
+            device,
+            &fullscreen(
+                SHADOWED_LIGHTS_SHADER,
+                &shadow_layouts,
+                BlendMode::Additive,
+                LIGHT_FORMAT,
+            ),
+        )?;
+        let composite_pipeline = registry.get_or_create(
+            device,
+            &fullscreen(COMPOSITE_SHADER, &[], BlendMode::Multiply, format),
+        )?;
+        Ok([
+            lights_pipeline,
+            shadowed_lights_pipeline,
+            composite_pipeline,
+        ])
+    }
+
+    /// Picks up the pipelines rebuilt by [`PipelineRegistry::replace_shader`] after
+    /// one of the [`LIGHTING_SHADER_FILES`] changed.
+    pub fn reload_pipelines(
+        &mut self,
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+    ) -> Result<(), PipelineError> {
+        [
+            self.lights_pipeline,
+            self.shadowed_lights_pipeline,
+            self.composite_pipeline,
+        ] = Self::create_pipelines(device, registry, self.format)?;
+        Ok(())
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> (RenderTarget, RenderTarget) {
+        let normal_target = RenderTarget::new(
+            device,
+            &RenderTargetDescriptor {
+                format: NORMAL_FORMAT,
+                ..RenderTargetDescriptor::new("Normal Buffer", width, height)
+            },
+        );
+        let light_target = RenderTarget::new(
+            device,
+            &RenderTargetDescriptor {
+                format: LIGHT_FORMAT,
+                ..RenderTargetDescriptor::new("Light Buffer", width, height)
+            },
+        );
+        (normal_target, light_target)
+    }
+
+    fn create_lights_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lights Buffer"),
+            size: (capacity * std::mem::size_of::<LightRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_shadow_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<ShadowVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_lights_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("Lights Bind Group"),
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let (normal_target, light_target) = Self::create_targets(device, width, height);
+        self.normal_bind_group = normal_target
+            .color
+            .create_bind_group(device, &self.texture_layout);
+        self.light_bind_group = light_target
+            .color
+            .create_bind_group(device, &self.texture_layout);
+        self.normal_target = normal_target;
+        self.light_target = light_target;
+    }
+
+    /// The normal buffer. Build `Material::textured_lit` materials against its
+    /// `target_format()`.
+    pub fn normal_target(&self) -> &RenderTarget {
+        &self.normal_target
+    }
+
+    /// The accumulated light of the last [`Lighting::render`], before it was
+    /// multiplied over the scene.
+    pub fn light_target(&self) -> &RenderTarget {
+        &self.light_target
+    }
+
+    /// Starts a pass over the normal buffer, cleared to flat normals. Bind the
+    /// camera at group 0 and draw normal-mapped sprites with their lit materials.
+    pub fn begin_normal_pass<'e>(
+        &'e self,
+        encoder: &'e mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'e> {
+        self.normal_target.begin_pass(encoder, Some(FLAT_NORMAL))
+    }
+
+    /// Accumulates [`Lighting::lights`] into the light buffer and multiplies it over
+    /// `scene`, which has to be in the format given to [`Lighting::new`].
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera_bind_group: &wgpu::BindGroup,
+        scene: &wgpu::TextureView,
+    ) {
+        // Lights without shadows go first so the instanced quads can index them
+        // directly. The shadowed ones follow and are referenced by their vertices.
+        let mut raw = Vec::with_capacity(self.lights.len());
+        let mut shadowed = Vec::new();
+        for light in &self.lights {
+            match light.visible_areas(&self.occluders) {
+                Some(areas) => shadowed.push((light, areas)),
+                None => raw.push(light.to_raw()),
+            }
+        }
+        let unshadowed = raw.len() as u32;
+        let mut vertices = Vec::new();
+        for (light, areas) in shadowed {
+            let index = raw.len() as u32;
+            raw.push(light.to_raw());
+            let weight = 1.0 / areas.len() as f32;
+            let vertex = |position| ShadowVertex {
+                position,
+                light: index,
+                weight,
+            };
+            for VisibleArea { origin, polygon } in areas {
+                let next = polygon.iter().cycle().skip(1);
+                for (&a, &b) in polygon.iter().zip(next) {
+                    vertices.extend([vertex(origin), vertex(a), vertex(b)]);
+                }
+            }
+        }
+
+        let needed = std::mem::size_of_val(raw.as_slice()) as wgpu::BufferAddress;
+        if needed > self.lights_buffer.size() {
+            self.lights_buffer = Self::create_lights_buffer(device, raw.len().next_power_of_two());
+            self.lights_bind_group =
+                Self::create_lights_bind_group(device, &self.lights_layout, &self.lights_buffer);
+        }
+        queue.write_buffer(&self.lights_buffer, 0, bytemuck::cast_slice(&raw));
+        let needed = std::mem::size_of_val(vertices.as_slice()) as wgpu::BufferAddress;
+        if needed > self.shadow_vertex_buffer.size() {
+            self.shadow_vertex_buffer =
+                Self::create_shadow_vertex_buffer(device, vertices.len().next_power_of_two());
+        }
+        queue.write_buffer(
+            &self.shadow_vertex_buffer,
+            0,
+            bytemuck::cast_slice(&vertices),
+        );
+
+        let [r, g, b] = self.ambient.map(f64::from);
+        {
+            let mut render_pass = self
+                .light_target
+                .begin_pass(encoder, Some(wgpu::Color { r, g, b, a: 1.0 }));
+            render_pass.set_pipeline(&self.lights_pipeline);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.normal_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.lights_bind_group, &[]);
+            render_pass.draw(0..6, 0..unshadowed);
+            if !vertices.is_empty() {
+                render_pass.set_pipeline(&self.shadowed_lights_pipeline);
+                render_pass.set_vertex_buffer(0, self.shadow_vertex_buffer.slice(..needed));
+                render_pass.draw(0..vertices.len() as u32, 0..1);
+            }
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lighting Composite"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: scene,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &self.light_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Builds a tangent-space normal map from an image by treating its brightness,
+/// masked by alpha, as height. A quick way to give plain sprites some relief; hand
+/// painted normal maps will look better.
+pub fn normal_map_from_height(image: &image::RgbaImage, strength: f32) -> image::RgbaImage {
+    let (width, height) = image.dimensions();
+    let height_at = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        let [r, g, b, a] = image.get_pixel(x, y).0.map(|c| c as f32 / 255.0);
+        (0.2126 * r + 0.7152 * g + 0.0722 * b) * a
+    };
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        // Image rows go down but world y goes up, hence the flipped y gradient.
+        let dx = (height_at(x + 1, y) - height_at(x - 1, y)) * strength;
+        let dy = (height_at(x, y - 1) - height_at(x, y + 1)) * strength;
+        let length = (dx * dx + dy * dy + 1.0).sqrt();
+        let encode = |n: f32| ((n / length * 0.5 + 0.5) * 255.0).round() as u8;
+        image::Rgba([encode(-dx), encode(-dy), encode(1.0), 255])
+    })
+}
@@ -0,0 +1,498 @@
+use std::sync::RwLock;
+
+use wgpu::util::DeviceExt;
+
+use crate::instancing::SpriteInstance;
+use crate::pipeline::{
+    BlendMode, DepthTest, PipelineDescriptor, PipelineError, PipelineRegistry, ShaderFiles,
+    TargetFormat,
+};
+use crate::shader::{Shader, ShaderError};
+use crate::text::{Font, TextVertex};
+use crate::texture::ImageTexture;
+use crate::vertex::{ArrayTexturedVertex, ColoredVertex, Instance, TexturedVertex, Vertex};
+
+pub const TEXTURED_SHADER: &str = "textured";
+pub const TEXTURED_ARRAY_SHADER: &str = "textured_array";
+pub const COLORED_SHADER: &str = "colored";
+pub const TEXTURED_LIT_SHADER: &str = "textured_lit";
+pub const TEXT_SHADER: &str = "text";
+pub const TEXTURED_INSTANCED_SHADER: &str = "textured_instanced";
+pub const COLORED_INSTANCED_SHADER: &str = "colored_instanced";
+
+/// The file under `src/shaders` each builtin shader is compiled from, for watching
+/// them and swapping edits in with [`PipelineRegistry::replace_shader`].
+pub const BUILTIN_SHADER_FILES: [ShaderFiles; 7] = [
+    ShaderFiles {
+        name: TEXTURED_SHADER,
+        files: &["textured.wgsl"],
+    },
+    ShaderFiles {
+        name: TEXTURED_ARRAY_SHADER,
+        files: &["textured_array.wgsl"],
+    },
+    ShaderFiles {
+        name: COLORED_SHADER,
+        files: &["colored.wgsl"],
+    },
+    ShaderFiles {
+        name: TEXTURED_LIT_SHADER,
+        files: &["textured_lit.wgsl"],
+    },
+    ShaderFiles {
+        name: TEXT_SHADER,
+        files: &["text.wgsl"],
+    },
+    ShaderFiles {
+        name: TEXTURED_INSTANCED_SHADER,
+        files: &["textured_instanced.wgsl"],
+    },
+    ShaderFiles {
+        name: COLORED_INSTANCED_SHADER,
+        files: &["colored_instanced.wgsl"],
+    },
+];
+
+/// Registers the shaders the engine ships with under the names above. Every one
+/// of them takes the camera at group 0 and [`MaterialParams`] as its last group.
+pub fn register_builtin_shaders(
+    device: &wgpu::Device,
+    registry: &mut PipelineRegistry,
+    camera_layout: &wgpu::BindGroupLayout,
+) -> Result<(), ShaderError> {
+    let params_layout = MaterialParams::bind_group_layout(device);
+    let texture_layout = ImageTexture::bind_group_layout(device);
+    let builtins = [
+        (
+            TEXTURED_SHADER,
+            include_str!("shaders/textured.wgsl"),
+            vec![texture_layout.clone()],
+        ),
+        (
+            TEXTURED_ARRAY_SHADER,
+            include_str!("shaders/textured_array.wgsl"),
+            vec![ImageTexture::array_bind_group_layout(device)],
+        ),
+        (COLORED_SHADER, include_str!("shaders/colored.wgsl"), vec![]),
+        (
+            TEXTURED_LIT_SHADER,
+            include_str!("shaders/textured_lit.wgsl"),
+            vec![texture_layout.clone(), texture_layout.clone()],
+        ),
+        (
+            TEXT_SHADER,
+            include_str!("shaders/text.wgsl"),
+            vec![texture_layout.clone()],
+        ),
+        (
+            TEXTURED_INSTANCED_SHADER,
+            include_str!("shaders/textured_instanced.wgsl"),
+            vec![texture_layout],
+        ),
+        (
+            COLORED_INSTANCED_SHADER,
+            include_str!("shaders/colored_instanced.wgsl"),
+            vec![],
+        ),
+    ];
+    for (name, source, resources) in builtins {
+        let shader = Shader::from_wgsl(device, name, source)?;
+        let mut layouts = vec![camera_layout.clone()];
+        layouts.extend(resources);
+        layouts.push(params_layout.clone());
+        registry.add_shader(device, name, shader, layouts);
+    }
+    Ok(())
+}
+
+/// Uniform parameters every material carries, bound as the shader's last group.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialParams {
+    pub tint: [f32; 4],
+}
+
+impl Default for MaterialParams {
+    fn default() -> Self {
+        Self {
+            tint: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl MaterialParams {
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Material Params Bind Group Layout"),
+        })
+    }
+}
+
+/// Owned counterpart of [`PipelineDescriptor`] that a material keeps around so its
+/// pipeline can be looked up again later.
+#[derive(Clone, Debug)]
+pub struct MaterialDescriptor {
+    pub shader: String,
+    pub vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    pub blend: BlendMode,
+    pub topology: wgpu::PrimitiveTopology,
+    /// Ignored when `target` has no depth attachment.
+    pub depth_test: DepthTest,
+    pub target: TargetFormat,
+}
+
+impl MaterialDescriptor {
+    /// An opaque triangle-list material.
+    pub fn new(
+        shader: &str,
+        vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+        target: TargetFormat,
+    ) -> Self {
+        Self {
+            shader: shader.to_string(),
+            vertex_layouts,
+            blend: BlendMode::Replace,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            depth_test: DepthTest::ReadWrite,
+            target,
+        }
+    }
+
+    pub fn pipeline_descriptor(&self) -> PipelineDescriptor<'_> {
+        PipelineDescriptor {
+            shader: &self.shader,
+            vertex_layouts: &self.vertex_layouts,
+            blend: self.blend,
+            topology: self.topology,
+            depth: self
+                .target
+                .depth
+                .map(|format| self.depth_test.depth_state(format)),
+            sample_count: self.target.sample_count,
+            format: self.target.color,
+        }
+    }
+}
+
+/// A pipeline bundled with the bind groups and parameters needed to draw with it.
+///
+/// Bind group 0 is reserved for the camera and is set by the renderer. The material
+/// owns every group after that: its resource groups in order, then its params.
+pub struct Material {
+    pub descriptor: MaterialDescriptor,
+    pipeline: RwLock<wgpu::RenderPipeline>,
+    bind_groups: RwLock<Vec<wgpu::BindGroup>>,
+    params_buffer: wgpu::Buffer,
+}
+
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        descriptor: MaterialDescriptor,
+        resources: Vec<wgpu::BindGroup>,
+        params: MaterialParams,
+    ) -> Result<Self, PipelineError> {
+        let pipeline = registry.get_or_create(device, &descriptor.pipeline_descriptor())?;
+        let layouts = registry
+            .bind_group_layouts(&descriptor.shader)
+            .ok_or_else(|| PipelineError::UnknownShader(descriptor.shader.clone()))?;
+        // Camera + resources + params.
+        let provided = resources.len() + 2;
+        if layouts.len() != provided {
+            return Err(PipelineError::BindGroupCount {
+                shader: descriptor.shader.clone(),
+                expected: layouts.len(),
+                found: provided,
+            });
+        }
+
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layouts[layouts.len() - 1],
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("Material Params Bind Group"),
+        });
+
+        let mut bind_groups = resources;
+        bind_groups.push(params_bind_group);
+        Ok(Self {
+            descriptor,
+            pipeline: RwLock::new(pipeline),
+            bind_groups: RwLock::new(bind_groups),
+            params_buffer,
+        })
+    }
+
+    /// A material drawing [`TexturedVertex`] meshes with `texture`.
+    pub fn textured(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        texture: &ImageTexture,
+        blend: BlendMode,
+        target: TargetFormat,
+    ) -> Result<Self, PipelineError> {
+        let layouts = registry
+            .bind_group_layouts(TEXTURED_SHADER)
+            .ok_or_else(|| PipelineError::UnknownShader(TEXTURED_SHADER.to_string()))?;
+        let texture_bind_group = texture.create_bind_group(device, &layouts[1]);
+        let descriptor = MaterialDescriptor {
+            blend,
+            depth_test: DepthTest::for_blend(blend),
+            ..MaterialDescriptor::new(TEXTURED_SHADER, vec![TexturedVertex::description()], target)
+        };
+        Self::new(
+            device,
+            registry,
+            descriptor,
+            vec![texture_bind_group],
+            MaterialParams::default(),
+        )
+    }
+
+    /// A material drawing [`ArrayTexturedVertex`] meshes from the layers of a texture
+    /// array, so sprites from many same-sized sheets can share one draw call.
+    pub fn textured_array(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        texture: &ImageTexture,
+        blend: BlendMode,
+        target: TargetFormat,
+    ) -> Result<Self, PipelineError> {
+        let layouts = registry
+            .bind_group_layouts(TEXTURED_ARRAY_SHADER)
+            .ok_or_else(|| PipelineError::UnknownShader(TEXTURED_ARRAY_SHADER.to_string()))?;
+        let texture_bind_group = texture.create_bind_group(device, &layouts[1]);
+        let descriptor = MaterialDescriptor {
+            blend,
+            depth_test: DepthTest::for_blend(blend),
+            ..MaterialDescriptor::new(
+                TEXTURED_ARRAY_SHADER,
+                vec![ArrayTexturedVertex::description()],
+                target,
+            )
+        };
+        Self::new(
+            device,
+            registry,
+            descriptor,
+            vec![texture_bind_group],
+            MaterialParams::default(),
+        )
+    }
+
+    /// A material drawing the normal map of a [`TexturedVertex`] sprite into the
+    /// normal buffer of a [`Lighting`](crate::lighting::Lighting) pass, masked by
+    /// the alpha of `diffuse`. The sprite's colour is still drawn by an ordinary
+    /// textured material. Load `normal_map` with `srgb: false`.
+    pub fn textured_lit(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        diffuse: &ImageTexture,
+        normal_map: &ImageTexture,
+        target: TargetFormat,
+    ) -> Result<Self, PipelineError> {
+        let layouts = registry
+            .bind_group_layouts(TEXTURED_LIT_SHADER)
+            .ok_or_else(|| PipelineError::UnknownShader(TEXTURED_LIT_SHADER.to_string()))?;
+        let resources = vec![
+            diffuse.create_bind_group(device, &layouts[1]),
+            normal_map.create_bind_group(device, &layouts[2]),
+        ];
+        let descriptor = MaterialDescriptor {
+            blend: BlendMode::Alpha,
+            depth_test: DepthTest::ReadOnly,
+            ..MaterialDescriptor::new(
+                TEXTURED_LIT_SHADER,
+                vec![TexturedVertex::description()],
+                target,
+            )
+        };
+        Self::new(
+            device,
+            registry,
+            descriptor,
+            resources,
+            MaterialParams::default(),
+        )
+    }
+
+    /// A material drawing [`TextVertex`] meshes laid out by `font`, sampling its
+    /// glyph atlas. Alpha blended without depth writes, so draw it after whatever
+    /// the text sits on.
+    pub fn text(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        font: &Font,
+        target: TargetFormat,
+    ) -> Result<Self, PipelineError> {
+        let layouts = registry
+            .bind_group_layouts(TEXT_SHADER)
+            .ok_or_else(|| PipelineError::UnknownShader(TEXT_SHADER.to_string()))?;
+        let texture_bind_group = font.texture().create_bind_group(device, &layouts[1]);
+        let descriptor = MaterialDescriptor {
+            blend: BlendMode::Alpha,
+            depth_test: DepthTest::for_blend(BlendMode::Alpha),
+            ..MaterialDescriptor::new(TEXT_SHADER, vec![TextVertex::description()], target)
+        };
+        Self::new(
+            device,
+            registry,
+            descriptor,
+            vec![texture_bind_group],
+            MaterialParams::default(),
+        )
+    }
+
+    /// A material drawing [`ColoredVertex`] meshes using their vertex colours.
+    pub fn colored(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        blend: BlendMode,
+        target: TargetFormat,
+    ) -> Result<Self, PipelineError> {
+        let descriptor = MaterialDescriptor {
+            blend,
+            depth_test: DepthTest::for_blend(blend),
+            ..MaterialDescriptor::new(COLORED_SHADER, vec![ColoredVertex::description()], target)
+        };
+        Self::new(
+            device,
+            registry,
+            descriptor,
+            Vec::new(),
+            MaterialParams::default(),
+        )
+    }
+
+    /// A material drawing [`TexturedVertex`] meshes with `texture`, one copy per
+    /// [`SpriteInstance`]. Draw it with [`Mesh::draw_instanced`](crate::mesh::Mesh::draw_instanced).
+    pub fn textured_instanced(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        texture: &ImageTexture,
+        blend: BlendMode,
+        target: TargetFormat,
+    ) -> Result<Self, PipelineError> {
+        let layouts = registry
+            .bind_group_layouts(TEXTURED_INSTANCED_SHADER)
+            .ok_or_else(|| PipelineError::UnknownShader(TEXTURED_INSTANCED_SHADER.to_string()))?;
+        let texture_bind_group = texture.create_bind_group(device, &layouts[1]);
+        let descriptor = MaterialDescriptor {
+            blend,
+            depth_test: DepthTest::for_blend(blend),
+            ..MaterialDescriptor::new(
+                TEXTURED_INSTANCED_SHADER,
+                vec![TexturedVertex::description(), SpriteInstance::description()],
+                target,
+            )
+        };
+        Self::new(
+            device,
+            registry,
+            descriptor,
+            vec![texture_bind_group],
+            MaterialParams::default(),
+        )
+    }
+
+    /// A material drawing [`ColoredVertex`] meshes using their vertex colours, one
+    /// copy per [`SpriteInstance`]. The instances' `uv_rect` is ignored.
+    pub fn colored_instanced(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        blend: BlendMode,
+        target: TargetFormat,
+    ) -> Result<Self, PipelineError> {
+        let descriptor = MaterialDescriptor {
+            blend,
+            depth_test: DepthTest::for_blend(blend),
+            ..MaterialDescriptor::new(
+                COLORED_INSTANCED_SHADER,
+                vec![ColoredVertex::description(), SpriteInstance::description()],
+                target,
+            )
+        };
+        Self::new(
+            device,
+            registry,
+            descriptor,
+            Vec::new(),
+            MaterialParams::default(),
+        )
+    }
+
+    pub fn pipeline(&self) -> wgpu::RenderPipeline {
+        self.pipeline.read().unwrap().clone()
+    }
+
+    /// Looks the material's pipeline up in `registry` again, picking up a shader
+    /// swapped in with [`PipelineRegistry::replace_shader`]. Takes `&self` so shared
+    /// materials can be reloaded where they are.
+    pub fn reload_pipeline(
+        &self,
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+    ) -> Result<(), PipelineError> {
+        let pipeline = registry.get_or_create(device, &self.descriptor.pipeline_descriptor())?;
+        *self.pipeline.write().unwrap() = pipeline;
+        Ok(())
+    }
+
+    /// Binds `texture` as resource group `group`, numbered as in the shader, so the
+    /// first texture of a textured material is group 1. Needed when a texture is
+    /// replaced rather than written to, such as after reloading it at a new size.
+    /// Takes `&self` so shared materials can be updated where they are.
+    ///
+    /// Panics if `group` isn't one of the material's resource groups.
+    pub fn set_texture(
+        &self,
+        device: &wgpu::Device,
+        registry: &PipelineRegistry,
+        group: usize,
+        texture: &ImageTexture,
+    ) -> Result<(), PipelineError> {
+        let layouts = registry
+            .bind_group_layouts(&self.descriptor.shader)
+            .ok_or_else(|| PipelineError::UnknownShader(self.descriptor.shader.clone()))?;
+        let mut bind_groups = self.bind_groups.write().unwrap();
+        // The last group holds the params rather than a resource.
+        assert!(
+            (1..bind_groups.len()).contains(&group),
+            "material '{}' has no resource group {group}",
+            self.descriptor.shader
+        );
+        bind_groups[group - 1] = texture.create_bind_group(device, &layouts[group]);
+        Ok(())
+    }
+
+    pub fn set_params(&self, queue: &wgpu::Queue, params: MaterialParams) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+    }
+
+    /// Sets the pipeline and every material-owned bind group. The caller is still
+    /// responsible for the camera at group 0.
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.pipeline.read().unwrap());
+        for (i, bind_group) in self.bind_groups.read().unwrap().iter().enumerate() {
+            render_pass.set_bind_group(i as u32 + 1, bind_group, &[]);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use wgpu::util::DeviceExt;
+
+use crate::instancing::InstanceBuffer;
+use crate::vertex::{Instance, Vertex};
+
+/// A vertex and index buffer pair uploaded to the GPU.
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+}
+
+impl Mesh {
+    pub fn new<V: Vertex>(
+        device: &wgpu::Device,
+        vertices: &[V],
+        indices: &[u16],
+        label: &str,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+        }
+    }
+
+    /// Replaces the contents, e.g. for geometry that is deformed every frame. The
+    /// buffers are only recreated when the new data doesn't fit.
+    pub fn write<V: Vertex>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertices: &[V],
+        indices: &[u16],
+    ) {
+        // Buffer writes must be a multiple of 4 bytes, so an odd index count is
+        // padded with an index that is never drawn.
+        let mut padded = indices.to_vec();
+        if padded.len() % 2 == 1 {
+            padded.push(0);
+        }
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(vertices);
+        let index_bytes: &[u8] = bytemuck::cast_slice(&padded);
+        if vertex_bytes.len() as wgpu::BufferAddress > self.vertex_buffer.size()
+            || index_bytes.len() as wgpu::BufferAddress > self.index_buffer.size()
+        {
+            *self = Self::new(device, vertices, &padded, "Dynamic Mesh");
+        } else {
+            queue.write_buffer(&self.vertex_buffer, 0, vertex_bytes);
+            queue.write_buffer(&self.index_buffer, 0, index_bytes);
+        }
+        self.num_indices = indices.len() as u32;
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        // Empty buffers can't be sliced, and there would be nothing to draw anyway.
+        if self.num_indices == 0 {
+            return;
+        }
+        // slice(..) specifies what part of the buffer to use (all of it).
+        // If we wanted to only use part of a buffer we could provide another slice object.
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+
+    /// Draws one copy of the mesh for every instance in `instances`, bound to the
+    /// second vertex buffer slot after the mesh's own vertices.
+    pub fn draw_instanced<I: Instance>(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        instances: &InstanceBuffer<I>,
+    ) {
+        if self.num_indices == 0 || instances.is_empty() {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, instances.slice());
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..instances.len());
+    }
+}
@@ -0,0 +1,262 @@
+use crate::pipeline::{
+    BlendMode, PipelineDescriptor, PipelineError, PipelineRegistry, ShaderFiles,
+};
+use crate::post_process::{PostEffectDescriptor, PostProcessChain};
+use crate::render_target::{RenderTarget, RenderTargetDescriptor};
+use crate::shader::Shader;
+use crate::vertex::Vertex;
+
+pub const METABALLS_FIELD_SHADER: &str = "metaballs/field";
+/// Name of the post effect [`Metaballs::new`] adds to the chain.
+pub const METABALLS_EFFECT: &str = "metaballs";
+
+/// The files under `src/shaders` the field shader and the shader of the effect are
+/// put together from. Swap edits in with [`PipelineRegistry::replace_shader`], then
+/// call [`Metaballs::reload_pipelines`] and [`PostProcessChain::reload_pipelines`].
+pub const METABALLS_SHADER_FILES: [ShaderFiles; 2] = [
+    ShaderFiles {
+        name: METABALLS_FIELD_SHADER,
+        files: &["metaballs/field.wgsl"],
+    },
+    ShaderFiles {
+        name: "post/metaballs",
+        files: &["post/common.wgsl", "metaballs/composite.wgsl"],
+    },
+];
+
+/// Floating point so fields from many overlapping blobs can sum well past 1.
+const FIELD_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+crate::vertex! {
+    /// One blob of the field, drawn as an instance. Its influence reaches twice
+    /// `radius`, and where the fields of nearby blobs add up past the threshold they
+    /// merge into one bubble.
+    #[derive(PartialEq)]
+    pub struct Blob {
+        pub position: [f32; 2],
+        pub radius: f32,
+        pub color: [f32; 3],
+    }
+}
+
+impl Blob {
+    pub fn new(position: [f32; 2], radius: f32, color: [f32; 3]) -> Self {
+        Self {
+            position,
+            radius,
+            color,
+        }
+    }
+}
+
+/// Look of the bubbles, the `params` of the [`METABALLS_EFFECT`] post effect.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MetaballParams {
+    /// Colour of the rim just inside the edge, with alpha as its opacity.
+    pub rim_color: [f32; 4],
+    /// Field strength at which the surface sits. The default puts the edge of a lone
+    /// blob at its radius.
+    pub threshold: f32,
+    /// Width of the anti-aliased edge, in field units either side of `threshold`.
+    pub smoothness: f32,
+    /// How far the background is displaced where the surface is steepest, in pixels.
+    pub refraction: f32,
+    pub highlight: f32,
+    /// How much the blobs' colours tint what is seen through them, from 0 to 1.
+    pub tint: f32,
+    /// Width of the rim, in field units inside `threshold`.
+    pub rim_width: f32,
+    pub _pad: [f32; 2],
+}
+
+impl Default for MetaballParams {
+    fn default() -> Self {
+        Self {
+            rim_color: [1.0, 1.0, 1.0, 0.6],
+            // (1 - (1/2)^2)^2, the field at half of a blob's reach.
+            threshold: 0.5625,
+            smoothness: 0.03,
+            refraction: 12.0,
+            highlight: 0.8,
+            tint: 0.5,
+            rim_width: 0.15,
+            _pad: [0.0; 2],
+        }
+    }
+}
+
+/// Soft bubbles that merge as they touch and split as they pull apart.
+///
+/// Every blob adds a smooth falloff to a floating point field target in one
+/// instanced draw. The [`METABALLS_EFFECT`] post effect then thresholds that field
+/// over the scene, refracting what is behind the bubbles and adding a rim and a
+/// highlight, so it needs the [`PostProcessChain`] to be active.
+pub struct Metaballs {
+    pub blobs: Vec<Blob>,
+    field_target: RenderTarget,
+    pipeline: wgpu::RenderPipeline,
+    instance_buffer: wgpu::Buffer,
+}
+
+impl Metaballs {
+    /// Creates the field target and adds the [`METABALLS_EFFECT`] to the end of
+    /// `chain`, enabled, with [`MetaballParams::default`].
+    pub fn new(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        camera_layout: &wgpu::BindGroupLayout,
+        chain: &mut PostProcessChain,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, PipelineError> {
+        if registry.shader(METABALLS_FIELD_SHADER).is_none() {
+            let shader = Shader::from_wgsl(
+                device,
+                METABALLS_FIELD_SHADER,
+                include_str!("shaders/metaballs/field.wgsl"),
+            )?;
+            registry.add_shader(
+                device,
+                METABALLS_FIELD_SHADER,
+                shader,
+                vec![camera_layout.clone()],
+            );
+        }
+        let pipeline = Self::create_pipeline(device, registry)?;
+
+        let field_target = RenderTarget::new(
+            device,
+            &RenderTargetDescriptor {
+                format: FIELD_FORMAT,
+                ..RenderTargetDescriptor::new("Metaball Field", width, height)
+            },
+        );
+        chain.add_effect(
+            device,
+            registry,
+            &PostEffectDescriptor {
+                name: METABALLS_EFFECT,
+                source: include_str!("shaders/metaballs/composite.wgsl"),
+                params: bytemuck::bytes_of(&MetaballParams::default()),
+                texture: Some(&field_target.color),
+            },
+        )?;
+
+        Ok(Self {
+            blobs: Vec::new(),
+            field_target,
+            pipeline,
+            instance_buffer: Self::create_instance_buffer(device, 64),
+        })
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+    ) -> Result<wgpu::RenderPipeline, PipelineError> {
+        let instance_layout = wgpu::VertexBufferLayout {
+            step_mode: wgpu::VertexStepMode::Instance,
+            ..Blob::description()
+        };
+        registry.get_or_create(
+            device,
+            &PipelineDescriptor {
+                shader: METABALLS_FIELD_SHADER,
+                vertex_layouts: &[instance_layout],
+                blend: BlendMode::Accumulate,
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                depth: None,
+                sample_count: 1,
+                format: FIELD_FORMAT,
+            },
+        )
+    }
+
+    /// Picks up the field pipeline rebuilt by [`PipelineRegistry::replace_shader`].
+    /// The effect's pipeline is reloaded by [`PostProcessChain::reload_pipelines`].
+    pub fn reload_pipelines(
+        &mut self,
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+    ) -> Result<(), PipelineError> {
+        self.pipeline = Self::create_pipeline(device, registry)?;
+        Ok(())
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Blob Buffer"),
+            size: (capacity * std::mem::size_of::<Blob>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// The summed field of the last [`Metaballs::render_field`]: colour weighted by
+    /// field strength in rgb and the strength itself in alpha.
+    pub fn field_target(&self) -> &RenderTarget {
+        &self.field_target
+    }
+
+    /// Recreates the field target at the new size and points the effect in `chain`
+    /// at it. Call alongside [`PostProcessChain::resize`].
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        chain: &mut PostProcessChain,
+        width: u32,
+        height: u32,
+    ) {
+        self.field_target.resize(device, width, height);
+        chain.set_effect_texture(device, METABALLS_EFFECT, &self.field_target.color);
+    }
+
+    /// Sets the look of the bubbles on the effect in `chain`.
+    pub fn set_params(
+        &self,
+        queue: &wgpu::Queue,
+        chain: &PostProcessChain,
+        params: &MetaballParams,
+    ) {
+        if let Some(effect) = chain.effect(METABALLS_EFFECT) {
+            effect.set_params(queue, params);
+        }
+    }
+
+    /// Whether the effect in `chain` is enabled. Skip [`Metaballs::render_field`]
+    /// while it isn't.
+    pub fn is_enabled(&self, chain: &PostProcessChain) -> bool {
+        chain
+            .effect(METABALLS_EFFECT)
+            .is_some_and(|effect| effect.enabled)
+    }
+
+    /// Accumulates [`Metaballs::blobs`] into the field target, to be picked up by the
+    /// effect when the chain next runs.
+    pub fn render_field(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera_bind_group: &wgpu::BindGroup,
+    ) {
+        let needed = std::mem::size_of_val(self.blobs.as_slice()) as wgpu::BufferAddress;
+        if needed > self.instance_buffer.size() {
+            self.instance_buffer =
+                Self::create_instance_buffer(device, self.blobs.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.blobs));
+
+        let mut render_pass = self
+            .field_target
+            .begin_pass(encoder, Some(wgpu::Color::TRANSPARENT));
+        if self.blobs.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..needed));
+        render_pass.draw(0..6, 0..self.blobs.len() as u32);
+    }
+}
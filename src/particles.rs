@@ -0,0 +1,764 @@
+use bytemuck::Zeroable;
+use wgpu::util::DeviceExt;
+
+use crate::lerp::Lerp;
+use crate::material::{Material, MaterialDescriptor, MaterialParams};
+use crate::pipeline::{
+    BlendMode, DepthTest, PipelineError, PipelineRegistry, ShaderFiles, TargetFormat,
+};
+use crate::shader::{Shader, ShaderError};
+use crate::texture::ImageTexture;
+use crate::vertex::Vertex;
+
+pub const PARTICLES_TEXTURED_SHADER: &str = "particles/textured";
+pub const PARTICLES_SDF_SHADER: &str = "particles/sdf";
+const PARTICLES_SIMULATE_SHADER: &str = "particles/simulate";
+/// The file under `src/shaders` the GPU backend's compute shader is compiled from.
+/// It isn't in the [`PipelineRegistry`]; swap edits in with
+/// [`ParticleSystem::reload_simulation`].
+pub const PARTICLES_SIMULATE_FILE: &str = "particles/simulate.wgsl";
+
+/// The files under `src/shaders` each particle shader is put together from. Swap
+/// edits to the render shaders in with [`PipelineRegistry::replace_shader`], then
+/// call [`Material::reload_pipeline`] on [`ParticleSystem::material`].
+pub const PARTICLE_SHADER_FILES: [ShaderFiles; 2] = [
+    ShaderFiles {
+        name: PARTICLES_TEXTURED_SHADER,
+        files: &["particles/common.wgsl", "particles/textured.wgsl"],
+    },
+    ShaderFiles {
+        name: PARTICLES_SDF_SHADER,
+        files: &["particles/common.wgsl", "particles/sdf.wgsl"],
+    },
+];
+
+/// Number of evenly spaced samples curves are baked into for the GPU. Matches
+/// `CURVE_SAMPLES` in `shaders/particles/common.wgsl`.
+const CURVE_SAMPLES: usize = 32;
+const WORKGROUP_SIZE: u32 = 64;
+
+crate::vertex! {
+    /// One particle as stored in the simulation buffer. Live while `age` is below
+    /// `lifetime`; a zeroed particle is dead.
+    #[derive(PartialEq)]
+    pub struct Particle {
+        pub position: [f32; 2],
+        pub velocity: [f32; 2],
+        pub age: f32,
+        pub lifetime: f32,
+        _pad: [f32; 2],
+    }
+}
+
+impl Particle {
+    pub fn new(position: [f32; 2], velocity: [f32; 2], lifetime: f32) -> Self {
+        Self {
+            position,
+            velocity,
+            age: 0.0,
+            lifetime,
+            _pad: [0.0; 2],
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    /// How far through its life the particle is, from 0 to 1.
+    pub fn normalized_age(&self) -> f32 {
+        (self.age / self.lifetime.max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+}
+
+/// Where new particles appear and which way they head off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitterShape {
+    /// From the emitter position in every direction.
+    Point,
+    /// From anywhere inside the circle, heading away from its centre.
+    Circle { radius: f32 },
+    /// From the emitter position, within `angle` radians either side of `direction`.
+    Cone { direction: [f32; 2], angle: f32 },
+    /// From anywhere along the line between two points relative to the emitter
+    /// position, heading out of its left side.
+    Line { start: [f32; 2], end: [f32; 2] },
+}
+
+/// A value that changes over a particle's life, linearly interpolated between keys
+/// at normalized ages from 0 to 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Lerp> Curve<T> {
+    /// A curve through `keys`, given as `(age, value)` pairs. They are sorted by age.
+    /// Before the first key and after the last the curve holds their values.
+    ///
+    /// Panics if `keys` is empty.
+    pub fn new(mut keys: Vec<(f32, T)>) -> Self {
+        assert!(!keys.is_empty(), "a curve needs at least one key");
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { keys }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(vec![(0.0, value)])
+    }
+
+    /// Straight from `start` at birth to `end` at death.
+    pub fn linear(start: T, end: T) -> Self {
+        Self::new(vec![(0.0, start), (1.0, end)])
+    }
+
+    pub fn sample(&self, t: f32) -> T {
+        let next = self.keys.partition_point(|&(age, _)| age <= t);
+        match next {
+            0 => self.keys[0].1,
+            n if n == self.keys.len() => self.keys[n - 1].1,
+            n => {
+                let (start_age, start) = self.keys[n - 1];
+                let (end_age, end) = self.keys[n];
+                start.lerp(end, (t - start_age) / (end_age - start_age))
+            }
+        }
+    }
+
+    fn bake(&self) -> [T; CURVE_SAMPLES] {
+        std::array::from_fn(|i| self.sample(i as f32 / (CURVE_SAMPLES - 1) as f32))
+    }
+}
+
+/// Everything about how an emitter spawns and moves its particles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmitterDescriptor {
+    pub shape: EmitterShape,
+    /// World position of the emitter. Move it to have particles trail behind.
+    pub position: [f32; 2],
+    /// Particles spawned per second.
+    pub rate: f32,
+    /// Size of the particle pool, fixed once the emitter is created. When it is full
+    /// the oldest slots are reused, so make it at least `rate` times the longest
+    /// lifetime.
+    pub max_particles: u32,
+    /// Seconds each particle lives, picked at random between the two.
+    pub lifetime: [f32; 2],
+    /// Initial speed, picked at random between the two.
+    pub speed: [f32; 2],
+    /// Added to the velocity every second along the direction of travel. Negative
+    /// values slow particles down.
+    pub acceleration: f32,
+    /// Added to the velocity every second. Point it up for rising bubbles.
+    pub gravity: [f32; 2],
+    /// Fraction of velocity lost per second, applied exponentially.
+    pub drag: f32,
+    pub color: Curve<[f32; 4]>,
+    /// Width of the particle in world units.
+    pub size: Curve<f32>,
+}
+
+impl Default for EmitterDescriptor {
+    fn default() -> Self {
+        Self {
+            shape: EmitterShape::Point,
+            position: [0.0, 0.0],
+            rate: 20.0,
+            max_particles: 256,
+            lifetime: [1.0, 2.0],
+            speed: [0.2, 0.4],
+            acceleration: 0.0,
+            gravity: [0.0, 0.0],
+            drag: 0.0,
+            color: Curve::constant([1.0, 1.0, 1.0, 1.0]),
+            size: Curve::constant(0.05),
+        }
+    }
+}
+
+/// A small xorshift generator, so emitters are reproducible from a seed without
+/// pulling in a dependency.
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Zero is the one state xorshift can't leave.
+        Self(seed.max(1))
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, [min, max]: [f32; 2]) -> f32 {
+        min.lerp(max, self.next_f32())
+    }
+}
+
+/// A particle written into slot `slot` of the pool.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spawn {
+    pub slot: usize,
+    pub particle: Particle,
+}
+
+/// Decides when and where particles are born. Given the same seed and the same
+/// sequence of calls it always spawns the same particles.
+#[derive(Clone, Debug)]
+pub struct Emitter {
+    pub descriptor: EmitterDescriptor,
+    /// Stops rate based spawning while still allowing bursts.
+    pub paused: bool,
+    rng: Rng,
+    accumulator: f32,
+    pending_burst: u32,
+    next_slot: usize,
+    capacity: usize,
+}
+
+impl Emitter {
+    pub fn new(descriptor: EmitterDescriptor, seed: u64) -> Self {
+        Self {
+            paused: false,
+            rng: Rng::new(seed),
+            accumulator: 0.0,
+            pending_burst: 0,
+            next_slot: 0,
+            capacity: descriptor.max_particles.max(1) as usize,
+            descriptor,
+        }
+    }
+
+    /// Number of slots in the pool.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Spawns `count` extra particles on the next [`Emitter::emit`].
+    pub fn burst(&mut self, count: u32) {
+        self.pending_burst += count;
+    }
+
+    /// The particles born over the next `dt` seconds, in consecutive pool slots
+    /// that wrap around at [`Emitter::capacity`].
+    pub fn emit(&mut self, dt: f32) -> Vec<Spawn> {
+        if !self.paused {
+            self.accumulator += self.descriptor.rate * dt;
+        }
+        let from_rate = self.accumulator.floor();
+        self.accumulator -= from_rate;
+        let count = (from_rate as usize + self.pending_burst as usize).min(self.capacity);
+        self.pending_burst = 0;
+
+        (0..count)
+            .map(|_| {
+                let slot = self.next_slot;
+                self.next_slot = (slot + 1) % self.capacity;
+                Spawn {
+                    slot,
+                    particle: self.spawn_particle(),
+                }
+            })
+            .collect()
+    }
+
+    fn spawn_particle(&mut self) -> Particle {
+        let desc = &self.descriptor;
+        let rng = &mut self.rng;
+        let [x, y] = desc.position;
+        let random_direction = |rng: &mut Rng| {
+            let angle = rng.next_f32() * std::f32::consts::TAU;
+            [angle.cos(), angle.sin()]
+        };
+        let (position, direction) = match desc.shape {
+            EmitterShape::Point => (desc.position, random_direction(rng)),
+            EmitterShape::Circle { radius } => {
+                let direction = random_direction(rng);
+                // sqrt keeps the spread even across the area rather than bunched
+                // up in the middle.
+                let distance = radius * rng.next_f32().sqrt();
+                (
+                    [x + direction[0] * distance, y + direction[1] * distance],
+                    direction,
+                )
+            }
+            EmitterShape::Cone { direction, angle } => {
+                let base = direction[1].atan2(direction[0]);
+                let angle = base + (rng.next_f32() * 2.0 - 1.0) * angle;
+                (desc.position, [angle.cos(), angle.sin()])
+            }
+            EmitterShape::Line { start, end } => {
+                let [px, py] = start.lerp(end, rng.next_f32());
+                let [dx, dy] = [end[0] - start[0], end[1] - start[1]];
+                let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+                ([x + px, y + py], [-dy / length, dx / length])
+            }
+        };
+        let speed = rng.range(desc.speed);
+        let lifetime = rng.range(desc.lifetime);
+        Particle::new(
+            position,
+            [direction[0] * speed, direction[1] * speed],
+            lifetime,
+        )
+    }
+
+    fn simulation_params(&self, dt: f32) -> SimulationParams {
+        SimulationParams {
+            gravity: self.descriptor.gravity,
+            dt,
+            drag: self.descriptor.drag,
+            acceleration: self.descriptor.acceleration,
+            count: self.capacity as u32,
+            _pad: [0.0; 2],
+        }
+    }
+}
+
+/// Matches `SimulationParams` in `shaders/particles/simulate.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SimulationParams {
+    gravity: [f32; 2],
+    dt: f32,
+    drag: f32,
+    acceleration: f32,
+    count: u32,
+    _pad: [f32; 2],
+}
+
+/// Advances every live particle by `dt` seconds under `descriptor`'s forces.
+///
+/// The CPU counterpart of `shaders/particles/simulate.wgsl`, used by
+/// [`SimulationBackend::Cpu`]. It produces the same result, which makes it a
+/// convenient way to check emitter settings without a GPU.
+pub fn simulate(particles: &mut [Particle], descriptor: &EmitterDescriptor, dt: f32) {
+    let damping = (-descriptor.drag * dt).exp();
+    for particle in particles.iter_mut().filter(|p| p.is_alive()) {
+        let [vx, vy] = particle.velocity;
+        let mut acceleration = descriptor.gravity;
+        let speed = (vx * vx + vy * vy).sqrt();
+        if speed > 1e-5 {
+            acceleration[0] += vx / speed * descriptor.acceleration;
+            acceleration[1] += vy / speed * descriptor.acceleration;
+        }
+        let velocity = [
+            (vx + acceleration[0] * dt) * damping,
+            (vy + acceleration[1] * dt) * damping,
+        ];
+        particle.velocity = velocity;
+        particle.position[0] += velocity[0] * dt;
+        particle.position[1] += velocity[1] * dt;
+        particle.age += dt;
+    }
+}
+
+/// Where particles are simulated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationBackend {
+    /// A compute shader updates the particle buffer in place.
+    Gpu,
+    /// [`simulate`] runs on the CPU and the whole pool is uploaded every frame.
+    /// For adapters without compute shaders, such as WebGL.
+    Cpu,
+}
+
+impl SimulationBackend {
+    /// `Gpu` when the adapter can run compute shaders, `Cpu` otherwise.
+    pub fn detect(adapter: &wgpu::Adapter) -> Self {
+        let flags = adapter.get_downlevel_capabilities().flags;
+        if flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+            SimulationBackend::Gpu
+        } else {
+            SimulationBackend::Cpu
+        }
+    }
+}
+
+/// Shapes SDF particles can be drawn as, resolution independent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SdfShape {
+    Circle = 0,
+    /// A see-through disc with a bright rim and a highlight.
+    Bubble = 1,
+}
+
+/// How each particle is drawn.
+#[derive(Clone, Copy)]
+pub enum ParticleStyle<'a> {
+    /// The texture stretched over the particle's quad, multiplied by its colour.
+    Textured(&'a ImageTexture),
+    /// A shape computed in the fragment shader. `softness` is how much of the
+    /// radius the edge fades over, from 0 to 1.
+    Sdf { shape: SdfShape, softness: f32 },
+}
+
+/// Matches `ParticleLook` in `shaders/particles/common.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleLook {
+    colors: [[f32; 4]; CURVE_SAMPLES],
+    sizes: [f32; CURVE_SAMPLES],
+    shape: u32,
+    softness: f32,
+    _pad: [f32; 2],
+}
+
+impl ParticleLook {
+    fn new(descriptor: &EmitterDescriptor, style: &ParticleStyle) -> Self {
+        let (shape, softness) = match *style {
+            ParticleStyle::Textured(_) => (0, 0.0),
+            ParticleStyle::Sdf { shape, softness } => (shape as u32, softness.clamp(1e-3, 1.0)),
+        };
+        Self {
+            colors: descriptor.color.bake(),
+            sizes: descriptor.size.bake(),
+            shape,
+            softness,
+            _pad: [0.0; 2],
+        }
+    }
+}
+
+/// Registers the particle render shaders under [`PARTICLES_TEXTURED_SHADER`] and
+/// [`PARTICLES_SDF_SHADER`]. Done by [`ParticleSystem::new`] the first time it is
+/// needed.
+pub fn register_particle_shaders(
+    device: &wgpu::Device,
+    registry: &mut PipelineRegistry,
+    camera_layout: &wgpu::BindGroupLayout,
+) -> Result<(), ShaderError> {
+    let look_layout = look_bind_group_layout(device);
+    let params_layout = MaterialParams::bind_group_layout(device);
+    let common = include_str!("shaders/particles/common.wgsl");
+    let shaders = [
+        (
+            PARTICLES_TEXTURED_SHADER,
+            include_str!("shaders/particles/textured.wgsl"),
+            Some(ImageTexture::bind_group_layout(device)),
+        ),
+        (
+            PARTICLES_SDF_SHADER,
+            include_str!("shaders/particles/sdf.wgsl"),
+            None,
+        ),
+    ];
+    for (name, source, texture_layout) in shaders {
+        let shader = Shader::from_wgsl(device, name, &format!("{common}\n{source}"))?;
+        let mut layouts = vec![camera_layout.clone(), look_layout.clone()];
+        layouts.extend(texture_layout);
+        layouts.push(params_layout.clone());
+        registry.add_shader(device, name, shader, layouts);
+    }
+    Ok(())
+}
+
+fn look_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+        label: Some("Particle Look Bind Group Layout"),
+    })
+}
+
+/// Everything needed to create a [`ParticleSystem`].
+pub struct ParticleSystemDescriptor<'a> {
+    pub emitter: EmitterDescriptor,
+    pub style: ParticleStyle<'a>,
+    pub blend: BlendMode,
+    pub target: TargetFormat,
+    pub backend: SimulationBackend,
+    /// Seeds the emitter, so the same seed replays the same particles.
+    pub seed: u64,
+}
+
+struct GpuSimulation {
+    pipeline: wgpu::ComputePipeline,
+    pipeline_layout: wgpu::PipelineLayout,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+}
+
+/// An [`Emitter`] together with the GPU resources to simulate and draw its
+/// particles.
+///
+/// Call [`ParticleSystem::update`] once a frame before the render pass, then
+/// [`ParticleSystem::draw`] inside it with the camera bound at group 0.
+pub struct ParticleSystem {
+    pub emitter: Emitter,
+    backend: SimulationBackend,
+    material: Material,
+    look_buffer: wgpu::Buffer,
+    particle_buffer: wgpu::Buffer,
+    /// The pool as simulated on the CPU. Empty with the GPU backend.
+    cpu_particles: Vec<Particle>,
+    gpu: Option<GpuSimulation>,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        camera_layout: &wgpu::BindGroupLayout,
+        desc: ParticleSystemDescriptor,
+    ) -> Result<Self, PipelineError> {
+        if registry.shader(PARTICLES_SDF_SHADER).is_none() {
+            register_particle_shaders(device, registry, camera_layout)?;
+        }
+
+        let max_particles = desc.emitter.max_particles.max(1) as usize;
+        let mut usage = wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST;
+        if desc.backend == SimulationBackend::Gpu {
+            usage |= wgpu::BufferUsages::STORAGE;
+        }
+        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Buffer"),
+            contents: bytemuck::cast_slice(&vec![Particle::zeroed(); max_particles]),
+            usage,
+        });
+        let look_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Look Buffer"),
+            contents: bytemuck::bytes_of(&ParticleLook::new(&desc.emitter, &desc.style)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let (shader, texture) = match desc.style {
+            ParticleStyle::Textured(texture) => (PARTICLES_TEXTURED_SHADER, Some(texture)),
+            ParticleStyle::Sdf { .. } => (PARTICLES_SDF_SHADER, None),
+        };
+        let layouts = registry
+            .bind_group_layouts(shader)
+            .ok_or_else(|| PipelineError::UnknownShader(shader.to_string()))?;
+        let mut resources = vec![device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layouts[1],
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: look_buffer.as_entire_binding(),
+            }],
+            label: Some("Particle Look Bind Group"),
+        })];
+        resources.extend(texture.map(|texture| texture.create_bind_group(device, &layouts[2])));
+        let instance_layout = wgpu::VertexBufferLayout {
+            step_mode: wgpu::VertexStepMode::Instance,
+            ..Particle::description()
+        };
+        let material = Material::new(
+            device,
+            registry,
+            MaterialDescriptor {
+                blend: desc.blend,
+                depth_test: DepthTest::for_blend(desc.blend),
+                ..MaterialDescriptor::new(shader, vec![instance_layout], desc.target)
+            },
+            resources,
+            MaterialParams::default(),
+        )?;
+
+        let gpu = match desc.backend {
+            SimulationBackend::Gpu => Some(Self::create_gpu_simulation(device, &particle_buffer)?),
+            SimulationBackend::Cpu => None,
+        };
+        let cpu_particles = match desc.backend {
+            SimulationBackend::Gpu => Vec::new(),
+            SimulationBackend::Cpu => vec![Particle::zeroed(); max_particles],
+        };
+        Ok(Self {
+            emitter: Emitter::new(desc.emitter, desc.seed),
+            backend: desc.backend,
+            material,
+            look_buffer,
+            particle_buffer,
+            cpu_particles,
+            gpu,
+        })
+    }
+
+    fn create_gpu_simulation(
+        device: &wgpu::Device,
+        particle_buffer: &wgpu::Buffer,
+    ) -> Result<GpuSimulation, ShaderError> {
+        let shader = Shader::from_wgsl(
+            device,
+            PARTICLES_SIMULATE_SHADER,
+            include_str!("shaders/particles/simulate.wgsl"),
+        )?;
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Storage { read_only: false }),
+                entry(1, wgpu::BufferBindingType::Uniform),
+            ],
+            label: Some("Particle Simulation Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Simulation Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_simulation_pipeline(device, &pipeline_layout, &shader);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Simulation Params"),
+            size: std::mem::size_of::<SimulationParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Particle Simulation Bind Group"),
+        });
+        Ok(GpuSimulation {
+            pipeline,
+            pipeline_layout,
+            bind_group,
+            params_buffer,
+        })
+    }
+
+    fn create_simulation_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &Shader,
+    ) -> wgpu::ComputePipeline {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle Simulation Pipeline"),
+            layout: Some(layout),
+            module: &shader.module,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        })
+    }
+
+    /// Swaps the GPU simulation over to `shader`, an edited
+    /// [`PARTICLES_SIMULATE_FILE`]. If wgpu rejects it the old simulation
+    /// keeps running and the error is returned. Does nothing with the CPU backend.
+    pub fn reload_simulation(
+        &mut self,
+        device: &wgpu::Device,
+        shader: &Shader,
+    ) -> Result<(), PipelineError> {
+        let Some(gpu) = &mut self.gpu else {
+            return Ok(());
+        };
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = Self::create_simulation_pipeline(device, &gpu.pipeline_layout, shader);
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(PipelineError::Rejected {
+                shader: PARTICLES_SIMULATE_SHADER.to_string(),
+                message: error.to_string(),
+            });
+        }
+        gpu.pipeline = pipeline;
+        Ok(())
+    }
+
+    pub fn backend(&self) -> SimulationBackend {
+        self.backend
+    }
+
+    /// The particle pool, when simulated on the CPU.
+    pub fn cpu_particles(&self) -> Option<&[Particle]> {
+        (self.backend == SimulationBackend::Cpu).then_some(self.cpu_particles.as_slice())
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Re-uploads the colour and size curves after changing them on
+    /// `emitter.descriptor`.
+    pub fn set_style(&self, queue: &wgpu::Queue, style: &ParticleStyle) {
+        let look = ParticleLook::new(&self.emitter.descriptor, style);
+        queue.write_buffer(&self.look_buffer, 0, bytemuck::bytes_of(&look));
+    }
+
+    /// Spawns new particles and advances the simulation by `dt` seconds. With the
+    /// GPU backend the work is recorded into `encoder`.
+    pub fn update(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, dt: f32) {
+        let spawns = self.emitter.emit(dt);
+        let params = self.emitter.simulation_params(dt);
+        match &self.gpu {
+            Some(gpu) => {
+                // Spawns fill consecutive slots, so at most two writes are needed:
+                // up to the end of the pool and then from its start.
+                let particles: Vec<Particle> = spawns.iter().map(|s| s.particle).collect();
+                if let Some(first) = spawns.first() {
+                    let to_end = particles.len().min(params.count as usize - first.slot);
+                    let stride = std::mem::size_of::<Particle>() as wgpu::BufferAddress;
+                    queue.write_buffer(
+                        &self.particle_buffer,
+                        first.slot as wgpu::BufferAddress * stride,
+                        bytemuck::cast_slice(&particles[..to_end]),
+                    );
+                    if to_end < particles.len() {
+                        queue.write_buffer(
+                            &self.particle_buffer,
+                            0,
+                            bytemuck::cast_slice(&particles[to_end..]),
+                        );
+                    }
+                }
+                queue.write_buffer(&gpu.params_buffer, 0, bytemuck::bytes_of(&params));
+
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Particle Simulation"),
+                    timestamp_writes: None,
+                });
+                compute_pass.set_pipeline(&gpu.pipeline);
+                compute_pass.set_bind_group(0, &gpu.bind_group, &[]);
+                compute_pass.dispatch_workgroups(params.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+            }
+            None => {
+                for spawn in spawns {
+                    self.cpu_particles[spawn.slot] = spawn.particle;
+                }
+                simulate(&mut self.cpu_particles, &self.emitter.descriptor, dt);
+                queue.write_buffer(
+                    &self.particle_buffer,
+                    0,
+                    bytemuck::cast_slice(&self.cpu_particles),
+                );
+            }
+        }
+    }
+
+    /// Draws the whole pool. Dead particles cost a vertex shader invocation each but
+    /// no fragments. The caller binds the camera at group 0.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        self.material.bind(render_pass);
+        render_pass.set_vertex_buffer(0, self.particle_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.emitter.capacity() as u32);
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use wgpu::{BlendComponent, PipelineCompilationOptions};
+
+use crate::shader::{Shader, ShaderError};
+
+/// How a material's fragments are combined with what is already in the target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Overwrite the destination. Alpha is ignored.
+    #[default]
+    Replace,
+    /// Classic `src * a + dst * (1 - a)` transparency for straight-alpha textures.
+    Alpha,
+    /// `src + dst * (1 - a)` for textures whose colour is already multiplied by alpha.
+    /// Avoids the dark fringes `Alpha` produces around filtered edges.
+    PremultipliedAlpha,
+    /// Adds the source on top of the destination, weighted by source alpha.
+    Additive,
+    /// Darkens the destination by the source colour. Expects premultiplied colour.
+    Multiply,
+    /// Lightens the destination, the inverse of `Multiply`. Expects premultiplied colour.
+    Screen,
+    /// Adds the source to the destination in every channel, alpha included. For
+    /// summing values into floating point targets rather than compositing colour.
+    Accumulate,
+}
+
+impl BlendMode {
+    /// Whether draws using this mode depend on what is behind them and so have to be
+    /// sorted back to front.
+    pub fn is_transparent(self) -> bool {
+        self != BlendMode::Replace
+    }
+
+    pub fn blend_state(self) -> wgpu::BlendState {
+        use wgpu::BlendFactor::*;
+        let component = |src_factor, dst_factor| BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: wgpu::BlendOperation::Add,
+        };
+        // Every transparent mode composites coverage the same way.
+        let over = component(One, OneMinusSrcAlpha);
+        match self {
+            BlendMode::Replace => wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::PremultipliedAlpha => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendState {
+                color: component(SrcAlpha, One),
+                alpha: component(Zero, One),
+            },
+            // src * dst + dst * (1 - a), i.e. fully transparent texels leave dst alone.
+            BlendMode::Multiply => wgpu::BlendState {
+                color: component(Dst, OneMinusSrcAlpha),
+                alpha: over,
+            },
+            BlendMode::Accumulate => wgpu::BlendState {
+                color: component(One, One),
+                alpha: component(One, One),
+            },
+            // src + dst - src * dst
+            BlendMode::Screen => wgpu::BlendState {
+                color: component(One, OneMinusSrc),
+                alpha: over,
+            },
+        }
+    }
+}
+
+/// The hashable subset of `wgpu::DepthStencilState` that materials are allowed to vary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DepthState {
+    pub format: wgpu::TextureFormat,
+    pub write_enabled: bool,
+    pub compare: wgpu::CompareFunction,
+}
+
+impl DepthState {
+    fn depth_stencil_state(self) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: self.format,
+            depth_write_enabled: self.write_enabled,
+            depth_compare: self.compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+}
+
+/// How a material interacts with the depth buffer, when there is one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DepthTest {
+    /// Always drawn, never occludes anything.
+    Disabled,
+    /// Hidden behind nearer geometry but doesn't occlude. Used for transparent draws.
+    ReadOnly,
+    #[default]
+    ReadWrite,
+}
+
+impl DepthTest {
+    /// Opaque draws write depth. Transparent ones only test against it so they
+    /// don't hide whatever is blended in behind them later in the frame.
+    pub fn for_blend(blend: BlendMode) -> Self {
+        if blend.is_transparent() {
+            DepthTest::ReadOnly
+        } else {
+            DepthTest::ReadWrite
+        }
+    }
+
+    pub fn depth_state(self, format: wgpu::TextureFormat) -> DepthState {
+        // LessEqual so that of two draws at the same depth the later one wins,
+        // which is what 2D content drawn in order expects.
+        let (write_enabled, compare) = match self {
+            DepthTest::Disabled => (false, wgpu::CompareFunction::Always),
+            DepthTest::ReadOnly => (false, wgpu::CompareFunction::LessEqual),
+            DepthTest::ReadWrite => (true, wgpu::CompareFunction::LessEqual),
+        };
+        DepthState {
+            format,
+            write_enabled,
+            compare,
+        }
+    }
+}
+
+/// The attachments a pipeline renders into. Every pipeline used in a render pass
+/// has to agree with the pass on all three.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TargetFormat {
+    pub color: wgpu::TextureFormat,
+    pub depth: Option<wgpu::TextureFormat>,
+    pub sample_count: u32,
+}
+
+impl TargetFormat {
+    /// A single-sampled colour target without depth.
+    pub fn color(color: wgpu::TextureFormat) -> Self {
+        Self {
+            color,
+            depth: None,
+            sample_count: 1,
+        }
+    }
+}
+
+/// Everything needed to build a render pipeline for a registered shader.
+#[derive(Clone, Debug)]
+pub struct PipelineDescriptor<'a> {
+    /// Name the shader was registered under with [`PipelineRegistry::add_shader`].
+    pub shader: &'a str,
+    pub vertex_layouts: &'a [wgpu::VertexBufferLayout<'a>],
+    pub blend: BlendMode,
+    pub topology: wgpu::PrimitiveTopology,
+    pub depth: Option<DepthState>,
+    pub sample_count: u32,
+    pub format: wgpu::TextureFormat,
+}
+
+/// Owned copy of a `wgpu::VertexBufferLayout` so it can live in a map key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct VertexLayoutKey {
+    array_stride: wgpu::BufferAddress,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    shader: String,
+    vertex_layouts: Vec<VertexLayoutKey>,
+    blend: BlendMode,
+    topology: wgpu::PrimitiveTopology,
+    depth: Option<DepthState>,
+    sample_count: u32,
+    format: wgpu::TextureFormat,
+}
+
+impl PipelineKey {
+    fn vertex_layouts(&self) -> Vec<wgpu::VertexBufferLayout<'_>> {
+        self.vertex_layouts
+            .iter()
+            .map(|layout| wgpu::VertexBufferLayout {
+                array_stride: layout.array_stride,
+                step_mode: layout.step_mode,
+                attributes: &layout.attributes,
+            })
+            .collect()
+    }
+
+    /// The descriptor this key was made from, given its vertex layouts.
+    fn descriptor<'a>(
+        &'a self,
+        vertex_layouts: &'a [wgpu::VertexBufferLayout<'a>],
+    ) -> PipelineDescriptor<'a> {
+        PipelineDescriptor {
+            shader: &self.shader,
+            vertex_layouts,
+            blend: self.blend,
+            topology: self.topology,
+            depth: self.depth,
+            sample_count: self.sample_count,
+            format: self.format,
+        }
+    }
+}
+
+impl From<&PipelineDescriptor<'_>> for PipelineKey {
+    fn from(desc: &PipelineDescriptor<'_>) -> Self {
+        Self {
+            shader: desc.shader.to_string(),
+            vertex_layouts: desc
+                .vertex_layouts
+                .iter()
+                .map(|layout| VertexLayoutKey {
+                    array_stride: layout.array_stride,
+                    step_mode: layout.step_mode,
+                    attributes: layout.attributes.to_vec(),
+                })
+                .collect(),
+            blend: desc.blend,
+            topology: desc.topology,
+            depth: desc.depth,
+            sample_count: desc.sample_count,
+            format: desc.format,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    UnknownShader(String),
+    Shader(ShaderError),
+    /// A material supplied a different number of bind groups than its shader declares.
+    BindGroupCount {
+        shader: String,
+        expected: usize,
+        found: usize,
+    },
+    /// wgpu refused to build a pipeline from a replacement shader. Holds wgpu's message.
+    Rejected {
+        shader: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::UnknownShader(name) => write!(f, "no shader registered as '{name}'"),
+            PipelineError::Shader(e) => e.fmt(f),
+            PipelineError::BindGroupCount {
+                shader,
+                expected,
+                found,
+            } => write!(
+                f,
+                "shader '{shader}' expects {expected} bind groups but the material provides {found}"
+            ),
+            PipelineError::Rejected { shader, message } => {
+                write!(
+                    f,
+                    "wgpu rejected a pipeline for shader '{shader}':\n{message}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl From<ShaderError> for PipelineError {
+    fn from(e: ShaderError) -> Self {
+        PipelineError::Shader(e)
+    }
+}
+
+/// The files under `src/shaders` a registered shader is put together from, joined
+/// with newlines in this order. Used to watch them and swap edits in with
+/// [`PipelineRegistry::replace_shader`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShaderFiles {
+    /// Name the shader is registered under.
+    pub name: &'static str,
+    pub files: &'static [&'static str],
+}
+
+struct ShaderEntry {
+    shader: Shader,
+    bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    pipeline_layout: wgpu::PipelineLayout,
+}
+
+/// Owns every shader the engine knows about and caches the pipelines built from them.
+///
+/// Asking for the same descriptor twice hands back the same pipeline, so materials
+/// can be created freely without compiling duplicate pipelines.
+#[derive(Default)]
+pub struct PipelineRegistry {
+    shaders: HashMap<String, ShaderEntry>,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+}
+
+impl PipelineRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `shader` under `name` along with the bind group layouts it expects,
+    /// in group order. Any pipelines previously built for `name` are dropped.
+    pub fn add_shader(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        shader: Shader,
+        bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    ) {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(name),
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: &[],
+        });
+        self.pipelines.retain(|key, _| key.shader != name);
+        self.shaders.insert(
+            name.to_string(),
+            ShaderEntry {
+                shader,
+                bind_group_layouts,
+                pipeline_layout,
+            },
+        );
+    }
+
+    /// Swaps the shader registered as `name` for `shader`, keeping its bind group
+    /// layouts, and rebuilds every pipeline made from it. A test pipeline is built
+    /// first too, so a shader that doesn't fit the layouts is caught even when no
+    /// pipeline uses it yet. If any of them can't be built the old shader and
+    /// pipelines stay and the error is returned.
+    ///
+    /// Materials keep drawing with the old pipelines until
+    /// [`Material::reload_pipeline`](crate::material::Material::reload_pipeline).
+    pub fn replace_shader(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        shader: Shader,
+    ) -> Result<(), PipelineError> {
+        let old = self
+            .shaders
+            .get(name)
+            .ok_or_else(|| PipelineError::UnknownShader(name.to_string()))?;
+        let entry = ShaderEntry {
+            shader,
+            bind_group_layouts: old.bind_group_layouts.clone(),
+            pipeline_layout: old.pipeline_layout.clone(),
+        };
+        let keys: Vec<PipelineKey> = self
+            .pipelines
+            .keys()
+            .filter(|key| key.shader == name)
+            .cloned()
+            .collect();
+
+        // wgpu reports what it rejects through the device, so catch it rather than
+        // letting the default handler panic.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let rebuilt = create_test_pipeline(device, name, &entry).and_then(|_| {
+            keys.into_iter()
+                .map(|key| {
+                    let vertex_layouts = key.vertex_layouts();
+                    let descriptor = key.descriptor(&vertex_layouts);
+                    let pipeline = create_pipeline(device, &entry, &descriptor)?;
+                    Ok((key, pipeline))
+                })
+                .collect::<Result<Vec<_>, ShaderError>>()
+        });
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(PipelineError::Rejected {
+                shader: name.to_string(),
+                message: error.to_string(),
+            });
+        }
+        let rebuilt = rebuilt?;
+        self.shaders.insert(name.to_string(), entry);
+        self.pipelines.extend(rebuilt);
+        Ok(())
+    }
+
+    pub fn shader(&self, name: &str) -> Option<&Shader> {
+        self.shaders.get(name).map(|entry| &entry.shader)
+    }
+
+    pub fn bind_group_layouts(&self, name: &str) -> Option<&[wgpu::BindGroupLayout]> {
+        self.shaders
+            .get(name)
+            .map(|entry| entry.bind_group_layouts.as_slice())
+    }
+
+    /// Number of distinct pipelines built so far.
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        desc: &PipelineDescriptor,
+    ) -> Result<wgpu::RenderPipeline, PipelineError> {
+        let key = PipelineKey::from(desc);
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            return Ok(pipeline.clone());
+        }
+
+        let entry = self
+            .shaders
+            .get(desc.shader)
+            .ok_or_else(|| PipelineError::UnknownShader(desc.shader.to_string()))?;
+        let pipeline = create_pipeline(device, entry, desc)?;
+        self.pipelines.insert(key, pipeline.clone());
+        Ok(pipeline)
+    }
+}
+
+/// Builds a pipeline from `entry` that nothing draws with, fed by a vertex buffer
+/// made to match the shader's inputs and writing to a plain colour target, to find
+/// out whether the shader fits its bind group layouts.
+fn create_test_pipeline(
+    device: &wgpu::Device,
+    name: &str,
+    entry: &ShaderEntry,
+) -> Result<wgpu::RenderPipeline, ShaderError> {
+    let attributes = entry.shader.vertex_attributes("vs_main")?;
+    let array_stride = attributes
+        .iter()
+        .map(|attribute| attribute.offset + attribute.format.size())
+        .max();
+    let vertex_layouts: Vec<_> = array_stride
+        .map(|array_stride| wgpu::VertexBufferLayout {
+            array_stride,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &attributes,
+        })
+        .into_iter()
+        .collect();
+    let descriptor = PipelineDescriptor {
+        shader: name,
+        vertex_layouts: &vertex_layouts,
+        blend: BlendMode::Replace,
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        depth: None,
+        sample_count: 1,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+    };
+    create_pipeline(device, entry, &descriptor)
+}
+
+/// Builds the pipeline `desc` describes from the shader in `entry`.
+fn create_pipeline(
+    device: &wgpu::Device,
+    entry: &ShaderEntry,
+    desc: &PipelineDescriptor,
+) -> Result<wgpu::RenderPipeline, ShaderError> {
+    entry
+        .shader
+        .check_vertex_input("vs_main", desc.vertex_layouts)?;
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(desc.shader),
+        layout: Some(&entry.pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &entry.shader.module,
+            entry_point: Some("vs_main"),
+            buffers: desc.vertex_layouts,
+            compilation_options: PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &entry.shader.module,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: desc.format,
+                blend: Some(desc.blend.blend_state()),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: desc.topology,
+            strip_index_format: None,
+            // A triangle is forwards if the verticies are counter clock wise.
+            // If they are backwards then the triangle is not rendered (culled)
+            // as per the cull_mode argument being set to Face::Back.
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: desc.depth.map(DepthState::depth_stencil_state),
+        multisample: wgpu::MultisampleState {
+            count: desc.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    });
+    Ok(pipeline)
+}
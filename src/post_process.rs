@@ -0,0 +1,633 @@
+use std::fmt;
+
+use wgpu::util::DeviceExt;
+
+use crate::pipeline::{
+    BlendMode, PipelineDescriptor, PipelineError, PipelineRegistry, ShaderFiles,
+};
+use crate::render_target::{RenderTarget, RenderTargetDescriptor};
+use crate::shader::Shader;
+use crate::texture::ImageTexture;
+
+const COMMON_SOURCE: &str = include_str!("shaders/post/common.wgsl");
+
+/// The files under `src/shaders` the shader of each builtin effect and colour
+/// grading is put together from. Swap edits in with
+/// [`PipelineRegistry::replace_shader`], then call
+/// [`PostProcessChain::reload_pipelines`].
+pub const POST_SHADER_FILES: [ShaderFiles; 6] = [
+    ShaderFiles {
+        name: "post/bloom",
+        files: &["post/common.wgsl", "post/bloom.wgsl"],
+    },
+    ShaderFiles {
+        name: "post/vignette",
+        files: &["post/common.wgsl", "post/vignette.wgsl"],
+    },
+    ShaderFiles {
+        name: "post/chromatic_aberration",
+        files: &["post/common.wgsl", "post/chromatic_aberration.wgsl"],
+    },
+    ShaderFiles {
+        name: "post/crt",
+        files: &["post/common.wgsl", "post/crt.wgsl"],
+    },
+    ShaderFiles {
+        name: "post/underwater",
+        files: &["post/common.wgsl", "post/underwater.wgsl"],
+    },
+    ShaderFiles {
+        name: "post/color_grading",
+        files: &["post/common.wgsl", "post/color_grading.wgsl"],
+    },
+];
+
+#[derive(Debug)]
+pub enum PostProcessError {
+    Pipeline(PipelineError),
+    /// A colour grading table isn't a strip of `n` slices each `n` x `n`, with at
+    /// least 2 entries per channel.
+    LutSize {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl fmt::Display for PostProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostProcessError::Pipeline(e) => e.fmt(f),
+            PostProcessError::LutSize { width, height } => write!(
+                f,
+                "colour grading table is {width}x{height} but should be n slices of n x n \
+                 side by side, with n at least 2"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PostProcessError {}
+
+impl From<PipelineError> for PostProcessError {
+    fn from(e: PipelineError) -> Self {
+        PostProcessError::Pipeline(e)
+    }
+}
+
+/// Uniforms every effect can read as `globals`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostGlobals {
+    resolution: [f32; 2],
+    time: f32,
+    _pad: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BloomParams {
+    /// Luminance above which pixels start to glow.
+    pub threshold: f32,
+    pub intensity: f32,
+    /// Blur spacing in pixels.
+    pub radius: f32,
+    pub _pad: f32,
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        Self {
+            threshold: 0.7,
+            intensity: 1.2,
+            radius: 3.0,
+            _pad: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VignetteParams {
+    pub strength: f32,
+    /// Distance from the centre where darkening is complete, in screen heights.
+    pub radius: f32,
+    pub softness: f32,
+    pub _pad: f32,
+}
+
+impl Default for VignetteParams {
+    fn default() -> Self {
+        Self {
+            strength: 0.8,
+            radius: 0.85,
+            softness: 0.5,
+            _pad: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ChromaticAberrationParams {
+    /// Red/blue split in pixels at the edge of the screen.
+    pub offset: f32,
+    pub _pad: [f32; 3],
+}
+
+impl Default for ChromaticAberrationParams {
+    fn default() -> Self {
+        Self {
+            offset: 4.0,
+            _pad: [0.0; 3],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColorGradingParams {
+    /// Blend between the ungraded (0) and fully graded (1) image.
+    pub intensity: f32,
+    pub lut_size: f32,
+    pub _pad: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CrtParams {
+    pub scanline_intensity: f32,
+    pub line_count: f32,
+    pub curvature: f32,
+    pub _pad: f32,
+}
+
+impl Default for CrtParams {
+    fn default() -> Self {
+        Self {
+            scanline_intensity: 0.35,
+            line_count: 240.0,
+            curvature: 0.08,
+            _pad: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UnderwaterParams {
+    /// RGB tint, with alpha controlling how strongly it is applied.
+    pub tint: [f32; 4],
+    pub amplitude: f32,
+    pub frequency: f32,
+    pub speed: f32,
+    pub _pad: f32,
+}
+
+impl Default for UnderwaterParams {
+    fn default() -> Self {
+        Self {
+            tint: [0.55, 0.85, 1.0, 0.6],
+            amplitude: 0.004,
+            frequency: 25.0,
+            speed: 2.0,
+            _pad: 0.0,
+        }
+    }
+}
+
+/// The effects that ship with the engine. Colour grading needs a lookup table and
+/// is added with [`PostProcessChain::add_color_grading`] instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuiltinEffect {
+    Bloom,
+    Vignette,
+    ChromaticAberration,
+    Crt,
+    Underwater,
+}
+
+impl BuiltinEffect {
+    pub fn name(self) -> &'static str {
+        match self {
+            BuiltinEffect::Bloom => "bloom",
+            BuiltinEffect::Vignette => "vignette",
+            BuiltinEffect::ChromaticAberration => "chromatic_aberration",
+            BuiltinEffect::Crt => "crt",
+            BuiltinEffect::Underwater => "underwater",
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            BuiltinEffect::Bloom => include_str!("shaders/post/bloom.wgsl"),
+            BuiltinEffect::Vignette => include_str!("shaders/post/vignette.wgsl"),
+            BuiltinEffect::ChromaticAberration => {
+                include_str!("shaders/post/chromatic_aberration.wgsl")
+            }
+            BuiltinEffect::Crt => include_str!("shaders/post/crt.wgsl"),
+            BuiltinEffect::Underwater => include_str!("shaders/post/underwater.wgsl"),
+        }
+    }
+
+    fn default_params(self) -> Vec<u8> {
+        match self {
+            BuiltinEffect::Bloom => bytemuck::bytes_of(&BloomParams::default()).to_vec(),
+            BuiltinEffect::Vignette => bytemuck::bytes_of(&VignetteParams::default()).to_vec(),
+            BuiltinEffect::ChromaticAberration => {
+                bytemuck::bytes_of(&ChromaticAberrationParams::default()).to_vec()
+            }
+            BuiltinEffect::Crt => bytemuck::bytes_of(&CrtParams::default()).to_vec(),
+            BuiltinEffect::Underwater => bytemuck::bytes_of(&UnderwaterParams::default()).to_vec(),
+        }
+    }
+}
+
+/// A fullscreen pass in a [`PostProcessChain`].
+pub struct PostEffect {
+    pub name: String,
+    /// Disabled effects are skipped without touching their resources.
+    pub enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    params_buffer: wgpu::Buffer,
+    uniforms: wgpu::BindGroup,
+    texture: Option<wgpu::BindGroup>,
+}
+
+impl PostEffect {
+    /// Overwrites the effect's `params` uniform. `T` has to match the layout of the
+    /// `Params` struct in its WGSL.
+    pub fn set_params<T: bytemuck::Pod>(&self, queue: &wgpu::Queue, params: &T) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(params));
+    }
+}
+
+/// Describes a custom effect for [`PostProcessChain::add_effect`].
+pub struct PostEffectDescriptor<'a> {
+    pub name: &'a str,
+    /// WGSL declaring `struct Params` and `fn fs_main(in: VertexOutput)`. It is
+    /// appended to `shaders/post/common.wgsl`, which provides `t_input`, `s_input`,
+    /// `globals` and `params`.
+    pub source: &'a str,
+    /// Initial contents of the `params` uniform.
+    pub params: &'a [u8],
+    /// Optional extra texture, bound at group 2 as a texture + sampler pair.
+    pub texture: Option<&'a ImageTexture>,
+}
+
+/// Runs the rendered scene through a sequence of fullscreen effects.
+///
+/// The scene is drawn into [`PostProcessChain::scene_target`] instead of the
+/// screen. Each enabled effect then reads one of two ping-pong targets and writes
+/// the other, with the last one writing to the final output.
+pub struct PostProcessChain {
+    effects: Vec<PostEffect>,
+    targets: [RenderTarget; 2],
+    input_bind_groups: [wgpu::BindGroup; 2],
+    texture_layout: wgpu::BindGroupLayout,
+    uniforms_layout: wgpu::BindGroupLayout,
+    globals_buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+}
+
+impl PostProcessChain {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture_layout = ImageTexture::bind_group_layout(device);
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let uniforms_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[uniform_entry(0), uniform_entry(1)],
+            label: Some("Post Process Uniforms Layout"),
+        });
+        let globals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post Process Globals"),
+            size: std::mem::size_of::<PostGlobals>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let targets = Self::create_targets(device, format, width, height);
+        let input_bind_groups = Self::create_input_bind_groups(device, &texture_layout, &targets);
+        Self {
+            effects: Vec::new(),
+            targets,
+            input_bind_groups,
+            texture_layout,
+            uniforms_layout,
+            globals_buffer,
+            format,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> [RenderTarget; 2] {
+        ["Post Process Target A", "Post Process Target B"].map(|label| {
+            RenderTarget::new(
+                device,
+                &RenderTargetDescriptor {
+                    format,
+                    ..RenderTargetDescriptor::new(label, width, height)
+                },
+            )
+        })
+    }
+
+    fn create_input_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        targets: &[RenderTarget; 2],
+    ) -> [wgpu::BindGroup; 2] {
+        [
+            targets[0].color.create_bind_group(device, layout),
+            targets[1].color.create_bind_group(device, layout),
+        ]
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = Self::create_targets(device, self.format, width, height);
+        self.input_bind_groups =
+            Self::create_input_bind_groups(device, &self.texture_layout, &self.targets);
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        shader_name: &str,
+    ) -> Result<wgpu::RenderPipeline, PipelineError> {
+        registry.get_or_create(
+            device,
+            &PipelineDescriptor {
+                shader: shader_name,
+                vertex_layouts: &[],
+                blend: BlendMode::Replace,
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                depth: None,
+                sample_count: 1,
+                format: self.format,
+            },
+        )
+    }
+
+    /// Appends a custom effect to the end of the chain, enabled. Its shader is
+    /// registered as `post/<name>`.
+    pub fn add_effect(
+        &mut self,
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        desc: &PostEffectDescriptor,
+    ) -> Result<&mut PostEffect, PipelineError> {
+        let shader_name = format!("post/{}", desc.name);
+        let source = format!("{COMMON_SOURCE}\n{}", desc.source);
+        let shader = Shader::from_wgsl(device, &shader_name, &source)?;
+        let mut layouts = vec![self.texture_layout.clone(), self.uniforms_layout.clone()];
+        if desc.texture.is_some() {
+            layouts.push(self.texture_layout.clone());
+        }
+        registry.add_shader(device, &shader_name, shader, layouts);
+        let pipeline = self.create_pipeline(device, registry, &shader_name)?;
+
+        // Uniform bindings must be at least 16 bytes.
+        let mut params = desc.params.to_vec();
+        params.resize(params.len().max(16).next_multiple_of(16), 0);
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&shader_name),
+            contents: &params,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let uniforms = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.uniforms_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.globals_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(&shader_name),
+        });
+        let texture = desc
+            .texture
+            .map(|texture| texture.create_bind_group(device, &self.texture_layout));
+
+        self.effects.push(PostEffect {
+            name: desc.name.to_string(),
+            enabled: true,
+            pipeline,
+            params_buffer,
+            uniforms,
+            texture,
+        });
+        Ok(self.effects.last_mut().unwrap())
+    }
+
+    /// Appends one of the built-in effects with its default parameters, enabled.
+    pub fn add_builtin(
+        &mut self,
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        effect: BuiltinEffect,
+    ) -> Result<&mut PostEffect, PipelineError> {
+        let params = effect.default_params();
+        self.add_effect(
+            device,
+            registry,
+            &PostEffectDescriptor {
+                name: effect.name(),
+                source: effect.source(),
+                params: &params,
+                texture: None,
+            },
+        )
+    }
+
+    /// Appends a colour grading effect using `lut`, a strip of `lut_size` slices each
+    /// `lut_size` x `lut_size` as made by [`identity_lut`]. Load it with
+    /// `srgb: false` so the table is used as authored, and without mipmaps, which
+    /// would blend neighbouring slices together.
+    pub fn add_color_grading(
+        &mut self,
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+        lut: &ImageTexture,
+        intensity: f32,
+    ) -> Result<&mut PostEffect, PostProcessError> {
+        let (width, height) = (lut.width(), lut.height());
+        if height < 2 || height.checked_mul(height) != Some(width) {
+            return Err(PostProcessError::LutSize { width, height });
+        }
+        let params = ColorGradingParams {
+            intensity,
+            lut_size: height as f32,
+            _pad: [0.0; 2],
+        };
+        Ok(self.add_effect(
+            device,
+            registry,
+            &PostEffectDescriptor {
+                name: "color_grading",
+                source: include_str!("shaders/post/color_grading.wgsl"),
+                params: bytemuck::bytes_of(&params),
+                texture: Some(lut),
+            },
+        )?)
+    }
+
+    /// Picks up the pipelines rebuilt by [`PipelineRegistry::replace_shader`] after
+    /// the shader of an effect was swapped, such as one of the
+    /// [`POST_SHADER_FILES`].
+    pub fn reload_pipelines(
+        &mut self,
+        device: &wgpu::Device,
+        registry: &mut PipelineRegistry,
+    ) -> Result<(), PipelineError> {
+        let pipelines = self
+            .effects
+            .iter()
+            .map(|effect| self.create_pipeline(device, registry, &format!("post/{}", effect.name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (effect, pipeline) in self.effects.iter_mut().zip(pipelines) {
+            effect.pipeline = pipeline;
+        }
+        Ok(())
+    }
+
+    pub fn effect(&self, name: &str) -> Option<&PostEffect> {
+        self.effects.iter().find(|effect| effect.name == name)
+    }
+
+    pub fn effect_mut(&mut self, name: &str) -> Option<&mut PostEffect> {
+        self.effects.iter_mut().find(|effect| effect.name == name)
+    }
+
+    pub fn effects(&self) -> &[PostEffect] {
+        &self.effects
+    }
+
+    /// Points an effect's extra texture at `texture`, for instance after the texture
+    /// it was added with has been recreated at a new size. Returns `false` if there is
+    /// no such effect or it was added without a texture.
+    pub fn set_effect_texture(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        texture: &ImageTexture,
+    ) -> bool {
+        let layout = &self.texture_layout;
+        match self.effects.iter_mut().find(|effect| effect.name == name) {
+            Some(PostEffect {
+                texture: Some(bind_group),
+                ..
+            }) => {
+                *bind_group = texture.create_bind_group(device, layout);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Flips an effect on or off, returning its new state.
+    pub fn toggle(&mut self, name: &str) -> Option<bool> {
+        let effect = self.effect_mut(name)?;
+        effect.enabled = !effect.enabled;
+        Some(effect.enabled)
+    }
+
+    /// Whether any effect will run. When none will, render straight to the output
+    /// and skip the chain entirely.
+    pub fn is_active(&self) -> bool {
+        self.effects.iter().any(|effect| effect.enabled)
+    }
+
+    /// Where the scene has to be rendered for the chain to pick it up.
+    pub fn scene_target(&self) -> &RenderTarget {
+        &self.targets[0]
+    }
+
+    /// Records every enabled effect, reading the scene target and finishing in `output`.
+    pub fn run(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        time: f32,
+    ) {
+        let globals = PostGlobals {
+            resolution: [
+                self.targets[0].width() as f32,
+                self.targets[0].height() as f32,
+            ],
+            time,
+            _pad: 0.0,
+        };
+        queue.write_buffer(&self.globals_buffer, 0, bytemuck::bytes_of(&globals));
+
+        let enabled: Vec<_> = self.effects.iter().filter(|e| e.enabled).collect();
+        let mut source = 0;
+        for (i, effect) in enabled.iter().enumerate() {
+            let destination = if i + 1 == enabled.len() {
+                output
+            } else {
+                &self.targets[1 - source].color.view
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&effect.name),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: destination,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&effect.pipeline);
+            render_pass.set_bind_group(0, &self.input_bind_groups[source], &[]);
+            render_pass.set_bind_group(1, &effect.uniforms, &[]);
+            if let Some(texture) = &effect.texture {
+                render_pass.set_bind_group(2, texture, &[]);
+            }
+            render_pass.draw(0..3, 0..1);
+            source = 1 - source;
+        }
+    }
+}
+
+/// A colour grading table that leaves colours unchanged, laid out as `size` slices
+/// of `size` x `size` side by side. Edit it in an image editor to make a grade.
+///
+/// Panics if `size` is less than 2, since a table needs both ends of each channel.
+pub fn identity_lut(size: u32) -> image::RgbaImage {
+    assert!(
+        size >= 2,
+        "a colour grading table needs at least 2 entries per channel"
+    );
+    let scale = 255.0 / (size - 1) as f32;
+    image::RgbaImage::from_fn(size * size, size, |x, y| {
+        let r = (x % size) as f32 * scale;
+        let g = y as f32 * scale;
+        let b = (x / size) as f32 * scale;
+        image::Rgba([r.round() as u8, g.round() as u8, b.round() as u8, 255])
+    })
+}
@@ -0,0 +1,131 @@
+use std::cmp::Ordering;
+
+use crate::material::Material;
+use crate::mesh::Mesh;
+
+/// One mesh drawn with one material.
+pub struct DrawItem<'a> {
+    pub material: &'a Material,
+    pub mesh: &'a Mesh,
+    /// Sort layer, or z-index. Higher layers end up in front of lower ones.
+    pub layer: i32,
+    /// Distance from the camera within the layer. Larger is further away.
+    pub depth: f32,
+}
+
+impl DrawItem<'_> {
+    fn is_transparent(&self) -> bool {
+        self.material.descriptor.blend.is_transparent()
+    }
+}
+
+/// Maps sort layers onto slices of the depth range so that, with a depth buffer,
+/// a higher layer is always in front of a lower one regardless of draw order.
+///
+/// Each layer is drawn with a viewport whose depth range is its slice, so shaders
+/// don't need to know about layers at all. Layers outside the range are clamped.
+#[derive(Clone, Copy, Debug)]
+pub struct LayerDepth {
+    pub min_layer: i32,
+    pub max_layer: i32,
+}
+
+impl Default for LayerDepth {
+    fn default() -> Self {
+        Self {
+            min_layer: -16,
+            max_layer: 15,
+        }
+    }
+}
+
+impl LayerDepth {
+    /// The `(min_depth, max_depth)` slice `layer` is drawn into.
+    pub fn range(&self, layer: i32) -> (f32, f32) {
+        let layer = layer.clamp(self.min_layer, self.max_layer);
+        let count = (self.max_layer - self.min_layer + 1) as f32;
+        // The highest layer takes the nearest slice.
+        let slot = (self.max_layer - layer) as f32;
+        (slot / count, (slot + 1.0) / count)
+    }
+}
+
+/// Collects the draws for a frame so they can be issued in a correct order.
+///
+/// Without a depth buffer everything is drawn layer by layer. Within a layer opaque
+/// draws go first, then transparent draws from back to front so each one blends
+/// over everything already behind it.
+///
+/// With a depth buffer the depth test keeps opaque draws correct in any order, so
+/// they are all drawn first, nearest layer first to save fill rate. Transparent
+/// draws follow, still sorted back to front.
+#[derive(Default)]
+pub struct RenderQueue<'a> {
+    items: Vec<DrawItem<'a>>,
+    layer_depth: Option<(LayerDepth, f32, f32)>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A queue for a pass with a `width` x `height` depth attachment.
+    pub fn with_layer_depth(layer_depth: LayerDepth, width: u32, height: u32) -> Self {
+        Self {
+            items: Vec::new(),
+            layer_depth: Some((layer_depth, width as f32, height as f32)),
+        }
+    }
+
+    pub fn push(&mut self, item: DrawItem<'a>) {
+        self.items.push(item);
+    }
+
+    pub fn sort(&mut self) {
+        let depth_tested = self.layer_depth.is_some();
+        self.items.sort_by(|a, b| {
+            let back_to_front = || {
+                if a.is_transparent() {
+                    b.depth.partial_cmp(&a.depth).unwrap_or(Ordering::Equal)
+                } else {
+                    Ordering::Equal
+                }
+            };
+            if depth_tested {
+                a.is_transparent()
+                    .cmp(&b.is_transparent())
+                    .then_with(|| {
+                        if a.is_transparent() {
+                            a.layer.cmp(&b.layer)
+                        } else {
+                            b.layer.cmp(&a.layer)
+                        }
+                    })
+                    .then_with(back_to_front)
+            } else {
+                a.layer
+                    .cmp(&b.layer)
+                    .then(a.is_transparent().cmp(&b.is_transparent()))
+                    .then_with(back_to_front)
+            }
+        });
+    }
+
+    /// Sorts and records every queued draw. The camera must already be bound at group 0.
+    pub fn draw(&mut self, render_pass: &mut wgpu::RenderPass) {
+        self.sort();
+        let mut current_layer = None;
+        for item in &self.items {
+            if let Some((layer_depth, width, height)) = self.layer_depth {
+                if current_layer != Some(item.layer) {
+                    let (min_depth, max_depth) = layer_depth.range(item.layer);
+                    render_pass.set_viewport(0.0, 0.0, width, height, min_depth, max_depth);
+                    current_layer = Some(item.layer);
+                }
+            }
+            item.material.bind(render_pass);
+            item.mesh.draw(render_pass);
+        }
+    }
+}
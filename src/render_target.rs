@@ -0,0 +1,146 @@
+use crate::pipeline::TargetFormat;
+use crate::texture::{DepthTexture, ImageTexture, TextureOptions};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RenderTargetDescriptor<'a> {
+    pub label: &'a str,
+    pub width: u32,
+    pub height: u32,
+    /// Has to be filterable to be sampled through [`ImageTexture::bind_group_layout`].
+    pub format: wgpu::TextureFormat,
+    pub depth: bool,
+    /// Filtering and wrapping used when the target is sampled afterwards.
+    pub sampling: TextureOptions,
+}
+
+impl<'a> RenderTargetDescriptor<'a> {
+    /// An sRGB colour target without depth, sampled with linear filtering.
+    pub fn new(label: &'a str, width: u32, height: u32) -> Self {
+        Self {
+            label,
+            width,
+            height,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            depth: false,
+            sampling: TextureOptions {
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// An offscreen colour texture, plus optional depth, that can be rendered into
+/// and then sampled like any [`ImageTexture`].
+///
+/// Useful for minimaps, portals, UI previews and as the input to post-processing.
+pub struct RenderTarget {
+    /// The colour attachment. Pass this anywhere an `ImageTexture` is expected, for
+    /// instance to `Material::textured`, to draw what was rendered.
+    pub color: ImageTexture,
+    pub depth: Option<DepthTexture>,
+    label: String,
+    sampling: TextureOptions,
+}
+
+impl RenderTarget {
+    pub fn new(device: &wgpu::Device, desc: &RenderTargetDescriptor) -> Self {
+        let width = desc.width.max(1);
+        let height = desc.height.max(1);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(desc.label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: desc.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = desc.sampling.create_sampler(device, Some(desc.label));
+        let depth = desc
+            .depth
+            .then(|| DepthTexture::new(device, width, height, 1, desc.label));
+        Self {
+            color: ImageTexture {
+                texture,
+                view,
+                sampler,
+            },
+            depth,
+            label: desc.label.to_string(),
+            sampling: desc.sampling,
+        }
+    }
+
+    /// Recreates the attachments at a new size. Bind groups made from the old
+    /// texture keep pointing at it and have to be recreated too.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if (width, height) == (self.width(), self.height()) {
+            return;
+        }
+        *self = Self::new(
+            device,
+            &RenderTargetDescriptor {
+                label: &self.label,
+                width,
+                height,
+                format: self.format(),
+                depth: self.depth.is_some(),
+                sampling: self.sampling,
+            },
+        );
+    }
+
+    pub fn width(&self) -> u32 {
+        self.color.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.color.height()
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.color.texture.format()
+    }
+
+    /// What pipelines drawing into this target have to be built for.
+    pub fn target_format(&self) -> TargetFormat {
+        TargetFormat {
+            color: self.format(),
+            depth: self.depth.as_ref().map(|_| DepthTexture::FORMAT),
+            sample_count: 1,
+        }
+    }
+
+    /// Starts a pass drawing into this target. `clear` of `None` keeps the previous
+    /// contents, otherwise the colour is cleared to it. Depth is always cleared.
+    pub fn begin_pass<'e>(
+        &'e self,
+        encoder: &'e mut wgpu::CommandEncoder,
+        clear: Option<wgpu::Color>,
+    ) -> wgpu::RenderPass<'e> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.color.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: self.depth.as_ref().map(DepthTexture::attachment),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+}
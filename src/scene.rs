@@ -0,0 +1,503 @@
+use std::fmt;
+use std::sync::Arc;
+
+use cgmath::{Matrix4, Rad, SquareMatrix, Vector2, Vector3, Vector4};
+
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::render_queue::{DrawItem, RenderQueue};
+use crate::shapes::Shape;
+use crate::text::TextLayout;
+use crate::vertex::{ColoredVertex, Positioned, TexturedVertex};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneError {
+    /// The node was never added or has since been removed.
+    UnknownNode(NodeId),
+    /// Reparenting would make a node its own ancestor.
+    Cycle(NodeId),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownNode(id) => write!(f, "no scene node {id:?}"),
+            Self::Cycle(id) => write!(f, "scene node {id:?} can't be its own ancestor"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// Refers to a node in a [`Scene`]. Ids of removed nodes are never handed out
+/// again, so a stale id finds nothing rather than some other node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+/// Placement of a node relative to its parent: scaled, then rotated about z,
+/// then translated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    /// z only decides draw order between nodes in the same layer.
+    pub translation: Vector3<f32>,
+    /// Counter-clockwise, in radians.
+    pub rotation: f32,
+    pub scale: Vector2<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: 0.0,
+            scale: Vector2::new(1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn new(translation: Vector3<f32>) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: Vector2<f32>) -> Self {
+        Self { scale, ..self }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from_angle_z(Rad(self.rotation))
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, 1.0)
+    }
+}
+
+/// What a node draws, in its own local space. The material it is drawn with must
+/// take the matching vertices.
+#[derive(Clone, Debug)]
+pub enum Renderable {
+    /// Coloured geometry, drawn with [`Material::colored`].
+    Shape {
+        vertices: Vec<ColoredVertex>,
+        indices: Vec<u16>,
+    },
+    /// A quad `size` across centred on the node, showing the `uv_rect` part of
+    /// the texture. Drawn with [`Material::textured`].
+    Sprite { size: [f32; 2], uv_rect: [f32; 4] },
+    /// A block of text from [`Font::layout`](crate::text::Font::layout), drawn with
+    /// [`Material::text`].
+    Text(TextLayout),
+}
+
+impl Renderable {
+    pub fn shape(shape: &impl Shape, color: [f32; 4]) -> Self {
+        Self::Shape {
+            vertices: shape.col_vertices(color),
+            indices: shape.indices(),
+        }
+    }
+
+    /// A sprite showing the whole texture.
+    pub fn sprite(size: [f32; 2]) -> Self {
+        Self::Sprite {
+            size,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// A renderable with the material it is drawn with and its geometry in world
+/// space, rebuilt whenever the node moves or the renderable changes.
+struct Drawable {
+    renderable: Renderable,
+    material: Arc<Material>,
+    mesh: Option<Mesh>,
+    stale: bool,
+}
+
+impl Drawable {
+    fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world: &Matrix4<f32>) {
+        match &self.renderable {
+            Renderable::Shape { vertices, indices } => {
+                let vertices = transformed(vertices, world);
+                Self::write(&mut self.mesh, device, queue, &vertices, indices);
+            }
+            &Renderable::Sprite {
+                size: [width, height],
+                uv_rect: [u, v, uv_width, uv_height],
+            } => {
+                let (x, y) = (width / 2.0, height / 2.0);
+                // Corners in the same order as a `Square`, with v running down.
+                let vertices = [
+                    TexturedVertex::new([-x, y, 0.0], [u, v]),
+                    TexturedVertex::new([x, y, 0.0], [u + uv_width, v]),
+                    TexturedVertex::new([-x, -y, 0.0], [u, v + uv_height]),
+                    TexturedVertex::new([x, -y, 0.0], [u + uv_width, v + uv_height]),
+                ];
+                let vertices = transformed(&vertices, world);
+                Self::write(
+                    &mut self.mesh,
+                    device,
+                    queue,
+                    &vertices,
+                    &[0, 2, 1, 1, 2, 3],
+                );
+            }
+            Renderable::Text(layout) => {
+                let vertices = transformed(&layout.vertices, world);
+                Self::write(&mut self.mesh, device, queue, &vertices, &layout.indices);
+            }
+        }
+        self.stale = false;
+    }
+
+    fn write<V: Positioned>(
+        mesh: &mut Option<Mesh>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertices: &[V],
+        indices: &[u16],
+    ) {
+        match mesh {
+            Some(mesh) => mesh.write(device, queue, vertices, indices),
+            None => *mesh = Some(Mesh::new(device, vertices, indices, "Scene Node Mesh")),
+        }
+    }
+}
+
+fn transformed<V: Positioned>(vertices: &[V], world: &Matrix4<f32>) -> Vec<V> {
+    vertices
+        .iter()
+        .map(|&vertex| {
+            let [x, y, z] = vertex.position();
+            let position = world * Vector4::new(x, y, z, 1.0);
+            vertex.with_position([position.x, position.y, position.z])
+        })
+        .collect()
+}
+
+/// An object in the world, placed relative to its parent.
+pub struct Node {
+    pub name: String,
+    /// Hidden nodes aren't drawn, and neither are any of their descendants.
+    pub visible: bool,
+    /// Sort layer its renderable is drawn in, see [`DrawItem::layer`].
+    pub layer: i32,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Matrix4<f32>,
+    /// Set when the transform has changed since the world matrix was worked out.
+    dirty: bool,
+    drawable: Option<Drawable>,
+}
+
+impl Node {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+            layer: 0,
+            transform: Transform::default(),
+            parent: None,
+            children: Vec::new(),
+            world: Matrix4::identity(),
+            dirty: true,
+            drawable: None,
+        }
+    }
+
+    pub fn with_transform(self, transform: Transform) -> Self {
+        Self { transform, ..self }
+    }
+
+    pub fn with_layer(self, layer: i32) -> Self {
+        Self { layer, ..self }
+    }
+
+    pub fn with_renderable(mut self, renderable: Renderable, material: Arc<Material>) -> Self {
+        self.set_renderable(renderable, material);
+        self
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// The transform, marking this node and everything below it to be moved on
+    /// the next [`Scene::update_world`].
+    pub fn transform_mut(&mut self) -> &mut Transform {
+        self.dirty = true;
+        &mut self.transform
+    }
+
+    /// Local to world space, as of the last [`Scene::update_world`].
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        self.world
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn renderable(&self) -> Option<&Renderable> {
+        self.drawable.as_ref().map(|drawable| &drawable.renderable)
+    }
+
+    /// The renderable, to be rebuilt on the next [`Scene::prepare`].
+    pub fn renderable_mut(&mut self) -> Option<&mut Renderable> {
+        self.drawable.as_mut().map(|drawable| {
+            drawable.stale = true;
+            &mut drawable.renderable
+        })
+    }
+
+    pub fn set_renderable(&mut self, renderable: Renderable, material: Arc<Material>) {
+        self.drawable = Some(Drawable {
+            renderable,
+            material,
+            mesh: None,
+            stale: true,
+        });
+    }
+
+    pub fn clear_renderable(&mut self) {
+        self.drawable = None;
+    }
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+/// A tree of nodes, each placed relative to its parent.
+///
+/// Every frame, after moving nodes, call [`Scene::prepare`] to work out world
+/// matrices for whatever moved and rebuild the meshes of renderables that need it,
+/// then [`Scene::queue_draws`] to draw every visible one.
+#[derive(Default)]
+pub struct Scene {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `node` at the top level of the scene.
+    pub fn add(&mut self, node: Node) -> NodeId {
+        let id = self.insert(node);
+        self.roots.push(id);
+        id
+    }
+
+    /// Adds `node` as the last child of `parent`.
+    pub fn add_child(&mut self, parent: NodeId, node: Node) -> Result<NodeId, SceneError> {
+        if self.get(parent).is_none() {
+            return Err(SceneError::UnknownNode(parent));
+        }
+        let id = self.insert(Node {
+            parent: Some(parent),
+            ..node
+        });
+        self.node_mut(parent).children.push(id);
+        Ok(id)
+    }
+
+    fn insert(&mut self, node: Node) -> NodeId {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Removes `id` along with all of its descendants.
+    pub fn remove(&mut self, id: NodeId) -> Result<(), SceneError> {
+        let parent = self.get(id).ok_or(SceneError::UnknownNode(id))?.parent;
+        self.siblings_mut(parent).retain(|&sibling| sibling != id);
+        let mut removing = vec![id];
+        while let Some(id) = removing.pop() {
+            let slot = &mut self.slots[id.index as usize];
+            if let Some(node) = slot.node.take() {
+                removing.extend(node.children);
+            }
+            slot.generation += 1;
+            self.free.push(id.index);
+        }
+        Ok(())
+    }
+
+    /// Moves `id` under `parent`, or to the top level for `None`. Its transform
+    /// stays relative to whatever it is now under.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), SceneError> {
+        let old_parent = self.get(id).ok_or(SceneError::UnknownNode(id))?.parent;
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return Err(SceneError::Cycle(id));
+            }
+            ancestor = self
+                .get(ancestor_id)
+                .ok_or(SceneError::UnknownNode(ancestor_id))?
+                .parent;
+        }
+        self.siblings_mut(old_parent)
+            .retain(|&sibling| sibling != id);
+        self.siblings_mut(parent).push(id);
+        let node = self.node_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    /// For ids known to be live, e.g. taken from another node.
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.get_mut(id).expect("scene node links are kept in sync")
+    }
+
+    /// The list `parent`'s children are kept in, or the top level for `None`.
+    fn siblings_mut(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+        match parent {
+            Some(parent) => &mut self.node_mut(parent).children,
+            None => &mut self.roots,
+        }
+    }
+
+    /// The material of every node with a renderable, hidden or not. Materials
+    /// shared between nodes come up once per node.
+    pub fn materials(&self) -> impl Iterator<Item = &Arc<Material>> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.node.as_ref()?.drawable.as_ref())
+            .map(|drawable| &drawable.material)
+    }
+
+    /// The nodes at the top level of the scene.
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// The first node named `name`, searching depth first.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = self.get(id)?;
+            if node.name == name {
+                return Some(id);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        None
+    }
+
+    /// Works out world matrices for every node whose transform, or any of whose
+    /// ancestors' transforms, changed since the last call.
+    pub fn update_world(&mut self) {
+        let mut stack: Vec<(NodeId, Matrix4<f32>, bool)> = self
+            .roots
+            .iter()
+            .map(|&id| (id, Matrix4::identity(), false))
+            .collect();
+        while let Some((id, parent_world, parent_moved)) = stack.pop() {
+            let node = self.node_mut(id);
+            let moved = node.dirty || parent_moved;
+            if moved {
+                node.world = parent_world * node.transform.matrix();
+                node.dirty = false;
+                if let Some(drawable) = &mut node.drawable {
+                    drawable.stale = true;
+                }
+            }
+            let world = node.world;
+            stack.extend(node.children.iter().map(|&child| (child, world, moved)));
+        }
+    }
+
+    /// Updates world matrices and rebuilds the meshes of visible renderables that
+    /// moved or changed. Hidden ones are rebuilt once they are shown again.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.update_world();
+        let mut stack = self.roots.clone();
+        while let Some(id) = stack.pop() {
+            let node = self.node_mut(id);
+            if !node.visible {
+                continue;
+            }
+            stack.extend(&node.children);
+            let world = node.world;
+            if let Some(drawable) = node.drawable.as_mut().filter(|drawable| drawable.stale) {
+                drawable.upload(device, queue, &world);
+            }
+        }
+    }
+
+    /// Queues a draw for every visible renderable prepared so far. Depth within a
+    /// layer comes from world z, with the camera looking down -z.
+    pub fn queue_draws<'a>(&'a self, draws: &mut RenderQueue<'a>) {
+        let mut stack = self.roots.clone();
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.get(id).filter(|node| node.visible) else {
+                continue;
+            };
+            stack.extend(&node.children);
+            let Some(drawable) = &node.drawable else {
+                continue;
+            };
+            if let Some(mesh) = &drawable.mesh {
+                draws.push(DrawItem {
+                    material: &drawable.material,
+                    mesh,
+                    layer: node.layer,
+                    depth: -node.world.w.z,
+                });
+            }
+        }
+    }
+}
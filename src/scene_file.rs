@@ -0,0 +1,890 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use cgmath::{Point3, Vector3};
+
+use crate::camera::Camera;
+use crate::lighting::{Light, LightKind};
+use crate::material::Material;
+use crate::scene::{Node, NodeId, Renderable, Scene, Transform};
+use crate::shapes::{Circle, Square};
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(std::io::Error),
+    /// A line couldn't be understood. Lines count from 1.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// A node is an instance of a prefab that isn't declared. `line` is `None`
+    /// when the prefab was asked for by [`SceneFile::prefab`].
+    UnknownPrefab {
+        line: Option<usize>,
+        name: String,
+    },
+    /// [`SceneAssets`] has no texture at this path.
+    MissingTexture(String),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::Parse { line, message } => write!(f, "scene line {line}: {message}"),
+            Self::UnknownPrefab {
+                line: Some(line),
+                name,
+            } => write!(f, "scene line {line}: no prefab named `{name}`"),
+            Self::UnknownPrefab { line: None, name } => write!(f, "no prefab named `{name}`"),
+            Self::MissingTexture(path) => write!(f, "no texture `{path}` for a scene sprite"),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SceneFileError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+fn parse_error(line: usize, message: String) -> SceneFileError {
+    SceneFileError::Parse { line, message }
+}
+
+/// The value of an attribute as written: `1.5`, `1,0.5,0`, `circle` or `"a b"`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f32),
+    Numbers(Vec<f32>),
+    Text(String),
+}
+
+impl Value {
+    fn parse(text: &str, quoted: bool) -> Result<Self, String> {
+        if quoted {
+            return Ok(Self::Text(text.to_string()));
+        }
+        if text.contains(',') {
+            return text
+                .split(',')
+                .map(|number| number.trim().parse())
+                .collect::<Result<_, _>>()
+                .map(Self::Numbers)
+                .map_err(|_| format!("`{text}` should be numbers separated by commas, or quoted"));
+        }
+        Ok(match text.parse() {
+            Ok(number) => Self::Number(number),
+            Err(_) => Self::Text(text.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{number}"),
+            Self::Numbers(numbers) => {
+                let numbers: Vec<String> = numbers.iter().map(f32::to_string).collect();
+                write!(f, "{}", numbers.join(","))
+            }
+            Self::Text(text) => write_text(f, text),
+        }
+    }
+}
+
+/// Writes `text` bare if it would read back as the same text, quoted otherwise.
+fn write_text(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    let bare = !text.is_empty()
+        && text.parse::<f32>().is_err()
+        && !text
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\\' | '#' | '=' | ','));
+    if bare {
+        return write!(f, "{text}");
+    }
+    write!(f, "\"")?;
+    for c in text.chars() {
+        if matches!(c, '"' | '\\') {
+            write!(f, "\\")?;
+        }
+        write!(f, "{c}")?;
+    }
+    write!(f, "\"")
+}
+
+/// One `key=value` pair.
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    pub key: String,
+    pub value: Value,
+    /// Where it was read from, for errors. 0 for attributes made in code.
+    pub line: usize,
+}
+
+impl Attribute {
+    pub fn new(key: impl Into<String>, value: Value) -> Self {
+        Self {
+            key: key.into(),
+            value,
+            line: 0,
+        }
+    }
+
+    fn error(&self, expected: &str) -> SceneFileError {
+        parse_error(
+            self.line,
+            format!(
+                "`{}` should be {expected}, found `{}`",
+                self.key, self.value
+            ),
+        )
+    }
+
+    fn number(&self) -> Result<f32, SceneFileError> {
+        match self.value {
+            Value::Number(number) => Ok(number),
+            _ => Err(self.error("a number")),
+        }
+    }
+
+    fn integer<T: TryFrom<i64>>(&self) -> Result<T, SceneFileError> {
+        let number = self.number()?;
+        (number.fract() == 0.0)
+            .then(|| T::try_from(number as i64).ok())
+            .flatten()
+            .ok_or_else(|| self.error("a whole number in range"))
+    }
+
+    /// `N` numbers, or a single number repeated `N` times.
+    fn numbers<const N: usize>(&self) -> Result<[f32; N], SceneFileError> {
+        match &self.value {
+            Value::Number(number) => Ok([*number; N]),
+            Value::Numbers(numbers) => numbers
+                .as_slice()
+                .try_into()
+                .map_err(|_| self.error(&format!("{N} numbers"))),
+            Value::Text(_) => Err(self.error(&format!("{N} numbers"))),
+        }
+    }
+
+    fn text(&self) -> Result<&str, SceneFileError> {
+        match &self.value {
+            Value::Text(text) => Ok(text),
+            _ => Err(self.error("text")),
+        }
+    }
+
+    fn boolean(&self) -> Result<bool, SceneFileError> {
+        match self.text() {
+            Ok("true") => Ok(true),
+            Ok("false") => Ok(false),
+            _ => Err(self.error("`true` or `false`")),
+        }
+    }
+
+    fn unknown(&self, owner: &str) -> SceneFileError {
+        parse_error(
+            self.line,
+            format!("`{owner}` has no attribute `{}`", self.key),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentKind {
+    Transform,
+    Sprite,
+    Shape,
+    Collider,
+    Light,
+    Camera,
+}
+
+impl ComponentKind {
+    const ALL: [ComponentKind; 6] = [
+        Self::Transform,
+        Self::Sprite,
+        Self::Shape,
+        Self::Collider,
+        Self::Light,
+        Self::Camera,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Transform => "transform",
+            Self::Sprite => "sprite",
+            Self::Shape => "shape",
+            Self::Collider => "collider",
+            Self::Light => "light",
+            Self::Camera => "camera",
+        }
+    }
+}
+
+/// A component line, e.g. `shape kind=circle radius=0.2`.
+#[derive(Clone, Debug)]
+pub struct ComponentEntry {
+    pub kind: ComponentKind,
+    pub attributes: Vec<Attribute>,
+    pub line: usize,
+}
+
+/// A `node` or `prefab` block.
+#[derive(Clone, Debug)]
+pub struct NodeEntry {
+    pub name: String,
+    /// `prefab`, `layer` and `visible`, from the opening line.
+    pub attributes: Vec<Attribute>,
+    pub components: Vec<ComponentEntry>,
+    pub children: Vec<NodeEntry>,
+    pub line: usize,
+}
+
+impl NodeEntry {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            attributes: Vec::new(),
+            components: Vec::new(),
+            children: Vec::new(),
+            line: 0,
+        }
+    }
+
+    fn attribute(&self, key: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .rev()
+            .find(|attribute| attribute.key == key)
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, keyword: &str, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        write!(f, "{indent}{keyword} ")?;
+        write_text(f, &self.name)?;
+        write_attributes(f, &self.attributes)?;
+        writeln!(f)?;
+        for component in &self.components {
+            write!(f, "{indent}  {}", component.kind.name())?;
+            write_attributes(f, &component.attributes)?;
+            writeln!(f)?;
+        }
+        for child in &self.children {
+            child.write(f, "node", depth + 1)?;
+        }
+        writeln!(f, "{indent}end")
+    }
+}
+
+fn write_attributes(f: &mut fmt::Formatter<'_>, attributes: &[Attribute]) -> fmt::Result {
+    for attribute in attributes {
+        write!(f, " {}={}", attribute.key, attribute.value)?;
+    }
+    Ok(())
+}
+
+/// A level or set of prefabs, as written in a scene file.
+///
+/// Each line is a keyword followed by `key=value` attributes, and `#` starts a
+/// comment. `node <name>` and `prefab <name>` open blocks that `end` closes, and
+/// inside a block each component gets a line of its own. Indentation is only
+/// for the reader:
+///
+/// ```text
+/// prefab lantern layer=1
+///   shape kind=circle radius=0.05 color=1,0.8,0.3,1
+///   light color=1,0.8,0.3 radius=0.6
+/// end
+///
+/// node level
+///   node tree
+///     transform position=0,-0.5 scale=0.5
+///     sprite texture=happy-tree.png size=1,1
+///   end
+///   node blue_lantern prefab=lantern
+///     transform position=0.8,0
+///     light color=0.3,0.5,1
+///   end
+/// end
+/// ```
+///
+/// A node with `prefab=<name>` starts as a copy of that prefab. Its own
+/// attributes and component attributes override the prefab's one by one, and its
+/// children are added after the prefab's.
+///
+/// Saving writes back what was read, apart from comments and layout, so files
+/// survive a load and save unchanged in meaning.
+#[derive(Clone, Debug, Default)]
+pub struct SceneFile {
+    pub prefabs: Vec<NodeEntry>,
+    pub nodes: Vec<NodeEntry>,
+}
+
+/// Splits a line into words, keeping quoted text together. Returns each word and
+/// whether any of it was quoted.
+fn tokenize(line: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars();
+    let mut token: Option<(String, bool)> = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => break,
+            '"' => {
+                let (text, quoted) = token.get_or_insert_with(Default::default);
+                *quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => text.extend(chars.next()),
+                        Some(c) => text.push(c),
+                        None => return Err("quoted text is never closed".to_string()),
+                    }
+                }
+            }
+            c if c.is_whitespace() => tokens.extend(token.take()),
+            c => token.get_or_insert_with(Default::default).0.push(c),
+        }
+    }
+    tokens.extend(token);
+    Ok(tokens)
+}
+
+fn parse_attributes(
+    tokens: &[(String, bool)],
+    line: usize,
+) -> Result<Vec<Attribute>, SceneFileError> {
+    tokens
+        .iter()
+        .map(|(token, quoted)| {
+            let (key, value) = token.split_once('=').ok_or_else(|| {
+                parse_error(line, format!("expected `key=value`, found `{token}`"))
+            })?;
+            Ok(Attribute {
+                key: key.to_string(),
+                value: Value::parse(value, *quoted)
+                    .map_err(|message| parse_error(line, message))?,
+                line,
+            })
+        })
+        .collect()
+}
+
+/// An open `node` or `prefab` block.
+struct Block {
+    entry: NodeEntry,
+    prefab: bool,
+}
+
+impl SceneFile {
+    /// Reads a scene file. Attribute values are only checked by
+    /// [`SceneFile::resolve`].
+    pub fn parse(text: &str) -> Result<Self, SceneFileError> {
+        let mut file = Self::default();
+        let mut open: Vec<Block> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |message: String| parse_error(line_number, message);
+            let tokens = tokenize(line).map_err(error)?;
+            let Some(((keyword, _), rest)) = tokens.split_first() else {
+                continue;
+            };
+            match keyword.as_str() {
+                "node" | "prefab" => {
+                    let prefab = keyword == "prefab";
+                    if prefab && !open.is_empty() {
+                        return Err(error(
+                            "prefabs can only be declared at the top level".into(),
+                        ));
+                    }
+                    let Some(((name, _), rest)) = rest.split_first() else {
+                        return Err(error(format!("`{keyword}` needs a name")));
+                    };
+                    if prefab && file.prefabs.iter().any(|prefab| prefab.name == *name) {
+                        return Err(error(format!("prefab `{name}` is declared twice")));
+                    }
+                    open.push(Block {
+                        entry: NodeEntry {
+                            attributes: parse_attributes(rest, line_number)?,
+                            line: line_number,
+                            ..NodeEntry::new(name.as_str())
+                        },
+                        prefab,
+                    });
+                }
+                "end" => {
+                    let block = open.pop().ok_or_else(|| {
+                        error("`end` without a `node` or `prefab` to close".into())
+                    })?;
+                    match open.last_mut() {
+                        Some(parent) => parent.entry.children.push(block.entry),
+                        None if block.prefab => file.prefabs.push(block.entry),
+                        None => file.nodes.push(block.entry),
+                    }
+                }
+                keyword => {
+                    let kind = ComponentKind::ALL
+                        .into_iter()
+                        .find(|kind| kind.name() == keyword)
+                        .ok_or_else(|| error(format!("unknown keyword `{keyword}`")))?;
+                    let block = open
+                        .last_mut()
+                        .ok_or_else(|| error(format!("`{keyword}` must be inside a node")))?;
+                    if block.entry.components.iter().any(|c| c.kind == kind) {
+                        return Err(error(format!(
+                            "node `{}` already has a {keyword}",
+                            block.entry.name
+                        )));
+                    }
+                    block.entry.components.push(ComponentEntry {
+                        kind,
+                        attributes: parse_attributes(rest, line_number)?,
+                        line: line_number,
+                    });
+                }
+            }
+        }
+        if let Some(block) = open.pop() {
+            let keyword = if block.prefab { "prefab" } else { "node" };
+            return Err(parse_error(
+                block.entry.line,
+                format!(
+                    "`{keyword} {}` is never closed with `end`",
+                    block.entry.name
+                ),
+            ));
+        }
+        Ok(file)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneFileError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneFileError> {
+        Ok(std::fs::write(path, self.to_string())?)
+    }
+
+    /// Expands prefabs and checks every attribute, giving the nodes ready to
+    /// instantiate. Unused prefabs are checked too.
+    pub fn resolve(&self) -> Result<Vec<NodeTemplate>, SceneFileError> {
+        for prefab in &self.prefabs {
+            self.resolve_node(prefab, &mut Vec::new())?;
+        }
+        self.nodes
+            .iter()
+            .map(|node| self.resolve_node(node, &mut Vec::new()))
+            .collect()
+    }
+
+    /// A node made from the prefab `name`, e.g. to spawn pickups while playing.
+    pub fn prefab(&self, name: &str) -> Result<NodeTemplate, SceneFileError> {
+        let prefab = self
+            .prefabs
+            .iter()
+            .find(|prefab| prefab.name == name)
+            .ok_or_else(|| SceneFileError::UnknownPrefab {
+                line: None,
+                name: name.to_string(),
+            })?;
+        self.resolve_node(prefab, &mut Vec::new())
+    }
+
+    /// `expanding` holds the prefabs being expanded above this node, so one that
+    /// contains itself is caught.
+    fn resolve_node(
+        &self,
+        entry: &NodeEntry,
+        expanding: &mut Vec<String>,
+    ) -> Result<NodeTemplate, SceneFileError> {
+        let depth = expanding.len();
+        let merged = self.expand(entry, expanding)?;
+        let mut template = NodeTemplate::from_entry(&merged)?;
+        template.children = merged
+            .children
+            .iter()
+            .map(|child| self.resolve_node(child, expanding))
+            .collect::<Result<_, _>>()?;
+        expanding.truncate(depth);
+        Ok(template)
+    }
+
+    /// `entry` laid over the prefab it is an instance of, if any.
+    fn expand(
+        &self,
+        entry: &NodeEntry,
+        expanding: &mut Vec<String>,
+    ) -> Result<NodeEntry, SceneFileError> {
+        let Some(attribute) = entry.attribute("prefab") else {
+            return Ok(entry.clone());
+        };
+        let name = attribute.text()?;
+        if expanding.iter().any(|expanded| expanded == name) {
+            return Err(parse_error(
+                attribute.line,
+                format!("prefab `{name}` contains itself"),
+            ));
+        }
+        let prefab = self
+            .prefabs
+            .iter()
+            .find(|prefab| prefab.name == name)
+            .ok_or_else(|| SceneFileError::UnknownPrefab {
+                line: Some(attribute.line),
+                name: name.to_string(),
+            })?;
+        expanding.push(name.to_string());
+        let base = self.expand(prefab, expanding)?;
+
+        let not_prefab = |attribute: &&Attribute| attribute.key != "prefab";
+        let mut merged = NodeEntry {
+            name: entry.name.clone(),
+            attributes: base.attributes.iter().filter(not_prefab).cloned().collect(),
+            components: base.components,
+            children: base.children,
+            line: entry.line,
+        };
+        merged
+            .attributes
+            .extend(entry.attributes.iter().filter(not_prefab).cloned());
+        for component in &entry.components {
+            match merged
+                .components
+                .iter_mut()
+                .find(|c| c.kind == component.kind)
+            {
+                // Later attributes win, so the instance's override the prefab's.
+                Some(base) => base.attributes.extend(component.attributes.iter().cloned()),
+                None => merged.components.push(component.clone()),
+            }
+        }
+        merged.children.extend(entry.children.iter().cloned());
+        Ok(merged)
+    }
+}
+
+impl fmt::Display for SceneFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let blocks = self
+            .prefabs
+            .iter()
+            .map(|prefab| ("prefab", prefab))
+            .chain(self.nodes.iter().map(|node| ("node", node)));
+        for (i, (keyword, entry)) in blocks.enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            entry.write(f, keyword, 0)?;
+        }
+        Ok(())
+    }
+}
+
+/// A textured quad, with the texture given as a path for [`SceneAssets`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteComponent {
+    pub texture: String,
+    pub size: [f32; 2],
+    pub uv_rect: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShapeKind {
+    Circle { radius: f32, segments: u16 },
+    Square { size: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapeComponent {
+    pub kind: ShapeKind,
+    pub color: [f32; 4],
+}
+
+/// Bounds for gameplay to test against, centred on the node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Collider {
+    Circle { radius: f32 },
+    Rect { size: [f32; 2] },
+}
+
+/// A node with prefabs expanded and every component read, ready to instantiate.
+#[derive(Clone, Debug)]
+pub struct NodeTemplate {
+    pub name: String,
+    pub layer: i32,
+    pub visible: bool,
+    pub transform: Transform,
+    pub sprite: Option<SpriteComponent>,
+    pub shape: Option<ShapeComponent>,
+    pub collider: Option<Collider>,
+    /// Positioned at the node's origin; move it to the node's world position
+    /// once the node is placed.
+    pub light: Option<Light>,
+    /// Looking at the node's origin from `distance` in front of it, like the light
+    /// relative to the node.
+    pub camera: Option<Camera>,
+    pub children: Vec<NodeTemplate>,
+}
+
+impl NodeTemplate {
+    fn from_entry(entry: &NodeEntry) -> Result<Self, SceneFileError> {
+        let mut template = Self {
+            name: entry.name.clone(),
+            layer: 0,
+            visible: true,
+            transform: Transform::default(),
+            sprite: None,
+            shape: None,
+            collider: None,
+            light: None,
+            camera: None,
+            children: Vec::new(),
+        };
+        for attribute in &entry.attributes {
+            match attribute.key.as_str() {
+                "prefab" => {}
+                "layer" => template.layer = attribute.integer()?,
+                "visible" => template.visible = attribute.boolean()?,
+                _ => return Err(attribute.unknown("node")),
+            }
+        }
+        for component in &entry.components {
+            let attributes = &component.attributes;
+            match component.kind {
+                ComponentKind::Transform => template.transform = read_transform(attributes)?,
+                ComponentKind::Sprite => template.sprite = Some(read_sprite(component)?),
+                ComponentKind::Shape => template.shape = Some(read_shape(attributes)?),
+                ComponentKind::Collider => template.collider = Some(read_collider(attributes)?),
+                ComponentKind::Light => template.light = Some(read_light(attributes)?),
+                ComponentKind::Camera => template.camera = Some(read_camera(attributes)?),
+            }
+        }
+        Ok(template)
+    }
+
+    /// Adds this node and its children to `scene`, under `parent` or at the top
+    /// level. Returns every node added, depth first, with the template it came
+    /// from so lights, cameras and colliders can be picked up. Nothing is added if
+    /// it fails.
+    pub fn instantiate<'a>(
+        &'a self,
+        scene: &mut Scene,
+        parent: Option<NodeId>,
+        assets: &mut impl SceneAssets,
+    ) -> Result<Vec<(NodeId, &'a NodeTemplate)>, SceneFileError> {
+        let mut node = Node::new(self.name.as_str())
+            .with_transform(self.transform)
+            .with_layer(self.layer);
+        node.visible = self.visible;
+        if let Some(shape) = &self.shape {
+            let renderable = match shape.kind {
+                ShapeKind::Circle { radius, segments } => {
+                    Renderable::shape(&Circle::new([0.0, 0.0], segments, radius), shape.color)
+                }
+                ShapeKind::Square { size } => {
+                    Renderable::shape(&Square::new([0.0, 0.0], size), shape.color)
+                }
+            };
+            node.set_renderable(renderable, assets.shape_material());
+        }
+        // A node draws one renderable, so a sprite wins over a shape.
+        if let Some(sprite) = &self.sprite {
+            let material = assets
+                .sprite_material(&sprite.texture)
+                .ok_or_else(|| SceneFileError::MissingTexture(sprite.texture.clone()))?;
+            let renderable = Renderable::Sprite {
+                size: sprite.size,
+                uv_rect: sprite.uv_rect,
+            };
+            node.set_renderable(renderable, material);
+        }
+        let id = match parent {
+            Some(parent) => scene
+                .add_child(parent, node)
+                .expect("instantiating under a node that is in the scene"),
+            None => scene.add(node),
+        };
+        let mut added = vec![(id, self)];
+        for child in &self.children {
+            match child.instantiate(scene, Some(id), assets) {
+                Ok(nodes) => added.extend(nodes),
+                Err(e) => {
+                    // Leave the scene as it was rather than half a level.
+                    let _ = scene.remove(id);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(added)
+    }
+}
+
+/// Where instantiated nodes get materials for the assets a scene file refers to.
+pub trait SceneAssets {
+    /// A textured material showing the texture at `path`, if there is one.
+    fn sprite_material(&mut self, path: &str) -> Option<Arc<Material>>;
+    /// A coloured material for shapes.
+    fn shape_material(&mut self) -> Arc<Material>;
+}
+
+fn read_transform(attributes: &[Attribute]) -> Result<Transform, SceneFileError> {
+    let mut transform = Transform::default();
+    for attribute in attributes {
+        match attribute.key.as_str() {
+            "position" => {
+                transform.translation = match &attribute.value {
+                    Value::Numbers(numbers) if numbers.len() == 2 => {
+                        Vector3::new(numbers[0], numbers[1], 0.0)
+                    }
+                    Value::Numbers(numbers) if numbers.len() == 3 => {
+                        Vector3::new(numbers[0], numbers[1], numbers[2])
+                    }
+                    _ => return Err(attribute.error("2 or 3 numbers")),
+                };
+            }
+            "rotation" => transform.rotation = attribute.number()?,
+            "scale" => transform.scale = attribute.numbers::<2>()?.into(),
+            _ => return Err(attribute.unknown("transform")),
+        }
+    }
+    Ok(transform)
+}
+
+fn read_sprite(component: &ComponentEntry) -> Result<SpriteComponent, SceneFileError> {
+    let mut sprite = SpriteComponent {
+        texture: String::new(),
+        size: [1.0, 1.0],
+        uv_rect: [0.0, 0.0, 1.0, 1.0],
+    };
+    for attribute in &component.attributes {
+        match attribute.key.as_str() {
+            "texture" => sprite.texture = attribute.text()?.to_string(),
+            "size" => sprite.size = attribute.numbers()?,
+            "uv" => sprite.uv_rect = attribute.numbers()?,
+            _ => return Err(attribute.unknown("sprite")),
+        }
+    }
+    if sprite.texture.is_empty() {
+        return Err(parse_error(
+            component.line,
+            "`sprite` needs a `texture`".to_string(),
+        ));
+    }
+    Ok(sprite)
+}
+
+fn read_shape(attributes: &[Attribute]) -> Result<ShapeComponent, SceneFileError> {
+    let mut kind = None;
+    let (mut radius, mut segments, mut size) = (0.5, 20, 1.0);
+    let mut color = [1.0; 4];
+    for attribute in attributes {
+        match attribute.key.as_str() {
+            "kind" => kind = Some(attribute),
+            "radius" => radius = attribute.number()?,
+            "segments" => {
+                segments = attribute.integer()?;
+                // A circle needs at least a triangle's worth of segments.
+                if segments < 3 {
+                    return Err(attribute.error("a whole number of at least 3"));
+                }
+            }
+            "size" => size = attribute.number()?,
+            "color" => color = attribute.numbers()?,
+            _ => return Err(attribute.unknown("shape")),
+        }
+    }
+    let kind = match kind.map(Attribute::text).transpose()? {
+        Some("circle") | None => ShapeKind::Circle { radius, segments },
+        Some("square") => ShapeKind::Square { size },
+        Some(_) => return Err(kind.unwrap().error("`circle` or `square`")),
+    };
+    Ok(ShapeComponent { kind, color })
+}
+
+fn read_collider(attributes: &[Attribute]) -> Result<Collider, SceneFileError> {
+    let mut kind = None;
+    let (mut radius, mut size) = (0.5, [1.0, 1.0]);
+    for attribute in attributes {
+        match attribute.key.as_str() {
+            "kind" => kind = Some(attribute),
+            "radius" => radius = attribute.number()?,
+            "size" => size = attribute.numbers()?,
+            _ => return Err(attribute.unknown("collider")),
+        }
+    }
+    match kind.map(Attribute::text).transpose()? {
+        Some("circle") | None => Ok(Collider::Circle { radius }),
+        Some("rect") => Ok(Collider::Rect { size }),
+        Some(_) => Err(kind.unwrap().error("`circle` or `rect`")),
+    }
+}
+
+fn read_light(attributes: &[Attribute]) -> Result<Light, SceneFileError> {
+    let mut light = Light::point([0.0, 0.0], [1.0, 1.0, 1.0], 1.0);
+    let mut kind = None;
+    let mut direction = [0.0, -1.0];
+    let (mut inner_angle, mut outer_angle) = (0.3, 0.5);
+    let mut height = None;
+    for attribute in attributes {
+        match attribute.key.as_str() {
+            "kind" => kind = Some(attribute),
+            "color" => light.color = attribute.numbers()?,
+            "intensity" => light.intensity = attribute.number()?,
+            "radius" => light.radius = attribute.number()?,
+            "falloff" => light.falloff = attribute.number()?,
+            "height" => height = Some(attribute.number()?),
+            "shadows" => light.shadows = attribute.boolean()?,
+            "softness" => light.shadow_softness = attribute.number()?,
+            "direction" => direction = attribute.numbers()?,
+            "inner_angle" => inner_angle = attribute.number()?,
+            "outer_angle" => outer_angle = attribute.number()?,
+            _ => return Err(attribute.unknown("light")),
+        }
+    }
+    // Like `Light::point`, the height follows the radius unless given.
+    light.height = height.unwrap_or(light.radius * 0.25);
+    light.kind = match kind.map(Attribute::text).transpose()? {
+        Some("point") | None => LightKind::Point,
+        Some("spot") => LightKind::Spot {
+            direction,
+            inner_angle,
+            outer_angle,
+        },
+        Some("directional") => LightKind::Directional { direction },
+        Some(_) => return Err(kind.unwrap().error("`point`, `spot` or `directional`")),
+    };
+    Ok(light)
+}
+
+fn read_camera(attributes: &[Attribute]) -> Result<Camera, SceneFileError> {
+    let mut camera = Camera {
+        eye: Point3::new(0.0, 0.0, 3.0),
+        target: Point3::new(0.0, 0.0, 0.0),
+        up: Vector3::unit_y(),
+        aspect: 1.0,
+        fovy: 45.0,
+        znear: 0.1,
+        zfar: 100.0,
+    };
+    for attribute in attributes {
+        match attribute.key.as_str() {
+            "distance" => camera.eye.z = attribute.number()?,
+            "fovy" => camera.fovy = attribute.number()?,
+            "znear" => camera.znear = attribute.number()?,
+            "zfar" => camera.zfar = attribute.number()?,
+            _ => return Err(attribute.unknown("camera")),
+        }
+    }
+    Ok(camera)
+}
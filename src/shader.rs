@@ -0,0 +1,319 @@
+use std::fmt;
+
+use naga::{Binding, ScalarKind, ShaderStage, TypeInner, VectorSize};
+
+/// A compiled WGSL module together with the naga IR it was parsed into.
+///
+/// Keeping the IR around lets us check pipeline inputs against what the shader
+/// actually declares before wgpu gets a chance to draw garbage.
+#[derive(Clone)]
+pub struct Shader {
+    pub label: String,
+    pub module: wgpu::ShaderModule,
+    ir: naga::Module,
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The WGSL failed to parse. Holds the formatted diagnostic.
+    Parse { label: String, message: String },
+    /// The WGSL parsed but is invalid, such as a type error. Holds the formatted
+    /// diagnostic.
+    Invalid { label: String, message: String },
+    /// The requested entry point does not exist as a vertex stage.
+    MissingEntryPoint { label: String, entry_point: String },
+    /// The shader reads a location that none of the vertex buffers provide.
+    MissingAttribute {
+        label: String,
+        location: u32,
+        expected: String,
+    },
+    /// A vertex buffer provides a location with a different type than the shader reads.
+    AttributeMismatch {
+        label: String,
+        location: u32,
+        expected: String,
+        found: wgpu::VertexFormat,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Parse { label, message } => {
+                write!(f, "failed to parse shader '{label}':\n{message}")
+            }
+            ShaderError::Invalid { label, message } => {
+                write!(f, "shader '{label}' is invalid:\n{message}")
+            }
+            ShaderError::MissingEntryPoint { label, entry_point } => {
+                write!(
+                    f,
+                    "shader '{label}' has no vertex entry point '{entry_point}'"
+                )
+            }
+            ShaderError::MissingAttribute {
+                label,
+                location,
+                expected,
+            } => write!(
+                f,
+                "shader '{label}' reads {expected} at @location({location}) \
+                 but no vertex buffer provides that location"
+            ),
+            ShaderError::AttributeMismatch {
+                label,
+                location,
+                expected,
+                found,
+            } => write!(
+                f,
+                "shader '{label}' reads {expected} at @location({location}) \
+                 but the vertex layout provides {found:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// The scalar kind, scalar width in bytes and component count of a single shader
+/// input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct InputType {
+    kind: ScalarKind,
+    width: u8,
+    components: u32,
+}
+
+impl InputType {
+    /// The type a vertex attribute of `format` arrives as in the shader. Normalized
+    /// and narrow formats are widened to 32-bit scalars, so only the 64-bit floats
+    /// differ in width.
+    fn from_format(format: wgpu::VertexFormat) -> Self {
+        use wgpu::VertexFormat as F;
+        let (kind, width, components) = match format {
+            F::Uint8 | F::Uint16 | F::Uint32 => (ScalarKind::Uint, 4, 1),
+            F::Uint8x2 | F::Uint16x2 | F::Uint32x2 => (ScalarKind::Uint, 4, 2),
+            F::Uint32x3 => (ScalarKind::Uint, 4, 3),
+            F::Uint8x4 | F::Uint16x4 | F::Uint32x4 => (ScalarKind::Uint, 4, 4),
+            F::Sint8 | F::Sint16 | F::Sint32 => (ScalarKind::Sint, 4, 1),
+            F::Sint8x2 | F::Sint16x2 | F::Sint32x2 => (ScalarKind::Sint, 4, 2),
+            F::Sint32x3 => (ScalarKind::Sint, 4, 3),
+            F::Sint8x4 | F::Sint16x4 | F::Sint32x4 => (ScalarKind::Sint, 4, 4),
+            F::Unorm8 | F::Snorm8 | F::Unorm16 | F::Snorm16 | F::Float16 | F::Float32 => {
+                (ScalarKind::Float, 4, 1)
+            }
+            F::Unorm8x2
+            | F::Snorm8x2
+            | F::Unorm16x2
+            | F::Snorm16x2
+            | F::Float16x2
+            | F::Float32x2 => (ScalarKind::Float, 4, 2),
+            F::Float32x3 => (ScalarKind::Float, 4, 3),
+            F::Unorm8x4
+            | F::Snorm8x4
+            | F::Unorm16x4
+            | F::Snorm16x4
+            | F::Float16x4
+            | F::Float32x4
+            | F::Unorm10_10_10_2
+            | F::Unorm8x4Bgra => (ScalarKind::Float, 4, 4),
+            F::Float64 => (ScalarKind::Float, 8, 1),
+            F::Float64x2 => (ScalarKind::Float, 8, 2),
+            F::Float64x3 => (ScalarKind::Float, 8, 3),
+            F::Float64x4 => (ScalarKind::Float, 8, 4),
+        };
+        Self {
+            kind,
+            width,
+            components,
+        }
+    }
+
+    /// A vertex format that arrives as exactly this type. There is none for
+    /// `f16` or `bool` inputs.
+    fn to_format(self) -> Option<wgpu::VertexFormat> {
+        use wgpu::VertexFormat as F;
+        let formats = match (self.kind, self.width) {
+            (ScalarKind::Uint, 4) => [F::Uint32, F::Uint32x2, F::Uint32x3, F::Uint32x4],
+            (ScalarKind::Sint, 4) => [F::Sint32, F::Sint32x2, F::Sint32x3, F::Sint32x4],
+            (ScalarKind::Float, 4) => [F::Float32, F::Float32x2, F::Float32x3, F::Float32x4],
+            (ScalarKind::Float, 8) => [F::Float64, F::Float64x2, F::Float64x3, F::Float64x4],
+            _ => return None,
+        };
+        formats.get(self.components as usize - 1).copied()
+    }
+}
+
+impl fmt::Display for InputType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scalar = match (self.kind, self.width) {
+            (ScalarKind::Sint, _) => "i32",
+            (ScalarKind::Uint, _) => "u32",
+            (ScalarKind::Bool, _) => "bool",
+            (_, 2) => "f16",
+            (_, 8) => "f64",
+            _ => "f32",
+        };
+        match self.components {
+            1 => write!(f, "{scalar}"),
+            n => write!(f, "vec{n}<{scalar}>"),
+        }
+    }
+}
+
+impl Shader {
+    pub fn from_wgsl(
+        device: &wgpu::Device,
+        label: &str,
+        source: &str,
+    ) -> Result<Self, ShaderError> {
+        // Parsing and validating with naga first gives us a readable error instead
+        // of a wgpu panic.
+        let ir = naga::front::wgsl::parse_str(source).map_err(|e| ShaderError::Parse {
+            label: label.to_string(),
+            message: e.emit_to_string(source),
+        })?;
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&ir)
+        .map_err(|e| ShaderError::Invalid {
+            label: label.to_string(),
+            message: e.emit_to_string(source),
+        })?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        Ok(Self {
+            label: label.to_string(),
+            module,
+            ir,
+        })
+    }
+
+    /// Checks that every `@location` read by `entry_point` is provided by one of
+    /// `buffers` with a matching scalar kind, width and component count.
+    pub fn check_vertex_input(
+        &self,
+        entry_point: &str,
+        buffers: &[wgpu::VertexBufferLayout],
+    ) -> Result<(), ShaderError> {
+        let entry = self.vertex_entry_point(entry_point)?;
+        for (location, expected) in self.vertex_inputs(&entry.function) {
+            let provided = buffers
+                .iter()
+                .flat_map(|b| b.attributes.iter())
+                .find(|a| a.shader_location == location);
+            match provided {
+                None => {
+                    return Err(ShaderError::MissingAttribute {
+                        label: self.label.clone(),
+                        location,
+                        expected: expected.to_string(),
+                    })
+                }
+                Some(attribute) if InputType::from_format(attribute.format) != expected => {
+                    return Err(ShaderError::AttributeMismatch {
+                        label: self.label.clone(),
+                        location,
+                        expected: expected.to_string(),
+                        found: attribute.format,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Attributes for a single vertex buffer that provides every `@location` read
+    /// by `entry_point` with the type it reads, for building a pipeline to test the
+    /// shader with. Inputs no vertex format can provide are left out.
+    pub fn vertex_attributes(
+        &self,
+        entry_point: &str,
+    ) -> Result<Vec<wgpu::VertexAttribute>, ShaderError> {
+        let entry = self.vertex_entry_point(entry_point)?;
+        let mut offset = 0;
+        let attributes = self
+            .vertex_inputs(&entry.function)
+            .into_iter()
+            .filter_map(|(shader_location, input)| {
+                let format = input.to_format()?;
+                let attribute = wgpu::VertexAttribute {
+                    format,
+                    offset,
+                    shader_location,
+                };
+                offset += format.size();
+                Some(attribute)
+            })
+            .collect();
+        Ok(attributes)
+    }
+
+    fn vertex_entry_point(&self, entry_point: &str) -> Result<&naga::EntryPoint, ShaderError> {
+        self.ir
+            .entry_points
+            .iter()
+            .find(|e| e.stage == ShaderStage::Vertex && e.name == entry_point)
+            .ok_or_else(|| ShaderError::MissingEntryPoint {
+                label: self.label.clone(),
+                entry_point: entry_point.to_string(),
+            })
+    }
+
+    /// Collects the `@location` inputs of an entry point, looking inside struct
+    /// arguments as well as plain ones.
+    fn vertex_inputs(&self, function: &naga::Function) -> Vec<(u32, InputType)> {
+        let mut inputs = Vec::new();
+        for argument in &function.arguments {
+            match &argument.binding {
+                Some(binding) => self.push_input(&mut inputs, binding, argument.ty),
+                None => {
+                    if let TypeInner::Struct { members, .. } = &self.ir.types[argument.ty].inner {
+                        for member in members {
+                            if let Some(binding) = &member.binding {
+                                self.push_input(&mut inputs, binding, member.ty);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        inputs
+    }
+
+    fn push_input(
+        &self,
+        inputs: &mut Vec<(u32, InputType)>,
+        binding: &Binding,
+        ty: naga::Handle<naga::Type>,
+    ) {
+        let Binding::Location { location, .. } = binding else {
+            return;
+        };
+        let input = match self.ir.types[ty].inner {
+            TypeInner::Scalar(scalar) => InputType {
+                kind: scalar.kind,
+                width: scalar.width,
+                components: 1,
+            },
+            TypeInner::Vector { size, scalar } => InputType {
+                kind: scalar.kind,
+                width: scalar.width,
+                components: match size {
+                    VectorSize::Bi => 2,
+                    VectorSize::Tri => 3,
+                    VectorSize::Quad => 4,
+                },
+            },
+            _ => return,
+        };
+        inputs.push((*location, input));
+    }
+}
@@ -0,0 +1,54 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct MaterialParams {
+    tint: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> material: MaterialParams;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct InstanceInput {
+    @location(8) position: vec2<f32>,
+    @location(9) rotation: f32,
+    @location(10) scale: vec2<f32>,
+    @location(11) tint: vec4<f32>,
+    // The uv rect at location 12 is unused without a texture.
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let local = model.position.xy * instance.scale;
+    let world = instance.position + vec2<f32>(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    var out: VertexOutput;
+    out.color = model.color * instance.tint * material.tint;
+    out.clip_position = camera.view_proj * vec4<f32>(world, model.position.z, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
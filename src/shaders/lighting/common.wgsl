@@ -0,0 +1,58 @@
+// Shared by lights.wgsl and shadowed_lights.wgsl, which are appended to this.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Encoded normals of everything on screen, flat (0.5, 0.5, 1.0) where no
+// normal-mapped sprite was drawn.
+@group(1) @binding(0)
+var t_normal: texture_2d<f32>;
+
+const POINT: u32 = 0u;
+const SPOT: u32 = 1u;
+const DIRECTIONAL: u32 = 2u;
+
+struct Light {
+    position: vec2<f32>,
+    direction: vec2<f32>,
+    color: vec3<f32>,
+    intensity: f32,
+    radius: f32,
+    falloff: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+    kind: u32,
+    height: f32,
+    _pad0: f32,
+    _pad1: f32,
+};
+@group(2) @binding(0)
+var<storage, read> lights: array<Light>;
+
+// How much of `light` reaches the pixel at `frag_position`, which shows the
+// world at `world_position`.
+fn shade(light: Light, world_position: vec2<f32>, frag_position: vec2<f32>) -> vec3<f32> {
+    let encoded = textureLoad(t_normal, vec2<i32>(frag_position), 0).rgb;
+    let normal = normalize(encoded * 2.0 - 1.0);
+
+    var to_light: vec3<f32>;
+    var attenuation = 1.0;
+    if light.kind == DIRECTIONAL {
+        to_light = vec3<f32>(-light.direction, light.height);
+    } else {
+        let offset = light.position - world_position;
+        let distance = length(offset);
+        attenuation = pow(clamp(1.0 - distance / light.radius, 0.0, 1.0), light.falloff);
+        if light.kind == SPOT {
+            let cos_angle = dot(-offset / max(distance, 1e-5), light.direction);
+            attenuation *= smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+        }
+        to_light = vec3<f32>(offset, light.height);
+    }
+
+    let diffuse = max(dot(normal, normalize(to_light)), 0.0);
+    return light.color * light.intensity * attenuation * diffuse;
+}
@@ -0,0 +1,27 @@
+// Multiplies the light buffer over the scene. The pipeline uses the Multiply
+// blend mode, so all this has to do is output the light.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// A single counter clockwise triangle that covers the whole screen.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+@group(0) @binding(0)
+var t_light: texture_2d<f32>;
+@group(0) @binding(1)
+var s_light: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(t_light, s_light, in.uv).rgb, 1.0);
+}
@@ -0,0 +1,46 @@
+// Accumulates lights that cast no shadows into the light buffer, one instanced
+// quad per light.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec2<f32>,
+    @location(1) @interpolate(flat) light: u32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    // Two counter clockwise triangles making a unit quad.
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let light = lights[instance_index];
+
+    var out: VertexOutput;
+    out.light = instance_index;
+    if light.kind == DIRECTIONAL {
+        // Directional lights reach everywhere, so cover the whole screen.
+        out.clip_position = vec4<f32>(corner, 0.0, 1.0);
+        out.world_position = vec2<f32>(0.0);
+    } else {
+        // Everything else only needs the square its radius fits in.
+        let world_position = light.position + corner * light.radius;
+        out.clip_position = camera.view_proj * vec4<f32>(world_position, 0.0, 1.0);
+        out.world_position = world_position;
+    }
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let light = lights[in.light];
+    return vec4<f32>(shade(light, in.world_position, in.clip_position.xy), 1.0);
+}
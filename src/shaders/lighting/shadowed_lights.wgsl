@@ -0,0 +1,32 @@
+// Accumulates shadow casting lights into the light buffer. Each light is drawn
+// as the triangle fan of the area it can see, so occluded pixels are never
+// touched. Soft shadows draw several fans from around the light, each weighted.
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) light: u32,
+    @location(2) weight: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec2<f32>,
+    @location(1) @interpolate(flat) light: u32,
+    @location(2) weight: f32,
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 0.0, 1.0);
+    out.world_position = model.position;
+    out.light = model.light;
+    out.weight = model.weight;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let light = lights[in.light];
+    return vec4<f32>(shade(light, in.world_position, in.clip_position.xy) * in.weight, 1.0);
+}
@@ -0,0 +1,71 @@
+// Turns the summed blob field into bubbles over the scene: thresholded with a
+// soft edge, refracting the background, with a tinted rim and a highlight.
+
+struct Params {
+    rim_color: vec4<f32>,
+    // Field strength at which the surface of a bubble sits.
+    threshold: f32,
+    // Width of the anti-aliased edge, in field units either side of the threshold.
+    smoothness: f32,
+    // How far the background is displaced at the steepest part of the surface,
+    // in pixels.
+    refraction: f32,
+    highlight: f32,
+    // How much the blobs' own colour tints the background seen through them.
+    tint: f32,
+    // Width of the rim inside the threshold, in field units.
+    rim_width: f32,
+    _pad0: f32,
+    _pad1: f32,
+};
+
+@group(2) @binding(0)
+var t_field: texture_2d<f32>;
+@group(2) @binding(1)
+var s_field: sampler;
+
+// Scales the field gradient, measured per pixel, into the slope of the surface.
+const STEEPNESS: f32 = 40.0;
+
+fn field_at(uv: vec2<f32>) -> f32 {
+    return textureSample(t_field, s_field, uv).a;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / globals.resolution;
+    let sample = textureSample(t_field, s_field, in.uv);
+    let field = sample.a;
+    let background = textureSample(t_input, s_input, in.uv);
+
+    // Treat the field as a height map and light the bulge it makes. The gradient
+    // points up the slope, towards the middle of each bubble.
+    let gradient = vec2<f32>(
+        field_at(in.uv + vec2<f32>(texel.x, 0.0)) - field_at(in.uv - vec2<f32>(texel.x, 0.0)),
+        field_at(in.uv + vec2<f32>(0.0, texel.y)) - field_at(in.uv - vec2<f32>(0.0, texel.y)),
+    ) * 0.5;
+    let normal = normalize(vec3<f32>(-gradient * STEEPNESS, 1.0));
+
+    // Looking through the curved surface pulls the background in from the edges,
+    // like a lens.
+    let behind = textureSample(t_input, s_input, in.uv - normal.xy * params.refraction * texel);
+
+    // Light from the top left of the screen.
+    let to_light = normalize(vec3<f32>(-0.5, -0.5, 1.0));
+    let half_vector = normalize(to_light + vec3<f32>(0.0, 0.0, 1.0));
+    let specular = pow(max(dot(normal, half_vector), 0.0), 48.0) * params.highlight;
+
+    let coverage = smoothstep(
+        params.threshold - params.smoothness,
+        params.threshold + params.smoothness,
+        field,
+    );
+    let rim = 1.0 - smoothstep(params.threshold, params.threshold + params.rim_width, field);
+    // Colour weighted by each blob's share of the field.
+    let color = sample.rgb / max(field, 1e-5);
+
+    var bubble = behind.rgb * mix(vec3<f32>(1.0), color, params.tint);
+    bubble = mix(bubble, params.rim_color.rgb, rim * params.rim_color.a);
+    bubble += vec3<f32>(specular);
+    return vec4<f32>(mix(background.rgb, bubble, coverage), background.a);
+}
@@ -0,0 +1,60 @@
+// Sums the field of every blob into a floating point target, one instanced quad
+// per blob. Alpha holds the field strength and rgb the colour weighted by it, so
+// overlapping blobs blend their colours where they merge.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct BlobInput {
+    @location(0) position: vec2<f32>,
+    @location(1) radius: f32,
+    @location(2) color: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Position within the blob's influence, -1 to 1 across the quad.
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec3<f32>,
+};
+
+// How far a blob's field reaches, relative to its radius. Far enough that nearby
+// blobs pull towards each other before they touch.
+const INFLUENCE: f32 = 2.0;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    blob: BlobInput,
+) -> VertexOutput {
+    // Two counter clockwise triangles making a unit quad.
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let world_position = blob.position + corner * blob.radius * INFLUENCE;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 0.0, 1.0);
+    out.local = corner;
+    out.color = blob.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // (1 - d^2)^2 falls smoothly to zero at the edge of the influence, so the sum
+    // has no creases where quads overlap.
+    let d2 = dot(in.local, in.local);
+    let falloff = max(1.0 - d2, 0.0);
+    let field = falloff * falloff;
+    return vec4<f32>(in.color * field, field);
+}
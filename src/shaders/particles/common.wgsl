@@ -0,0 +1,76 @@
+// Shared by the particle render shaders, which are appended to this. Each
+// particle is one instance of a quad, read straight out of the buffer the
+// simulation writes.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+const CURVE_SAMPLES: u32 = 32u;
+
+// Colour and size over a particle's life, baked to evenly spaced samples.
+// Sizes are packed four to a vec4 to keep the uniform array stride small.
+struct ParticleLook {
+    colors: array<vec4<f32>, 32>,
+    sizes: array<vec4<f32>, 8>,
+    shape: u32,
+    softness: f32,
+    _pad0: f32,
+    _pad1: f32,
+};
+@group(1) @binding(0)
+var<uniform> look: ParticleLook;
+
+struct InstanceInput {
+    @location(0) position: vec2<f32>,
+    @location(1) velocity: vec2<f32>,
+    @location(2) age: f32,
+    @location(3) lifetime: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+fn size_sample(index: u32) -> f32 {
+    return look.sizes[index / 4u][index % 4u];
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    particle: InstanceInput,
+) -> VertexOutput {
+    // Two counter clockwise triangles making a unit quad.
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    let t = clamp(particle.age / max(particle.lifetime, 1e-5), 0.0, 1.0);
+    let f = t * f32(CURVE_SAMPLES - 1u);
+    let i = min(u32(f), CURVE_SAMPLES - 2u);
+    let blend = f - f32(i);
+    let color = mix(look.colors[i], look.colors[i + 1u], blend);
+    var size = mix(size_sample(i), size_sample(i + 1u), blend);
+    // Dead particles collapse to a point and produce no fragments.
+    if particle.age >= particle.lifetime {
+        size = 0.0;
+    }
+
+    var out: VertexOutput;
+    let world_position = particle.position + corner * size * 0.5;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, -corner.y) * 0.5 + 0.5;
+    out.color = color;
+    return out;
+}
@@ -0,0 +1,24 @@
+struct MaterialParams {
+    tint: vec4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> material: MaterialParams;
+
+const SHAPE_CIRCLE: u32 = 0u;
+const SHAPE_BUBBLE: u32 = 1u;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = in.uv * 2.0 - 1.0;
+    let distance = length(p);
+    let edge = 1.0 - smoothstep(1.0 - look.softness, 1.0, distance);
+    var coverage = edge;
+    if look.shape == SHAPE_BUBBLE {
+        // A thin bright rim with a see-through middle and a small highlight.
+        let rim = smoothstep(0.55, 0.95, distance);
+        let highlight = 1.0 - smoothstep(0.0, 0.2, length(p - vec2<f32>(-0.35, -0.35)));
+        coverage = max(rim, highlight) * edge;
+    }
+    let color = in.color * material.tint;
+    return vec4<f32>(color.rgb, color.a * coverage);
+}
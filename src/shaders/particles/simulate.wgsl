@@ -0,0 +1,48 @@
+// Advances every live particle by one step. Mirrors `particles::simulate` on
+// the CPU, so keep the two in sync.
+
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    age: f32,
+    lifetime: f32,
+    _pad: vec2<f32>,
+};
+
+struct SimulationParams {
+    gravity: vec2<f32>,
+    dt: f32,
+    drag: f32,
+    acceleration: f32,
+    count: u32,
+    _pad0: f32,
+    _pad1: f32,
+};
+
+@group(0) @binding(0)
+var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(1)
+var<uniform> params: SimulationParams;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.count {
+        return;
+    }
+    var particle = particles[index];
+    if particle.age >= particle.lifetime {
+        return;
+    }
+
+    var acceleration = params.gravity;
+    let speed = length(particle.velocity);
+    if speed > 1e-5 {
+        acceleration += particle.velocity / speed * params.acceleration;
+    }
+    particle.velocity += acceleration * params.dt;
+    particle.velocity *= exp(-params.drag * params.dt);
+    particle.position += particle.velocity * params.dt;
+    particle.age += params.dt;
+    particles[index] = particle;
+}
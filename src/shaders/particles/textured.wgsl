@@ -0,0 +1,15 @@
+@group(2) @binding(0)
+var t_particle: texture_2d<f32>;
+@group(2) @binding(1)
+var s_particle: sampler;
+
+struct MaterialParams {
+    tint: vec4<f32>,
+};
+@group(3) @binding(0)
+var<uniform> material: MaterialParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_particle, s_particle, in.uv) * in.color * material.tint;
+}
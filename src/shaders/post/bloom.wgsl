@@ -0,0 +1,31 @@
+// Single pass bloom: blurs the parts of the image brighter than the threshold and
+// adds them back on top.
+
+struct Params {
+    threshold: f32,
+    intensity: f32,
+    radius: f32,
+    _pad: f32,
+};
+
+fn bright(uv: vec2<f32>) -> vec3<f32> {
+    let color = textureSample(t_input, s_input, uv).rgb;
+    return color * max(luminance(color) - params.threshold, 0.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base = textureSample(t_input, s_input, in.uv);
+    let texel = params.radius / globals.resolution;
+    var glow = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var x = -3; x <= 3; x++) {
+        for (var y = -3; y <= 3; y++) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            let weight = exp(-dot(offset, offset) / 8.0);
+            glow += bright(in.uv + offset * texel) * weight;
+            total += weight;
+        }
+    }
+    return vec4<f32>(base.rgb + glow / total * params.intensity, base.a);
+}
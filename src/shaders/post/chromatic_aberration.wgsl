@@ -0,0 +1,17 @@
+struct Params {
+    // Maximum channel offset in pixels, reached at the screen edges.
+    offset: f32,
+    _pad0: f32,
+    _pad1: f32,
+    _pad2: f32,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = (in.uv - 0.5) * 2.0;
+    let shift = direction * params.offset / globals.resolution;
+    let r = textureSample(t_input, s_input, in.uv + shift).r;
+    let ga = textureSample(t_input, s_input, in.uv).ga;
+    let b = textureSample(t_input, s_input, in.uv - shift).b;
+    return vec4<f32>(r, ga.x, b, ga.y);
+}
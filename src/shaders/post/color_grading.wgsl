@@ -0,0 +1,34 @@
+// Colour grading through a lookup table stored as a horizontal strip of `lut_size`
+// slices, each `lut_size` x `lut_size`, with blue selecting the slice.
+
+struct Params {
+    intensity: f32,
+    lut_size: f32,
+    _pad: vec2<f32>,
+};
+
+@group(2) @binding(0)
+var t_lut: texture_2d<f32>;
+@group(2) @binding(1)
+var s_lut: sampler;
+
+fn lut_sample(color: vec3<f32>, slice: f32) -> vec3<f32> {
+    let size = params.lut_size;
+    let u = (slice * size + color.r * (size - 1.0) + 0.5) / (size * size);
+    let v = (color.g * (size - 1.0) + 0.5) / size;
+    // Neighbouring pixels can land in slices far apart, so derivatives would pick
+    // a blurry mip. Always read the full-size table.
+    return textureSampleLevel(t_lut, s_lut, vec2<f32>(u, v), 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    // LUTs are authored against display colours, so look up in sRGB space.
+    let graded_input = clamp(linear_to_srgb(color.rgb), vec3<f32>(0.0), vec3<f32>(1.0));
+    let blue = graded_input.b * (params.lut_size - 1.0);
+    let slice = floor(blue);
+    let next = min(slice + 1.0, params.lut_size - 1.0);
+    let graded = mix(lut_sample(graded_input, slice), lut_sample(graded_input, next), blue - slice);
+    return vec4<f32>(mix(color.rgb, srgb_to_linear(graded), params.intensity), color.a);
+}
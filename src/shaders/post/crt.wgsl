@@ -0,0 +1,21 @@
+struct Params {
+    scanline_intensity: f32,
+    // Number of scanlines over the height of the screen.
+    line_count: f32,
+    curvature: f32,
+    _pad: f32,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Bulge the image outwards like the glass of an old tube.
+    let centered = in.uv * 2.0 - 1.0;
+    let bent = centered * (1.0 + params.curvature * dot(centered.yx, centered.yx));
+    let uv = bent * 0.5 + 0.5;
+    // Sampled before the bounds check, textureSample has to stay in uniform control flow.
+    let color = textureSample(t_input, s_input, uv);
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+    let scanline = 0.5 + 0.5 * sin(uv.y * params.line_count * 6.2831853);
+    let shaded = vec4<f32>(color.rgb * mix(1.0, scanline, params.scanline_intensity), color.a);
+    return select(vec4<f32>(0.0, 0.0, 0.0, 1.0), shaded, inside);
+}
@@ -0,0 +1,21 @@
+// Wobbles the image as if seen through moving water and tints it towards the deep.
+
+struct Params {
+    tint: vec4<f32>,
+    // Wobble size as a fraction of the screen.
+    amplitude: f32,
+    frequency: f32,
+    speed: f32,
+    _pad: f32,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let t = globals.time * params.speed;
+    let wobble = vec2<f32>(
+        sin(in.uv.y * params.frequency + t),
+        cos(in.uv.x * params.frequency * 0.8 + t * 1.3),
+    ) * params.amplitude;
+    let color = textureSample(t_input, s_input, in.uv + wobble);
+    return vec4<f32>(mix(color.rgb, color.rgb * params.tint.rgb, params.tint.a), color.a);
+}
@@ -0,0 +1,15 @@
+struct Params {
+    strength: f32,
+    radius: f32,
+    softness: f32,
+    _pad: f32,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    let aspect = globals.resolution.x / globals.resolution.y;
+    let distance = length((in.uv - 0.5) * vec2<f32>(aspect, 1.0));
+    let shade = smoothstep(params.radius, params.radius - params.softness, distance);
+    return vec4<f32>(color.rgb * mix(1.0, shade, params.strength), color.a);
+}
@@ -0,0 +1,50 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.color = model.color;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+// The glyph atlas of a font. Outline fonts are rasterized as white with coverage
+// in alpha, so the vertex colour decides their colour.
+@group(1) @binding(0)
+var t_glyphs: texture_2d<f32>;
+@group(1) @binding(1)
+var s_glyphs: sampler;
+
+struct MaterialParams {
+    tint: vec4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> material: MaterialParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_glyphs, s_glyphs, in.tex_coords) * in.color * material.tint;
+}
@@ -0,0 +1,49 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) layer: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    // Integers can't be interpolated, every vertex of a sprite has the same layer anyway.
+    @location(1) @interpolate(flat) layer: u32,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.layer = model.layer;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d_array<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+struct MaterialParams {
+    tint: vec4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> material: MaterialParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords, in.layer) * material.tint;
+}
@@ -0,0 +1,61 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(8) position: vec2<f32>,
+    @location(9) rotation: f32,
+    @location(10) scale: vec2<f32>,
+    @location(11) tint: vec4<f32>,
+    @location(12) uv_rect: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let local = model.position.xy * instance.scale;
+    let world = instance.position + vec2<f32>(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    var out: VertexOutput;
+    out.tex_coords = instance.uv_rect.xy + model.tex_coords * instance.uv_rect.zw;
+    out.tint = instance.tint;
+    out.clip_position = camera.view_proj * vec4<f32>(world, model.position.z, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+struct MaterialParams {
+    tint: vec4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> material: MaterialParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint * material.tint;
+}
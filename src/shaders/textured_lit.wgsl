@@ -0,0 +1,52 @@
+// Draws a sprite's normal map into the lighting normal buffer. The sprite's
+// colour comes from textured.wgsl as usual; this only tells the lights which
+// way each of its pixels faces.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+@group(2) @binding(0)
+var t_normal: texture_2d<f32>;
+@group(2) @binding(1)
+var s_normal: sampler;
+
+struct MaterialParams {
+    tint: vec4<f32>,
+};
+@group(3) @binding(0)
+var<uniform> material: MaterialParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_diffuse, s_diffuse, in.tex_coords).a * material.tint.a;
+    let normal = textureSample(t_normal, s_normal, in.tex_coords).rgb;
+    return vec4<f32>(normal, coverage);
+}
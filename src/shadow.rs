@@ -0,0 +1,144 @@
+use crate::shapes::Shape;
+
+/// Angle either side of every segment end that a ray is also cast at, so rays
+/// slip past corners and find whatever lies behind them.
+const CORNER_EPSILON: f32 = 1e-4;
+
+/// A closed polygon that blocks light, such as a wall, tile collision shape or
+/// prop. Lights with shadows enabled can't reach anything behind it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occluder {
+    points: Vec<[f32; 2]>,
+}
+
+impl Occluder {
+    /// An occluder with the given corners, in order around its edge. The last
+    /// point connects back to the first.
+    pub fn new(points: Vec<[f32; 2]>) -> Self {
+        Self { points }
+    }
+
+    /// An occluder with the same outline as `shape`, so the geometry drawn for
+    /// something can also make it cast shadows.
+    pub fn from_shape(shape: &impl Shape) -> Self {
+        Self::new(shape.outline())
+    }
+
+    pub fn points(&self) -> &[[f32; 2]] {
+        &self.points
+    }
+
+    fn edges(&self) -> impl Iterator<Item = Segment> + '_ {
+        let next = self.points.iter().cycle().skip(1);
+        self.points
+            .iter()
+            .zip(next)
+            .map(|(&a, &b)| Segment { a, b })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    a: [f32; 2],
+    b: [f32; 2],
+}
+
+impl Segment {
+    fn distance_to(self, point: [f32; 2]) -> f32 {
+        let edge = sub(self.b, self.a);
+        let length_squared = dot(edge, edge);
+        let t = if length_squared > 0.0 {
+            (dot(sub(point, self.a), edge) / length_squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let closest = [self.a[0] + edge[0] * t, self.a[1] + edge[1] * t];
+        length(sub(point, closest))
+    }
+
+    /// Distance along the ray from `origin` in `direction` at which it crosses this
+    /// segment, if it does.
+    fn intersect(self, origin: [f32; 2], direction: [f32; 2]) -> Option<f32> {
+        let edge = sub(self.b, self.a);
+        let denominator = cross(direction, edge);
+        if denominator.abs() < 1e-9 {
+            return None;
+        }
+        let to_start = sub(self.a, origin);
+        let t = cross(to_start, edge) / denominator;
+        let s = cross(to_start, direction) / denominator;
+        (t >= 0.0 && (0.0..=1.0).contains(&s)).then_some(t)
+    }
+}
+
+/// The part of the square of half-size `reach` around `origin` that can be seen from
+/// `origin` past `occluders`, as points in counter clockwise order around `origin`.
+///
+/// Returns `None` when no occluder comes within reach, in which case nothing is
+/// hidden and the whole square is visible.
+pub fn visibility_polygon(
+    origin: [f32; 2],
+    reach: f32,
+    occluders: &[Occluder],
+) -> Option<Vec<[f32; 2]>> {
+    // Anything further than the corner of the bounding square can't matter.
+    let max_distance = reach * std::f32::consts::SQRT_2;
+    let mut segments: Vec<Segment> = occluders
+        .iter()
+        .flat_map(Occluder::edges)
+        .filter(|segment| segment.distance_to(origin) < max_distance)
+        .collect();
+    if segments.is_empty() {
+        return None;
+    }
+
+    // The bounding square stops rays that miss every occluder.
+    let [x, y] = origin;
+    let bounds = Occluder::new(vec![
+        [x - reach, y - reach],
+        [x + reach, y - reach],
+        [x + reach, y + reach],
+        [x - reach, y + reach],
+    ]);
+    segments.extend(bounds.edges());
+
+    let mut angles: Vec<f32> = segments
+        .iter()
+        .flat_map(|segment| [segment.a, segment.b])
+        .flat_map(|corner| {
+            let angle = (corner[1] - y).atan2(corner[0] - x);
+            [angle - CORNER_EPSILON, angle, angle + CORNER_EPSILON]
+        })
+        .collect();
+    angles.sort_by(f32::total_cmp);
+    angles.dedup();
+
+    let polygon = angles
+        .into_iter()
+        .filter_map(|angle| {
+            let direction = [angle.cos(), angle.sin()];
+            let nearest = segments
+                .iter()
+                .filter_map(|segment| segment.intersect(origin, direction))
+                .min_by(f32::total_cmp)?;
+            Some([x + direction[0] * nearest, y + direction[1] * nearest])
+        })
+        .collect();
+    Some(polygon)
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn length(a: [f32; 2]) -> f32 {
+    dot(a, a).sqrt()
+}
@@ -4,6 +4,9 @@ pub trait Shape {
     fn tex_vertices(&self, tex_coords: [f32; 2]) -> Vec<TexturedVertex>;
     fn col_vertices(&self, color: [f32; 4]) -> Vec<ColoredVertex>;
     fn indices(&self) -> Vec<u16>;
+    /// The corners of the shape in order around its edge, e.g. to cast shadows
+    /// with [`Occluder::from_shape`](crate::shadow::Occluder::from_shape).
+    fn outline(&self) -> Vec<[f32; 2]>;
 }
 
 pub struct Circle {
@@ -73,10 +76,22 @@ impl Shape for Circle {
 
     fn indices(&self) -> Vec<u16> {
         (1u16..self.num_triangles() + 1)
-            .into_iter()
             .flat_map(|i| vec![i + 1, i, 0])
             .collect::<Vec<_>>()
     }
+
+    fn outline(&self) -> Vec<[f32; 2]> {
+        let angle_step = 2.0 * std::f32::consts::PI / self.num_vertices as f32;
+        (0..self.num_vertices)
+            .map(|i| {
+                let theta = -angle_step * i as f32;
+                [
+                    self.position[0] + self.radius * theta.cos(),
+                    self.position[1] + self.radius * theta.sin(),
+                ]
+            })
+            .collect()
+    }
 }
 
 pub struct Square {
@@ -89,37 +104,44 @@ impl Square {
     pub fn new(position: [f32; 2], length: f32) -> Self {
         Self { position, length }
     }
-}
-impl Shape for Square {
-    fn col_vertices(&self, color: [f32; 4]) -> Vec<ColoredVertex> {
+
+    fn corners(&self) -> [[f32; 3]; 4] {
         let half_length = self.length / 2.0;
-        vec![
+        let [x, y] = self.position;
+        [
             // Top-left
-            ColoredVertex::new([-half_length, half_length, 0.0], color),
+            [x - half_length, y + half_length, 0.0],
             // Top-right
-            ColoredVertex::new([half_length, half_length, 0.0], color),
+            [x + half_length, y + half_length, 0.0],
             // Bottom-left
-            ColoredVertex::new([-half_length, -half_length, 0.0], color),
+            [x - half_length, y - half_length, 0.0],
             // Bottom-right
-            ColoredVertex::new([half_length, -half_length, 0.0], color),
+            [x + half_length, y - half_length, 0.0],
         ]
     }
+}
+impl Shape for Square {
+    fn col_vertices(&self, color: [f32; 4]) -> Vec<ColoredVertex> {
+        self.corners()
+            .into_iter()
+            .map(|position| ColoredVertex::new(position, color))
+            .collect()
+    }
     fn tex_vertices(&self, tex_coords: [f32; 2]) -> Vec<TexturedVertex> {
-        let half_length = self.length / 2.0;
-        vec![
-            // Top-left
-            TexturedVertex::new([-half_length, half_length, 0.0], tex_coords),
-            // Top-right
-            TexturedVertex::new([half_length, half_length, 0.0], tex_coords),
-            // Bottom-left
-            TexturedVertex::new([-half_length, -half_length, 0.0], tex_coords),
-            // Bottom-right
-            TexturedVertex::new([half_length, -half_length, 0.0], tex_coords),
-        ]
+        self.corners()
+            .into_iter()
+            .map(|position| TexturedVertex::new(position, tex_coords))
+            .collect()
     }
 
     fn indices(&self) -> Vec<u16> {
         // Two triangles forming a square with counter-clockwise winding
         vec![0, 2, 1, 1, 2, 3]
     }
+
+    fn outline(&self) -> Vec<[f32; 2]> {
+        let [top_left, top_right, bottom_left, bottom_right] =
+            self.corners().map(|[x, y, _]| [x, y]);
+        vec![top_left, bottom_left, bottom_right, top_right]
+    }
 }
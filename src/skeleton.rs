@@ -0,0 +1,989 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use cgmath::{ElementWise, Matrix3, Rad, Vector2, Vector3};
+
+use crate::lerp::Lerp;
+use crate::vertex::TexturedVertex;
+
+#[derive(Debug)]
+pub enum SkeletonError {
+    Json(serde_json::Error),
+    UnknownBone(String),
+    UnknownSlot(String),
+    /// A bone is listed before its parent.
+    BoneOrder(String),
+    /// A mesh attachment whose vertex, UV and triangle lists don't line up.
+    InvalidMesh(String),
+}
+
+impl fmt::Display for SkeletonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "invalid skeleton JSON: {e}"),
+            Self::UnknownBone(name) => write!(f, "no bone named {name:?}"),
+            Self::UnknownSlot(name) => write!(f, "no slot named {name:?}"),
+            Self::BoneOrder(name) => write!(f, "bone {name:?} comes before its parent"),
+            Self::InvalidMesh(name) => write!(f, "mesh attachment {name:?} is malformed"),
+        }
+    }
+}
+
+impl std::error::Error for SkeletonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SkeletonError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+/// Placement of a bone relative to its parent, or of an attachment relative to
+/// its bone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoneTransform {
+    pub translation: Vector2<f32>,
+    /// Counter-clockwise, in radians.
+    pub rotation: f32,
+    pub scale: Vector2<f32>,
+}
+
+impl Default for BoneTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vector2::new(0.0, 0.0),
+            rotation: 0.0,
+            scale: Vector2::new(1.0, 1.0),
+        }
+    }
+}
+
+impl BoneTransform {
+    /// Scales, then rotates, then translates.
+    pub fn matrix(&self) -> Matrix3<f32> {
+        Matrix3::from_translation(self.translation)
+            * Matrix3::from_angle_z(Rad(self.rotation))
+            * Matrix3::from_nonuniform_scale(self.scale.x, self.scale.y)
+    }
+
+    /// Blends towards `other` by `t` from 0 to 1, turning the short way round.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let turn = (other.rotation - self.rotation + std::f32::consts::PI)
+            .rem_euclid(std::f32::consts::TAU)
+            - std::f32::consts::PI;
+        Self {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation + turn * t,
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bone {
+    pub name: String,
+    /// Always before this bone in [`Skeleton::bones`].
+    pub parent: Option<usize>,
+    /// The bone's transform in the setup pose.
+    pub setup: BoneTransform,
+    pub length: f32,
+}
+
+/// Something drawn by a bone. Slots are drawn in order, later ones on top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slot {
+    pub name: String,
+    pub bone: usize,
+    /// Attachment shown in the setup pose.
+    pub attachment: Option<String>,
+}
+
+/// One bone's pull on a skinned vertex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoneWeight {
+    pub bone: usize,
+    /// Where the vertex sits in the bone's space in the setup pose.
+    pub position: Vector2<f32>,
+    pub weight: f32,
+}
+
+/// A sprite held rigidly by its slot's bone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegionAttachment {
+    /// Name of the texture region, for [`Skeleton::set_region`].
+    pub path: String,
+    pub transform: BoneTransform,
+    pub width: f32,
+    pub height: f32,
+    pub uv_rect: [f32; 4],
+}
+
+/// A textured mesh whose vertices are weighted to any number of bones, so it
+/// bends smoothly around joints.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshAttachment {
+    /// Name of the texture region, for [`Skeleton::set_region`].
+    pub path: String,
+    /// Texture coordinates within the region, one per vertex.
+    pub uvs: Vec<[f32; 2]>,
+    pub triangles: Vec<u16>,
+    /// The bones moving each vertex. Weights add up to 1.
+    pub vertices: Vec<Vec<BoneWeight>>,
+    pub uv_rect: [f32; 4],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Attachment {
+    Region(RegionAttachment),
+    Mesh(MeshAttachment),
+}
+
+/// How a track moves from one key to the next.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Holds the value until the next key.
+    Stepped,
+    /// Eases along the cubic Bézier curve from (0, 0) to (1, 1) with the control
+    /// points (x1, y1) and (x2, y2).
+    Bezier([f32; 4]),
+}
+
+impl Interpolation {
+    /// Maps the fraction `t` of the time between two keys onto the fraction of
+    /// the way between their values.
+    pub fn apply(&self, t: f32) -> f32 {
+        match *self {
+            Self::Linear => t,
+            Self::Stepped => 0.0,
+            Self::Bezier([x1, y1, x2, y2]) => {
+                let bezier = |a: f32, b: f32, s: f32| {
+                    let r = 1.0 - s;
+                    3.0 * r * r * s * a + 3.0 * r * s * s * b + s * s * s
+                };
+                // x is monotonic in s for control points within 0..1, so bisect
+                // for the s at which the curve reaches t.
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..16 {
+                    let mid = (low + high) * 0.5;
+                    if bezier(x1, x2, mid) < t {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                bezier(y1, y2, (low + high) * 0.5)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe<T> {
+    /// In seconds.
+    pub time: f32,
+    pub value: T,
+    /// How the value moves from this key to the next.
+    pub interpolation: Interpolation,
+}
+
+/// Keys sorted by time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track<T> {
+    pub keys: Vec<Keyframe<T>>,
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Self { keys: Vec::new() }
+    }
+}
+
+impl<T: Lerp> Track<T> {
+    /// The value at `time`, held at the first and last keys outside them. `None`
+    /// for a track without keys.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let next = self.keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keys.first().map(|key| key.value);
+        }
+        let key = &self.keys[next - 1];
+        let Some(next_key) = self.keys.get(next) else {
+            return Some(key.value);
+        };
+        let t = (time - key.time) / (next_key.time - key.time);
+        Some(key.value.lerp(next_key.value, key.interpolation.apply(t)))
+    }
+}
+
+/// Animation of one bone, as offsets from its setup pose.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BoneTrack {
+    pub bone: usize,
+    /// Added to the setup rotation, in radians.
+    pub rotation: Track<f32>,
+    /// Added to the setup translation.
+    pub translation: Track<Vector2<f32>>,
+    /// Multiplies the setup scale.
+    pub scale: Track<Vector2<f32>>,
+}
+
+/// Swaps what a slot shows, e.g. to blink or change a hand's pose.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AttachmentTrack {
+    pub slot: usize,
+    /// Sorted by time. `None` hides the slot.
+    pub keys: Vec<(f32, Option<String>)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SkeletonClip {
+    pub name: String,
+    /// In seconds.
+    pub duration: f32,
+    pub bone_tracks: Vec<BoneTrack>,
+    pub attachment_tracks: Vec<AttachmentTrack>,
+}
+
+impl SkeletonClip {
+    /// Poses the animated bones as they are `time` seconds into the clip, mixed
+    /// into whatever `pose` holds by `weight` from 0 to 1. Attachments switch
+    /// once the weight reaches one half.
+    pub fn apply(&self, skeleton: &Skeleton, pose: &mut Pose, time: f32, weight: f32) {
+        for track in &self.bone_tracks {
+            let setup = &skeleton.bones[track.bone].setup;
+            let target = BoneTransform {
+                translation: setup.translation
+                    + track
+                        .translation
+                        .sample(time)
+                        .unwrap_or(Vector2::new(0.0, 0.0)),
+                rotation: setup.rotation + track.rotation.sample(time).unwrap_or(0.0),
+                scale: setup
+                    .scale
+                    .mul_element_wise(track.scale.sample(time).unwrap_or(Vector2::new(1.0, 1.0))),
+            };
+            let local = &mut pose.locals[track.bone];
+            *local = local.lerp(&target, weight);
+        }
+        if weight < 0.5 {
+            return;
+        }
+        for track in &self.attachment_tracks {
+            let index = track
+                .keys
+                .partition_point(|(key_time, _)| *key_time <= time);
+            if let Some((_, attachment)) = index.checked_sub(1).map(|i| &track.keys[i]) {
+                pose.attachments[track.slot] = attachment.clone();
+            }
+        }
+    }
+}
+
+/// Bones, slots and attachments in their setup pose, with the clips that move
+/// them. Shared by every [`Pose`] of the character.
+#[derive(Clone, Default)]
+pub struct Skeleton {
+    /// Parents come before their children.
+    pub bones: Vec<Bone>,
+    pub slots: Vec<Slot>,
+    /// Attachments of each slot by name, in the same order as `slots`.
+    pub attachments: Vec<HashMap<String, Attachment>>,
+    pub clips: HashMap<String, Arc<SkeletonClip>>,
+}
+
+impl Skeleton {
+    pub fn bone(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|bone| bone.name == name)
+    }
+
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.name == name)
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Arc<SkeletonClip>> {
+        self.clips.get(name)
+    }
+
+    /// Points every attachment drawn from the texture region `path` at `uv_rect`,
+    /// e.g. a frame of a [`SpriteSheet`](crate::sprite_animation::SpriteSheet).
+    /// Attachments cover the whole texture until then. Returns how many changed.
+    pub fn set_region(&mut self, path: &str, uv_rect: [f32; 4]) -> usize {
+        let mut count = 0;
+        for attachment in self
+            .attachments
+            .iter_mut()
+            .flat_map(|slot| slot.values_mut())
+        {
+            let (attachment_path, attachment_uv_rect) = match attachment {
+                Attachment::Region(region) => (&region.path, &mut region.uv_rect),
+                Attachment::Mesh(mesh) => (&mesh.path, &mut mesh.uv_rect),
+            };
+            if attachment_path == path {
+                *attachment_uv_rect = uv_rect;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Reads a skeleton exported as JSON from Spine 3.8, multiplying every length
+    /// by `scale` to bring it from pixels into world units.
+    ///
+    /// Bones, slots, the default skin's region and mesh attachments and the bone
+    /// and attachment timelines of every animation are read. Bones keep Spine's
+    /// default transform inheritance, and IK, paths, shear and colours are
+    /// skipped.
+    pub fn from_spine_json(json: &str, scale: f32) -> Result<Self, SkeletonError> {
+        spine::read(serde_json::from_str(json)?, scale)
+    }
+}
+
+/// A skeleton posed by animation: the transforms of its bones and what each slot
+/// shows.
+pub struct Pose {
+    pub locals: Vec<BoneTransform>,
+    pub attachments: Vec<Option<String>>,
+    world: Vec<Matrix3<f32>>,
+}
+
+impl Pose {
+    /// The setup pose of `skeleton`.
+    pub fn new(skeleton: &Skeleton) -> Self {
+        let mut pose = Self {
+            locals: Vec::new(),
+            attachments: Vec::new(),
+            world: Vec::new(),
+        };
+        pose.set_to_setup(skeleton);
+        pose.update_world(skeleton);
+        pose
+    }
+
+    pub fn set_to_setup(&mut self, skeleton: &Skeleton) {
+        self.locals = skeleton.bones.iter().map(|bone| bone.setup).collect();
+        self.attachments = skeleton
+            .slots
+            .iter()
+            .map(|slot| slot.attachment.clone())
+            .collect();
+    }
+
+    /// Works out every bone's transform into skeleton space from the local ones.
+    /// Call after posing and before [`Pose::world`] or [`Pose::skin`].
+    pub fn update_world(&mut self, skeleton: &Skeleton) {
+        self.world.clear();
+        for (bone, local) in skeleton.bones.iter().zip(&self.locals) {
+            let world = match bone.parent {
+                Some(parent) => self.world[parent] * local.matrix(),
+                None => local.matrix(),
+            };
+            self.world.push(world);
+        }
+    }
+
+    /// Transform of `bone` from its own space into skeleton space.
+    pub fn world(&self, bone: usize) -> Matrix3<f32> {
+        self.world[bone]
+    }
+
+    /// The triangles of every slot's attachment in draw order, in skeleton space.
+    /// Mesh attachments are skinned by blending their vertices between the world
+    /// transforms of the bones they are weighted to. Attachments past as many
+    /// vertices as 16 bit indices can reach are left out.
+    pub fn skin(&self, skeleton: &Skeleton) -> (Vec<TexturedVertex>, Vec<u16>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let point = |matrix: Matrix3<f32>, position: Vector2<f32>| {
+            (matrix * Vector3::new(position.x, position.y, 1.0)).truncate()
+        };
+        let uv =
+            |[x, y, width, height]: [f32; 4], [u, v]: [f32; 2]| [x + u * width, y + v * height];
+
+        for (slot_index, slot) in skeleton.slots.iter().enumerate() {
+            let Some(attachment) = self.attachments[slot_index]
+                .as_ref()
+                .and_then(|name| skeleton.attachments[slot_index].get(name))
+            else {
+                continue;
+            };
+            let count = match attachment {
+                Attachment::Region(_) => 4,
+                Attachment::Mesh(mesh) => mesh.vertices.len(),
+            };
+            if vertices.len() + count > u16::MAX as usize {
+                break;
+            }
+            let first = vertices.len() as u16;
+            match attachment {
+                Attachment::Region(region) => {
+                    let matrix = self.world[slot.bone] * region.transform.matrix();
+                    let (x, y) = (region.width * 0.5, region.height * 0.5);
+                    let corners = [
+                        ([-x, y], [0.0, 0.0]),
+                        ([x, y], [1.0, 0.0]),
+                        ([-x, -y], [0.0, 1.0]),
+                        ([x, -y], [1.0, 1.0]),
+                    ];
+                    vertices.extend(corners.map(|([x, y], tex_coords)| {
+                        let position = point(matrix, Vector2::new(x, y));
+                        TexturedVertex::new(
+                            [position.x, position.y, 0.0],
+                            uv(region.uv_rect, tex_coords),
+                        )
+                    }));
+                    indices.extend([0, 2, 1, 1, 2, 3].map(|i| first + i));
+                }
+                Attachment::Mesh(mesh) => {
+                    vertices.extend(mesh.vertices.iter().zip(&mesh.uvs).map(
+                        |(weights, &tex_coords)| {
+                            let position =
+                                weights.iter().fold(Vector2::new(0.0, 0.0), |sum, weight| {
+                                    sum + point(self.world[weight.bone], weight.position)
+                                        * weight.weight
+                                });
+                            TexturedVertex::new(
+                                [position.x, position.y, 0.0],
+                                uv(mesh.uv_rect, tex_coords),
+                            )
+                        },
+                    ));
+                    indices.extend(mesh.triangles.iter().map(|&i| first + i));
+                }
+            }
+        }
+        (vertices, indices)
+    }
+}
+
+/// Plays clips on a skeleton, crossfading from one to the next.
+pub struct SkeletonPlayer {
+    pub speed: f32,
+    /// Starts the clip over at its end rather than holding the last pose.
+    pub looping: bool,
+    clip: Arc<SkeletonClip>,
+    time: f32,
+    fade: Option<Fade>,
+}
+
+/// The clip being faded out.
+struct Fade {
+    clip: Arc<SkeletonClip>,
+    time: f32,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl SkeletonPlayer {
+    pub fn new(clip: Arc<SkeletonClip>) -> Self {
+        Self {
+            speed: 1.0,
+            looping: true,
+            clip,
+            time: 0.0,
+            fade: None,
+        }
+    }
+
+    pub fn clip(&self) -> &Arc<SkeletonClip> {
+        &self.clip
+    }
+
+    /// Seconds into the current clip.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Starts `clip` from the beginning, blending in over `fade` seconds from
+    /// wherever the current one is. Does nothing if `clip` is already playing.
+    pub fn play(&mut self, clip: &Arc<SkeletonClip>, fade: f32) {
+        if Arc::ptr_eq(&self.clip, clip) {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.clip, clip.clone());
+        self.fade = (fade > 0.0).then_some(Fade {
+            clip: previous,
+            time: self.time,
+            elapsed: 0.0,
+            duration: fade,
+        });
+        self.time = 0.0;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let dt = dt * self.speed;
+        self.time = self.advance(&self.clip, self.time, dt);
+        if let Some(mut fade) = self.fade.take() {
+            fade.elapsed += dt;
+            if fade.elapsed < fade.duration {
+                fade.time = self.advance(&fade.clip, fade.time, dt);
+                self.fade = Some(fade);
+            }
+        }
+    }
+
+    fn advance(&self, clip: &SkeletonClip, time: f32, dt: f32) -> f32 {
+        let time = time + dt;
+        if self.looping && clip.duration > 0.0 {
+            time.rem_euclid(clip.duration)
+        } else {
+            time.min(clip.duration)
+        }
+    }
+
+    /// Resets `pose` to the setup pose and applies the playing clips to it, then
+    /// updates its world transforms.
+    pub fn apply(&self, skeleton: &Skeleton, pose: &mut Pose) {
+        pose.set_to_setup(skeleton);
+        match &self.fade {
+            Some(fade) => {
+                fade.clip.apply(skeleton, pose, fade.time, 1.0);
+                self.clip
+                    .apply(skeleton, pose, self.time, fade.elapsed / fade.duration);
+            }
+            None => self.clip.apply(skeleton, pose, self.time, 1.0),
+        }
+        pose.update_world(skeleton);
+    }
+}
+
+/// Spine's JSON export and its conversion into a [`Skeleton`].
+mod spine {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use cgmath::Vector2;
+    use serde::Deserialize;
+
+    use super::{
+        Attachment, AttachmentTrack, Bone, BoneTrack, BoneTransform, BoneWeight, Interpolation,
+        Keyframe, MeshAttachment, RegionAttachment, Skeleton, SkeletonClip, SkeletonError, Slot,
+        Track,
+    };
+
+    #[derive(Deserialize)]
+    pub struct Document {
+        #[serde(default)]
+        bones: Vec<BoneData>,
+        #[serde(default)]
+        slots: Vec<SlotData>,
+        #[serde(default)]
+        skins: Skins,
+        #[serde(default)]
+        animations: HashMap<String, AnimationData>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct BoneData {
+        name: String,
+        parent: Option<String>,
+        #[serde(default)]
+        length: f32,
+        #[serde(default)]
+        x: f32,
+        #[serde(default)]
+        y: f32,
+        #[serde(default)]
+        rotation: f32,
+        #[serde(default = "one")]
+        scale_x: f32,
+        #[serde(default = "one")]
+        scale_y: f32,
+    }
+
+    fn one() -> f32 {
+        1.0
+    }
+
+    #[derive(Deserialize)]
+    struct SlotData {
+        name: String,
+        bone: String,
+        attachment: Option<String>,
+    }
+
+    /// Attachments by slot, then by name.
+    type SkinAttachments = HashMap<String, HashMap<String, AttachmentData>>;
+
+    /// Spine 3.8 writes skins as a list, earlier versions as a map by name.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Skins {
+        List(Vec<SkinData>),
+        Map(HashMap<String, SkinAttachments>),
+    }
+
+    impl Default for Skins {
+        fn default() -> Self {
+            Self::List(Vec::new())
+        }
+    }
+
+    impl Skins {
+        fn into_default(self) -> SkinAttachments {
+            match self {
+                Self::List(skins) => skins
+                    .into_iter()
+                    .find(|skin| skin.name == "default")
+                    .map(|skin| skin.attachments)
+                    .unwrap_or_default(),
+                Self::Map(mut skins) => skins.remove("default").unwrap_or_default(),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct SkinData {
+        name: String,
+        #[serde(default)]
+        attachments: SkinAttachments,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct AttachmentData {
+        #[serde(rename = "type", default = "region")]
+        kind: String,
+        path: Option<String>,
+        #[serde(default)]
+        x: f32,
+        #[serde(default)]
+        y: f32,
+        #[serde(default)]
+        rotation: f32,
+        #[serde(default = "one")]
+        scale_x: f32,
+        #[serde(default = "one")]
+        scale_y: f32,
+        #[serde(default)]
+        width: f32,
+        #[serde(default)]
+        height: f32,
+        #[serde(default)]
+        uvs: Vec<f32>,
+        #[serde(default)]
+        triangles: Vec<u16>,
+        #[serde(default)]
+        vertices: Vec<f32>,
+    }
+
+    fn region() -> String {
+        "region".to_string()
+    }
+
+    #[derive(Deserialize, Default)]
+    struct AnimationData {
+        #[serde(default)]
+        bones: HashMap<String, BoneTimelines>,
+        #[serde(default)]
+        slots: HashMap<String, SlotTimelines>,
+    }
+
+    #[derive(Deserialize, Default)]
+    struct BoneTimelines {
+        #[serde(default)]
+        rotate: Vec<RotateKey>,
+        #[serde(default)]
+        translate: Vec<TranslateKey>,
+        #[serde(default)]
+        scale: Vec<ScaleKey>,
+    }
+
+    #[derive(Deserialize, Default)]
+    struct SlotTimelines {
+        #[serde(default)]
+        attachment: Vec<AttachmentKey>,
+    }
+
+    #[derive(Deserialize)]
+    struct RotateKey {
+        #[serde(default)]
+        time: f32,
+        #[serde(default)]
+        angle: f32,
+        #[serde(flatten)]
+        curve: CurveData,
+    }
+
+    #[derive(Deserialize)]
+    struct TranslateKey {
+        #[serde(default)]
+        time: f32,
+        #[serde(default)]
+        x: f32,
+        #[serde(default)]
+        y: f32,
+        #[serde(flatten)]
+        curve: CurveData,
+    }
+
+    #[derive(Deserialize)]
+    struct ScaleKey {
+        #[serde(default)]
+        time: f32,
+        #[serde(default = "one")]
+        x: f32,
+        #[serde(default = "one")]
+        y: f32,
+        #[serde(flatten)]
+        curve: CurveData,
+    }
+
+    #[derive(Deserialize)]
+    struct AttachmentKey {
+        #[serde(default)]
+        time: f32,
+        name: Option<String>,
+    }
+
+    /// Spine 3.8 writes a Bézier curve as `curve` with `c2` to `c4` beside it,
+    /// earlier versions as an array of all four.
+    #[derive(Deserialize)]
+    struct CurveData {
+        curve: Option<CurveValue>,
+        c2: Option<f32>,
+        c3: Option<f32>,
+        c4: Option<f32>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CurveValue {
+        Named(String),
+        Number(f32),
+        Array([f32; 4]),
+    }
+
+    impl CurveData {
+        fn interpolation(&self) -> Interpolation {
+            match &self.curve {
+                Some(CurveValue::Named(name)) if name == "stepped" => Interpolation::Stepped,
+                Some(CurveValue::Named(_)) | None => Interpolation::Linear,
+                Some(CurveValue::Number(c1)) => Interpolation::Bezier([
+                    *c1,
+                    self.c2.unwrap_or(0.0),
+                    self.c3.unwrap_or(1.0),
+                    self.c4.unwrap_or(1.0),
+                ]),
+                Some(CurveValue::Array(curve)) => Interpolation::Bezier(*curve),
+            }
+        }
+    }
+
+    fn track<K, T>(keys: &[K], key: impl Fn(&K) -> (f32, T, &CurveData)) -> Track<T> {
+        let mut keys: Vec<Keyframe<T>> = keys
+            .iter()
+            .map(|data| {
+                let (time, value, curve) = key(data);
+                Keyframe {
+                    time,
+                    value,
+                    interpolation: curve.interpolation(),
+                }
+            })
+            .collect();
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Track { keys }
+    }
+
+    pub fn read(document: Document, scale: f32) -> Result<Skeleton, SkeletonError> {
+        let mut bones: Vec<Bone> = Vec::with_capacity(document.bones.len());
+        for data in document.bones {
+            let parent = match &data.parent {
+                Some(parent) => Some(
+                    bones
+                        .iter()
+                        .position(|bone| &bone.name == parent)
+                        .ok_or_else(|| SkeletonError::BoneOrder(data.name.clone()))?,
+                ),
+                None => None,
+            };
+            bones.push(Bone {
+                name: data.name,
+                parent,
+                setup: BoneTransform {
+                    translation: Vector2::new(data.x, data.y) * scale,
+                    rotation: data.rotation.to_radians(),
+                    scale: Vector2::new(data.scale_x, data.scale_y),
+                },
+                length: data.length * scale,
+            });
+        }
+        let bone_index = |name: &str| {
+            bones
+                .iter()
+                .position(|bone| bone.name == name)
+                .ok_or_else(|| SkeletonError::UnknownBone(name.to_string()))
+        };
+
+        let slots = document
+            .slots
+            .into_iter()
+            .map(|data| {
+                Ok(Slot {
+                    bone: bone_index(&data.bone)?,
+                    name: data.name,
+                    attachment: data.attachment,
+                })
+            })
+            .collect::<Result<Vec<_>, SkeletonError>>()?;
+        let slot_index = |name: &str| {
+            slots
+                .iter()
+                .position(|slot| slot.name == name)
+                .ok_or_else(|| SkeletonError::UnknownSlot(name.to_string()))
+        };
+
+        let mut attachments = vec![HashMap::new(); slots.len()];
+        for (slot_name, slot_attachments) in document.skins.into_default() {
+            let slot = slot_index(&slot_name)?;
+            for (name, data) in slot_attachments {
+                let attachment = attachment(&name, data, slots[slot].bone, bones.len(), scale)?;
+                attachments[slot].insert(name, attachment);
+            }
+        }
+
+        let mut clips = HashMap::new();
+        for (name, animation) in document.animations {
+            let mut clip = SkeletonClip {
+                name: name.clone(),
+                ..Default::default()
+            };
+            for (bone_name, timelines) in animation.bones {
+                clip.bone_tracks.push(BoneTrack {
+                    bone: bone_index(&bone_name)?,
+                    rotation: track(&timelines.rotate, |key| {
+                        (key.time, key.angle.to_radians(), &key.curve)
+                    }),
+                    translation: track(&timelines.translate, |key| {
+                        (key.time, Vector2::new(key.x, key.y) * scale, &key.curve)
+                    }),
+                    scale: track(&timelines.scale, |key| {
+                        (key.time, Vector2::new(key.x, key.y), &key.curve)
+                    }),
+                });
+            }
+            for (slot_name, timelines) in animation.slots {
+                let mut keys: Vec<(f32, Option<String>)> = timelines
+                    .attachment
+                    .into_iter()
+                    .map(|key| (key.time, key.name))
+                    .collect();
+                keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+                clip.attachment_tracks.push(AttachmentTrack {
+                    slot: slot_index(&slot_name)?,
+                    keys,
+                });
+            }
+            // A clip lasts until its last key.
+            let bone_times = clip.bone_tracks.iter().flat_map(|track| {
+                [
+                    track.rotation.keys.last().map(|key| key.time),
+                    track.translation.keys.last().map(|key| key.time),
+                    track.scale.keys.last().map(|key| key.time),
+                ]
+            });
+            let attachment_times = clip
+                .attachment_tracks
+                .iter()
+                .map(|track| track.keys.last().map(|(time, _)| *time));
+            clip.duration = bone_times
+                .chain(attachment_times)
+                .flatten()
+                .fold(0.0, f32::max);
+            clips.insert(name, Arc::new(clip));
+        }
+
+        Ok(Skeleton {
+            bones,
+            slots,
+            attachments,
+            clips,
+        })
+    }
+
+    fn attachment(
+        name: &str,
+        data: AttachmentData,
+        slot_bone: usize,
+        bone_count: usize,
+        scale: f32,
+    ) -> Result<Attachment, SkeletonError> {
+        let path = data.path.unwrap_or_else(|| name.to_string());
+        if data.kind != "mesh" {
+            return Ok(Attachment::Region(RegionAttachment {
+                path,
+                transform: BoneTransform {
+                    translation: Vector2::new(data.x, data.y) * scale,
+                    rotation: data.rotation.to_radians(),
+                    scale: Vector2::new(data.scale_x, data.scale_y),
+                },
+                width: data.width * scale,
+                height: data.height * scale,
+                uv_rect: [0.0, 0.0, 1.0, 1.0],
+            }));
+        }
+
+        let invalid = || SkeletonError::InvalidMesh(name.to_string());
+        let uvs: Vec<[f32; 2]> = data.uvs.chunks_exact(2).map(|uv| [uv[0], uv[1]]).collect();
+        let vertices = if data.vertices.len() == data.uvs.len() {
+            // Unweighted: positions in the space of the slot's bone.
+            data.vertices
+                .chunks_exact(2)
+                .map(|position| {
+                    vec![BoneWeight {
+                        bone: slot_bone,
+                        position: Vector2::new(position[0], position[1]) * scale,
+                        weight: 1.0,
+                    }]
+                })
+                .collect()
+        } else {
+            // Weighted: for each vertex a bone count, then bone, x, y and weight for
+            // each bone, with x and y in that bone's space.
+            let mut values = data.vertices.iter().copied();
+            let mut vertices = Vec::with_capacity(uvs.len());
+            while let Some(count) = values.next() {
+                let weights = (0..count as usize)
+                    .map(|_| {
+                        let mut next = || values.next().ok_or_else(invalid);
+                        let bone = next()? as usize;
+                        let position = Vector2::new(next()?, next()?) * scale;
+                        let weight = next()?;
+                        if bone >= bone_count {
+                            return Err(invalid());
+                        }
+                        Ok(BoneWeight {
+                            bone,
+                            position,
+                            weight,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                vertices.push(weights);
+            }
+            vertices
+        };
+        if vertices.len() != uvs.len()
+            || data.triangles.iter().any(|&i| i as usize >= vertices.len())
+        {
+            return Err(invalid());
+        }
+        Ok(Attachment::Mesh(MeshAttachment {
+            path,
+            uvs,
+            triangles: data.triangles,
+            vertices,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+        }))
+    }
+}
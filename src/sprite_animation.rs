@@ -0,0 +1,573 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::instancing::SpriteInstance;
+
+/// Shortest time a frame is shown for, so a zero duration can't stall
+/// [`SpritePlayer::update`].
+const MIN_FRAME_DURATION: f32 = 0.001;
+
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    Json(serde_json::Error),
+    /// The frame was packed rotated, which a UV rect can't express. Export the
+    /// sheet without "Rotate" ticked.
+    RotatedFrame(String),
+    /// A tag runs past the last frame of the sheet.
+    TagOutOfRange {
+        tag: String,
+        to: usize,
+        frames: usize,
+    },
+}
+
+impl fmt::Display for SpriteSheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "invalid Aseprite JSON: {e}"),
+            Self::RotatedFrame(name) => write!(f, "frame {name:?} is rotated in the sheet"),
+            Self::TagOutOfRange { tag, to, frames } => write!(
+                f,
+                "tag {tag:?} ends at frame {to} but the sheet has {frames} frames"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpriteSheetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SpriteSheetError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+/// One image of an animation: a region of the sheet's texture and how long it
+/// stays up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteFrame {
+    /// Offset and size of the region in texture coordinates, as taken by
+    /// [`SpriteInstance::uv_rect`].
+    pub uv_rect: [f32; 4],
+    /// The same region in pixels: x, y, width and height.
+    pub rect: [u32; 4],
+    /// Where the region sits inside the untrimmed image, for sheets exported with
+    /// transparent borders trimmed.
+    pub offset: [u32; 2],
+    /// Size of the untrimmed image.
+    pub source_size: [u32; 2],
+    /// In seconds.
+    pub duration: f32,
+}
+
+impl SpriteFrame {
+    /// A frame showing the pixels `rect` of a texture `texture_size` pixels big.
+    pub fn new(rect: [u32; 4], texture_size: [u32; 2], duration: f32) -> Self {
+        let [x, y, width, height] = rect;
+        let [texture_width, texture_height] = texture_size.map(|size| size as f32);
+        Self {
+            uv_rect: [
+                x as f32 / texture_width,
+                y as f32 / texture_height,
+                width as f32 / texture_width,
+                height as f32 / texture_height,
+            ],
+            rect,
+            offset: [0, 0],
+            source_size: [width, height],
+            duration,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// Stops on the last frame.
+    Once,
+    /// Starts over from the first frame after the last.
+    #[default]
+    Loop,
+    /// Runs back and forth between the first and last frames.
+    PingPong,
+}
+
+/// A named event fired when playback reaches a frame, e.g. to play a footstep.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub name: String,
+}
+
+/// A sequence of frames played in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    pub frames: Vec<SpriteFrame>,
+    pub mode: PlaybackMode,
+    pub events: Vec<FrameEvent>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, frames: Vec<SpriteFrame>, mode: PlaybackMode) -> Self {
+        Self {
+            name: name.to_string(),
+            frames,
+            mode,
+            events: Vec::new(),
+        }
+    }
+
+    /// Fires `name` every time playback reaches `frame`.
+    pub fn with_event(mut self, frame: usize, name: &str) -> Self {
+        self.events.push(FrameEvent {
+            frame,
+            name: name.to_string(),
+        });
+        self
+    }
+
+    /// Time to play every frame once, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Plays one [`AnimationClip`] at a time, advanced by the game clock.
+///
+/// Clips are shared, so many players can run the same one out of step.
+#[derive(Clone, Debug)]
+pub struct SpritePlayer {
+    /// Multiplies the time passed to [`SpritePlayer::update`].
+    pub speed: f32,
+    pub paused: bool,
+    clip: Arc<AnimationClip>,
+    frame: usize,
+    /// Time spent on the current frame so far.
+    elapsed: f32,
+    /// Playing backwards through a [`PlaybackMode::PingPong`] clip.
+    reversed: bool,
+    finished: bool,
+    /// The current frame's events are still to be fired.
+    entered: bool,
+}
+
+impl SpritePlayer {
+    pub fn new(clip: Arc<AnimationClip>) -> Self {
+        Self {
+            speed: 1.0,
+            paused: false,
+            clip,
+            frame: 0,
+            elapsed: 0.0,
+            reversed: false,
+            finished: false,
+            entered: false,
+        }
+    }
+
+    pub fn clip(&self) -> &Arc<AnimationClip> {
+        &self.clip
+    }
+
+    /// Switches to `clip` from its first frame, unless it is already playing.
+    pub fn play(&mut self, clip: &Arc<AnimationClip>) {
+        if !Arc::ptr_eq(&self.clip, clip) {
+            self.clip = clip.clone();
+            self.restart();
+        }
+    }
+
+    /// Goes back to the first frame of the current clip.
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.reversed = false;
+        self.finished = false;
+        self.entered = false;
+    }
+
+    /// Index of the frame showing in the clip.
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    /// The frame showing, or `None` for a clip without frames.
+    pub fn frame(&self) -> Option<&SpriteFrame> {
+        self.clip.frames.get(self.frame)
+    }
+
+    /// Whether a [`PlaybackMode::Once`] clip has come to rest on its last frame.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Moves playback on by `dt` seconds and returns the events of every frame
+    /// reached, in order. The first frame's events fire on the first update after
+    /// the clip starts.
+    pub fn update(&mut self, dt: f32) -> Vec<FrameEvent> {
+        let mut events = Vec::new();
+        if self.clip.frames.is_empty() {
+            return events;
+        }
+        if !self.entered {
+            self.entered = true;
+            self.fire(&mut events);
+        }
+        if self.paused || self.finished {
+            return events;
+        }
+
+        self.elapsed += dt * self.speed;
+        loop {
+            let duration = self.clip.frames[self.frame]
+                .duration
+                .max(MIN_FRAME_DURATION);
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+            if !self.advance() {
+                self.finished = true;
+                self.elapsed = 0.0;
+                break;
+            }
+            self.fire(&mut events);
+        }
+        events
+    }
+
+    /// Steps to the next frame, returning false at the end of a clip that doesn't
+    /// repeat.
+    fn advance(&mut self) -> bool {
+        let last = self.clip.frames.len() - 1;
+        match self.clip.mode {
+            PlaybackMode::Once if self.frame == last => return false,
+            PlaybackMode::Once => self.frame += 1,
+            PlaybackMode::Loop => {
+                self.frame = if self.frame == last {
+                    0
+                } else {
+                    self.frame + 1
+                }
+            }
+            PlaybackMode::PingPong if last == 0 => {}
+            PlaybackMode::PingPong => {
+                if (self.reversed && self.frame == 0) || (!self.reversed && self.frame == last) {
+                    self.reversed = !self.reversed;
+                }
+                if self.reversed {
+                    self.frame -= 1;
+                } else {
+                    self.frame += 1;
+                }
+            }
+        }
+        true
+    }
+
+    fn fire(&self, events: &mut Vec<FrameEvent>) {
+        events.extend(
+            self.clip
+                .events
+                .iter()
+                .filter(|event| event.frame == self.frame)
+                .cloned(),
+        );
+    }
+
+    /// `instance` showing the current frame.
+    pub fn apply(&self, instance: SpriteInstance) -> SpriteInstance {
+        match self.frame() {
+            Some(frame) => instance.with_uv_rect(frame.uv_rect),
+            None => instance,
+        }
+    }
+}
+
+/// A named region marked on the sprite, such as a hitbox or a pivot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slice {
+    pub name: String,
+    /// Sorted by frame. Each key holds from its frame until the next key.
+    pub keys: Vec<SliceKey>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SliceKey {
+    pub frame: usize,
+    /// x, y, width and height in pixels of the untrimmed image.
+    pub bounds: [u32; 4],
+    /// The centre of a nine-slice, relative to `bounds`.
+    pub center: Option<[u32; 4]>,
+    /// Relative to `bounds`.
+    pub pivot: Option<[i32; 2]>,
+}
+
+impl Slice {
+    /// The key in effect on `frame`, if the slice exists by then.
+    pub fn key(&self, frame: usize) -> Option<&SliceKey> {
+        self.keys.iter().rev().find(|key| key.frame <= frame)
+    }
+}
+
+/// Frames, clips and slices exported from Aseprite with File > Export Sprite Sheet.
+#[derive(Default)]
+pub struct SpriteSheet {
+    /// The sheet's image, relative to the JSON file.
+    pub image: String,
+    pub size: [u32; 2],
+    pub frames: Vec<SpriteFrame>,
+    /// One clip for each tag.
+    pub clips: HashMap<String, Arc<AnimationClip>>,
+    pub slices: Vec<Slice>,
+}
+
+impl SpriteSheet {
+    /// Reads the JSON data of an export, in either the "Hash" or the "Array"
+    /// layout.
+    ///
+    /// Every tag becomes a clip. Forward and reverse tags loop, or play once when
+    /// their repeat count is 1, and ping-pong tags bounce whatever their count.
+    pub fn from_aseprite_json(json: &str) -> Result<Self, SpriteSheetError> {
+        let document: aseprite::Document = serde_json::from_str(json)?;
+        let size = [document.meta.size.w, document.meta.size.h];
+
+        let frames = document
+            .frames
+            .into_vec()
+            .into_iter()
+            .map(|(name, frame)| {
+                if frame.rotated {
+                    return Err(SpriteSheetError::RotatedFrame(name));
+                }
+                let rect = frame.frame;
+                Ok(SpriteFrame {
+                    offset: [frame.sprite_source_size.x, frame.sprite_source_size.y],
+                    source_size: [frame.source_size.w, frame.source_size.h],
+                    ..SpriteFrame::new(
+                        [rect.x, rect.y, rect.w, rect.h],
+                        size,
+                        frame.duration as f32 / 1000.0,
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut clips = HashMap::new();
+        for tag in document.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err(SpriteSheetError::TagOutOfRange {
+                    tag: tag.name,
+                    to: tag.to,
+                    frames: frames.len(),
+                });
+            }
+            let mut clip_frames = frames[tag.from..=tag.to].to_vec();
+            if tag.direction.ends_with("reverse") {
+                clip_frames.reverse();
+            }
+            let mode = if tag.direction.starts_with("pingpong") {
+                PlaybackMode::PingPong
+            } else if tag.repeat.as_deref() == Some("1") {
+                PlaybackMode::Once
+            } else {
+                PlaybackMode::Loop
+            };
+            let clip = AnimationClip::new(&tag.name, clip_frames, mode);
+            clips.insert(tag.name, Arc::new(clip));
+        }
+
+        let slices = document
+            .meta
+            .slices
+            .into_iter()
+            .map(|slice| {
+                let mut keys: Vec<SliceKey> = slice
+                    .keys
+                    .into_iter()
+                    .map(|key| SliceKey {
+                        frame: key.frame,
+                        bounds: key.bounds.into(),
+                        center: key.center.map(Into::into),
+                        pivot: key.pivot.map(|pivot| [pivot.x, pivot.y]),
+                    })
+                    .collect();
+                keys.sort_by_key(|key| key.frame);
+                Slice {
+                    name: slice.name,
+                    keys,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            image: document.meta.image,
+            size,
+            frames,
+            clips,
+            slices,
+        })
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Arc<AnimationClip>> {
+        self.clips.get(name)
+    }
+
+    pub fn slice(&self, name: &str) -> Option<&Slice> {
+        self.slices.iter().find(|slice| slice.name == name)
+    }
+}
+
+/// The parts of Aseprite's JSON export the sheet is built from.
+mod aseprite {
+    use std::fmt;
+
+    use serde::de::{MapAccess, Visitor};
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    pub struct Document {
+        pub frames: Frames,
+        pub meta: Meta,
+    }
+
+    /// The "Hash" layout keys frames by file name, the "Array" layout lists them
+    /// with the name inside.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub enum Frames {
+        Array(Vec<NamedFrame>),
+        Hash(FrameMap),
+    }
+
+    impl Frames {
+        /// The frames in sheet order with their names.
+        pub fn into_vec(self) -> Vec<(String, Frame)> {
+            match self {
+                Self::Array(frames) => frames
+                    .into_iter()
+                    .map(|frame| (frame.filename, frame.frame))
+                    .collect(),
+                Self::Hash(FrameMap(frames)) => frames,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct NamedFrame {
+        pub filename: String,
+        #[serde(flatten)]
+        pub frame: Frame,
+    }
+
+    /// The frames of the "Hash" layout. Tags refer to frames by index, so they are
+    /// kept in the order they appear rather than collected into a map.
+    pub struct FrameMap(pub Vec<(String, Frame)>);
+
+    impl<'de> Deserialize<'de> for FrameMap {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct FrameMapVisitor;
+
+            impl<'de> Visitor<'de> for FrameMapVisitor {
+                type Value = FrameMap;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a map of frame names to frames")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<FrameMap, A::Error> {
+                    let mut frames = Vec::new();
+                    while let Some(entry) = map.next_entry()? {
+                        frames.push(entry);
+                    }
+                    Ok(FrameMap(frames))
+                }
+            }
+
+            deserializer.deserialize_map(FrameMapVisitor)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Frame {
+        pub frame: Rect,
+        #[serde(default)]
+        pub rotated: bool,
+        pub sprite_source_size: Rect,
+        pub source_size: Size,
+        /// In milliseconds.
+        pub duration: u32,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Meta {
+        #[serde(default)]
+        pub image: String,
+        pub size: Size,
+        #[serde(default)]
+        pub frame_tags: Vec<Tag>,
+        #[serde(default)]
+        pub slices: Vec<Slice>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Tag {
+        pub name: String,
+        pub from: usize,
+        pub to: usize,
+        #[serde(default)]
+        pub direction: String,
+        /// Written as a string, and left out for tags that repeat forever.
+        pub repeat: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Slice {
+        pub name: String,
+        pub keys: Vec<SliceKey>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SliceKey {
+        pub frame: usize,
+        pub bounds: Rect,
+        pub center: Option<Rect>,
+        pub pivot: Option<Point>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Rect {
+        pub x: u32,
+        pub y: u32,
+        pub w: u32,
+        pub h: u32,
+    }
+
+    impl From<Rect> for [u32; 4] {
+        fn from(rect: Rect) -> Self {
+            [rect.x, rect.y, rect.w, rect.h]
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Size {
+        pub w: u32,
+        pub h: u32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+}
@@ -0,0 +1,765 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ab_glyph::{Font as _, ScaleFont as _};
+use wgpu::util::DeviceExt;
+
+use crate::camera::CameraUniform;
+use crate::constants::OPENGL_TO_WGPU_MATRIX;
+use crate::mesh::Mesh;
+use crate::texture::{ImageTexture, TextureOptions};
+use crate::vertex::Positioned;
+
+/// Width and height of every font's glyph atlas.
+const ATLAS_SIZE: u32 = 1024;
+/// Empty pixels around each glyph in the atlas so filtering doesn't pick up its
+/// neighbours.
+const GLYPH_PADDING: u32 = 1;
+
+#[derive(Debug)]
+pub enum TextError {
+    Font(ab_glyph::InvalidFont),
+    Image(image::ImageError),
+    /// A BMFont descriptor line couldn't be understood. Lines count from 1.
+    BmFont {
+        line: usize,
+        message: String,
+    },
+    /// A BMFont descriptor refers to a page that wasn't passed in.
+    MissingPage(u32),
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::Font(e) => e.fmt(f),
+            TextError::Image(e) => e.fmt(f),
+            TextError::BmFont { line, message } => write!(f, "BMFont line {line}: {message}"),
+            TextError::MissingPage(page) => write!(f, "BMFont page {page} was not provided"),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+impl From<ab_glyph::InvalidFont> for TextError {
+    fn from(e: ab_glyph::InvalidFont) -> Self {
+        TextError::Font(e)
+    }
+}
+
+impl From<image::ImageError> for TextError {
+    fn from(e: image::ImageError) -> Self {
+        TextError::Image(e)
+    }
+}
+
+crate::vertex! {
+    /// A corner of one glyph quad. Drawn with [`Material::text`](crate::material::Material::text).
+    pub struct TextVertex {
+        position: [f32; 3],
+        tex_coords: [f32; 2],
+        color: [f32; 4],
+    }
+}
+
+impl TextVertex {
+    pub fn new(position: [f32; 3], tex_coords: [f32; 2], color: [f32; 4]) -> Self {
+        Self {
+            position,
+            tex_coords,
+            color,
+        }
+    }
+}
+
+impl Positioned for TextVertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn with_position(self, position: [f32; 3]) -> Self {
+        Self { position, ..self }
+    }
+}
+
+/// A run of text drawn in one colour.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextSpan<'a> {
+    pub text: &'a str,
+    pub color: [f32; 4],
+}
+
+impl<'a> TextSpan<'a> {
+    pub fn new(text: &'a str, color: [f32; 4]) -> Self {
+        Self { text, color }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Where and how a block of text is laid out.
+///
+/// Layout happens in font pixels with lines running down from the top of the block,
+/// then `scale` turns pixels into the units of the space the text is drawn in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextOptions {
+    /// The top left of the block. With `Center` or `Right` alignment lines are
+    /// aligned within `max_width`, or within the widest line if there is no limit.
+    pub position: [f32; 2],
+    pub depth: f32,
+    /// World units per font pixel. 1 in [`ScreenSpace`], where units are pixels.
+    pub scale: f32,
+    /// Lines longer than this many font pixels wrap at the last space, or mid-word
+    /// if a single word doesn't fit.
+    pub max_width: Option<f32>,
+    pub align: Align,
+    /// Multiplies the font's line height.
+    pub line_spacing: f32,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            depth: 0.0,
+            scale: 1.0,
+            max_width: None,
+            align: Align::Left,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+/// Vertices and indices for a block of text, plus its size in font pixels.
+#[derive(Clone, Debug, Default)]
+pub struct TextLayout {
+    pub vertices: Vec<TextVertex>,
+    pub indices: Vec<u16>,
+    pub size: [f32; 2],
+}
+
+impl TextLayout {
+    pub fn mesh(&self, device: &wgpu::Device, label: &str) -> Mesh {
+        Mesh::new(device, &self.vertices, &self.indices, label)
+    }
+}
+
+/// Where a rasterized glyph sits in the atlas and relative to the pen.
+#[derive(Clone, Copy, Debug)]
+struct GlyphQuad {
+    /// Top left of the bitmap from the pen position on the baseline, y down.
+    offset: [f32; 2],
+    size: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Glyph {
+    advance: f32,
+    /// `None` for glyphs with nothing to draw, such as spaces, or that didn't fit
+    /// in the atlas.
+    quad: Option<GlyphQuad>,
+}
+
+/// Packs glyph bitmaps into rows of a single image.
+struct GlyphAtlas {
+    image: image::RgbaImage,
+    cursor: [u32; 2],
+    row_height: u32,
+    /// Set when the image has changed since it was last uploaded.
+    dirty: bool,
+    full: bool,
+}
+
+impl GlyphAtlas {
+    fn new() -> Self {
+        Self {
+            image: image::RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE),
+            cursor: [GLYPH_PADDING; 2],
+            row_height: 0,
+            dirty: false,
+            full: false,
+        }
+    }
+
+    /// Copies `width` by `height` pixels produced by `pixel` into free space,
+    /// returning their uv rectangle.
+    fn insert(
+        &mut self,
+        width: u32,
+        height: u32,
+        mut pixel: impl FnMut(u32, u32) -> image::Rgba<u8>,
+    ) -> Option<([f32; 2], [f32; 2])> {
+        if self.cursor[0] + width + GLYPH_PADDING > ATLAS_SIZE {
+            self.cursor = [
+                GLYPH_PADDING,
+                self.cursor[1] + self.row_height + GLYPH_PADDING,
+            ];
+            self.row_height = 0;
+        }
+        if self.cursor[0] + width + GLYPH_PADDING > ATLAS_SIZE
+            || self.cursor[1] + height + GLYPH_PADDING > ATLAS_SIZE
+        {
+            if !self.full {
+                log::warn!("glyph atlas is full, new glyphs won't be drawn");
+                self.full = true;
+            }
+            return None;
+        }
+        let [x, y] = self.cursor;
+        for dy in 0..height {
+            for dx in 0..width {
+                self.image.put_pixel(x + dx, y + dy, pixel(dx, dy));
+            }
+        }
+        self.cursor[0] += width + GLYPH_PADDING;
+        self.row_height = self.row_height.max(height);
+        self.dirty = true;
+        let size = ATLAS_SIZE as f32;
+        Some((
+            [x as f32 / size, y as f32 / size],
+            [(x + width) as f32 / size, (y + height) as f32 / size],
+        ))
+    }
+}
+
+/// A character of a BMFont, in the units of its descriptor.
+#[derive(Clone, Copy, Debug)]
+struct BitmapChar {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    offset: [f32; 2],
+    advance: f32,
+    page: u32,
+}
+
+enum FontSource {
+    Outline {
+        font: ab_glyph::FontArc,
+        scale: ab_glyph::PxScale,
+    },
+    Bitmap {
+        chars: HashMap<char, BitmapChar>,
+        kerning: HashMap<(char, char), f32>,
+        pages: Vec<image::RgbaImage>,
+        /// Distance from the top of a line to the baseline.
+        base: f32,
+    },
+}
+
+/// A TrueType/OpenType font rasterized at one size, or a BMFont bitmap font, along
+/// with the atlas its glyphs are cached in.
+///
+/// Glyphs are added to the atlas the first time they are laid out and reused after
+/// that. [`Font::layout`] only updates the CPU copy, so call [`Font::upload`]
+/// before drawing, or use [`Font::mesh`] which does both.
+pub struct Font {
+    source: FontSource,
+    line_height: f32,
+    glyphs: HashMap<char, Glyph>,
+    atlas: GlyphAtlas,
+    texture: ImageTexture,
+}
+
+impl Font {
+    /// Loads a TrueType or OpenType font rasterized so that `size` pixels span its
+    /// ascent to descent.
+    pub fn from_ttf(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: Vec<u8>,
+        size: f32,
+    ) -> Result<Self, TextError> {
+        let font = ab_glyph::FontArc::try_from_vec(bytes)?;
+        let scale = ab_glyph::PxScale::from(size);
+        let scaled = font.as_scaled(scale);
+        let line_height = scaled.height() + scaled.line_gap();
+        let options = TextureOptions {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        };
+        Ok(Self::new(
+            device,
+            queue,
+            FontSource::Outline { font, scale },
+            line_height,
+            &options,
+        ))
+    }
+
+    /// Loads a BMFont from its text descriptor (the `.fnt` file) and page images,
+    /// given in page id order. Pixel fonts are sampled with nearest filtering.
+    pub fn from_bmfont(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        descriptor: &str,
+        pages: &[&[u8]],
+    ) -> Result<Self, TextError> {
+        let mut chars = HashMap::new();
+        let mut kerning = HashMap::new();
+        let mut line_height = None;
+        let mut base = None;
+        for (index, line) in descriptor.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |message: String| TextError::BmFont {
+                line: line_number,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (tag, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let attributes = parse_attributes(rest).map_err(error)?;
+            let number = |key: &str| -> Result<f32, TextError> {
+                let value = attributes
+                    .get(key)
+                    .ok_or_else(|| error(format!("`{tag}` is missing `{key}`")))?;
+                value
+                    .parse()
+                    .map_err(|_| error(format!("`{key}` should be a number, found `{value}`")))
+            };
+            let character = |key: &str| -> Result<char, TextError> {
+                let id = number(key)? as u32;
+                char::from_u32(id)
+                    .ok_or_else(|| error(format!("`{key}` {id} is not a valid character")))
+            };
+            match tag {
+                "common" => {
+                    line_height = Some(number("lineHeight")?);
+                    base = Some(number("base")?);
+                }
+                "char" => {
+                    let page = number("page").unwrap_or(0.0) as u32;
+                    if page as usize >= pages.len() {
+                        return Err(TextError::MissingPage(page));
+                    }
+                    chars.insert(
+                        character("id")?,
+                        BitmapChar {
+                            x: number("x")? as u32,
+                            y: number("y")? as u32,
+                            width: number("width")? as u32,
+                            height: number("height")? as u32,
+                            offset: [number("xoffset")?, number("yoffset")?],
+                            advance: number("xadvance")?,
+                            page,
+                        },
+                    );
+                }
+                "kerning" => {
+                    kerning.insert(
+                        (character("first")?, character("second")?),
+                        number("amount")?,
+                    );
+                }
+                _ => {}
+            }
+        }
+        let (Some(line_height), Some(base)) = (line_height, base) else {
+            return Err(TextError::BmFont {
+                line: descriptor.lines().count(),
+                message: "no `common` line with `lineHeight` and `base`".to_string(),
+            });
+        };
+        let pages = pages
+            .iter()
+            .map(|bytes| Ok(image::load_from_memory(bytes)?.to_rgba8()))
+            .collect::<Result<Vec<_>, TextError>>()?;
+        Ok(Self::new(
+            device,
+            queue,
+            FontSource::Bitmap {
+                chars,
+                kerning,
+                pages,
+                base,
+            },
+            line_height,
+            &TextureOptions::default(),
+        ))
+    }
+
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        source: FontSource,
+        line_height: f32,
+        options: &TextureOptions,
+    ) -> Self {
+        let atlas = GlyphAtlas::new();
+        let texture = ImageTexture::from_rgba(
+            device,
+            queue,
+            atlas.image.clone(),
+            Some("Glyph Atlas"),
+            options,
+            None,
+        );
+        Self {
+            source,
+            line_height,
+            glyphs: HashMap::new(),
+            atlas,
+            texture,
+        }
+    }
+
+    /// The glyph atlas, for [`Material::text`](crate::material::Material::text).
+    pub fn texture(&self) -> &ImageTexture {
+        &self.texture
+    }
+
+    /// Distance between the baselines of consecutive lines, in font pixels.
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    /// Writes glyphs added to the atlas since the last upload to its texture.
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        if !self.atlas.dirty {
+            return;
+        }
+        queue.write_texture(
+            self.texture.texture.as_image_copy(),
+            &self.atlas.image,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * ATLAS_SIZE),
+                rows_per_image: Some(ATLAS_SIZE),
+            },
+            self.texture.texture.size(),
+        );
+        self.atlas.dirty = false;
+    }
+
+    /// Lays out `spans` as one block of text and uploads any new glyphs, ready to
+    /// draw with a [`Material::text`](crate::material::Material::text).
+    pub fn mesh(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        spans: &[TextSpan],
+        options: &TextOptions,
+    ) -> Mesh {
+        let layout = self.layout(spans, options);
+        self.upload(queue);
+        layout.mesh(device, "Text Mesh")
+    }
+
+    /// Breaks `spans` into lines, applying kerning, wrapping and alignment, and builds
+    /// a quad for every visible glyph. Newlines in the text always start a new line.
+    /// Glyphs past as many as 16 bit indices can reach are left out.
+    pub fn layout(&mut self, spans: &[TextSpan], options: &TextOptions) -> TextLayout {
+        struct Placed {
+            glyph: Glyph,
+            x: f32,
+            color: [f32; 4],
+            whitespace: bool,
+        }
+
+        let mut lines: Vec<Vec<Placed>> = vec![Vec::new()];
+        let mut pen = 0.0;
+        let mut previous = None;
+        // Index in the current line of the first glyph after the last space.
+        let mut word_start = None;
+        for span in spans {
+            for c in span.text.chars() {
+                if c == '\n' {
+                    lines.push(Vec::new());
+                    pen = 0.0;
+                    previous = None;
+                    word_start = None;
+                    continue;
+                }
+                if c.is_control() {
+                    continue;
+                }
+                let glyph = self.glyph(c);
+                let mut x = pen + previous.map_or(0.0, |previous| self.kern(previous, c));
+                let whitespace = c.is_whitespace();
+                let line = lines.last_mut().unwrap();
+                let overflows = options
+                    .max_width
+                    .is_some_and(|max_width| x + glyph.advance > max_width);
+                if overflows && !whitespace && !line.is_empty() {
+                    // Carry the word so far over to the next line. A word that fills a
+                    // line by itself is broken here instead.
+                    let split = word_start.filter(|&start| start > 0).unwrap_or(line.len());
+                    let mut moved: Vec<Placed> = line.drain(split..).collect();
+                    let shift = moved.first().map_or(x, |placed| placed.x);
+                    for placed in &mut moved {
+                        placed.x -= shift;
+                    }
+                    x -= shift;
+                    lines.push(moved);
+                    word_start = Some(0);
+                }
+                let line = lines.last_mut().unwrap();
+                line.push(Placed {
+                    glyph,
+                    x,
+                    color: span.color,
+                    whitespace,
+                });
+                if whitespace {
+                    word_start = Some(line.len());
+                }
+                pen = x + glyph.advance;
+                previous = Some(c);
+            }
+        }
+
+        // Trailing spaces don't count towards a line's width.
+        let widths: Vec<f32> = lines
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .filter(|placed| !placed.whitespace)
+                    .map(|placed| placed.x + placed.glyph.advance)
+                    .fold(0.0, f32::max)
+            })
+            .collect();
+        let block_width = options
+            .max_width
+            .unwrap_or_else(|| widths.iter().copied().fold(0.0, f32::max));
+        let ascent = self.ascent();
+        let line_advance = self.line_height * options.line_spacing;
+
+        let mut layout = TextLayout {
+            size: [
+                widths.iter().copied().fold(0.0, f32::max),
+                line_advance * (lines.len() - 1) as f32 + self.line_height,
+            ],
+            ..Default::default()
+        };
+        let [origin_x, origin_y] = options.position;
+        'lines: for (index, (line, width)) in lines.iter().zip(&widths).enumerate() {
+            let start = match options.align {
+                Align::Left => 0.0,
+                Align::Center => ((block_width - width) / 2.0).round(),
+                Align::Right => (block_width - width).round(),
+            };
+            let baseline = ascent + line_advance * index as f32;
+            for placed in line {
+                let Some(quad) = placed.glyph.quad else {
+                    continue;
+                };
+                // Snapped to whole font pixels so glyphs stay sharp at a scale of 1.
+                let left = (start + placed.x + quad.offset[0]).round();
+                let top = (baseline + quad.offset[1]).round();
+                let corners = [
+                    ([left, top], quad.uv_min),
+                    ([left, top + quad.size[1]], [quad.uv_min[0], quad.uv_max[1]]),
+                    ([left + quad.size[0], top + quad.size[1]], quad.uv_max),
+                    ([left + quad.size[0], top], [quad.uv_max[0], quad.uv_min[1]]),
+                ];
+                let first = layout.vertices.len();
+                if first + corners.len() > u16::MAX as usize {
+                    break 'lines;
+                }
+                layout.vertices.extend(corners.map(|([x, y], tex_coords)| {
+                    // Lines run down the screen but world space y points up.
+                    let position = [
+                        origin_x + x * options.scale,
+                        origin_y - y * options.scale,
+                        options.depth,
+                    ];
+                    TextVertex::new(position, tex_coords, placed.color)
+                }));
+                // Counter clockwise once y is flipped.
+                layout
+                    .indices
+                    .extend([0, 1, 2, 0, 2, 3].map(|i| first as u16 + i));
+            }
+        }
+        layout
+    }
+
+    /// Distance from the top of a line to its baseline.
+    fn ascent(&self) -> f32 {
+        match &self.source {
+            FontSource::Outline { font, scale } => font.as_scaled(*scale).ascent(),
+            FontSource::Bitmap { base, .. } => *base,
+        }
+    }
+
+    fn kern(&self, first: char, second: char) -> f32 {
+        match &self.source {
+            FontSource::Outline { font, scale } => {
+                let font = font.as_scaled(*scale);
+                font.kern(font.glyph_id(first), font.glyph_id(second))
+            }
+            FontSource::Bitmap { kerning, .. } => {
+                kerning.get(&(first, second)).copied().unwrap_or(0.0)
+            }
+        }
+    }
+
+    /// Looks `c` up in the cache, rasterizing it into the atlas the first time.
+    fn glyph(&mut self, c: char) -> Glyph {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return *glyph;
+        }
+        let atlas = &mut self.atlas;
+        let glyph = match &self.source {
+            FontSource::Outline { font, scale } => {
+                let scaled = font.as_scaled(*scale);
+                let id = scaled.glyph_id(c);
+                let quad = font
+                    .outline_glyph(id.with_scale(*scale))
+                    .and_then(|outline| {
+                        let bounds = outline.px_bounds();
+                        let width = bounds.width() as u32;
+                        let height = bounds.height() as u32;
+                        let mut coverage = vec![0.0; (width * height) as usize];
+                        outline.draw(|x, y, c| {
+                            if x < width && y < height {
+                                coverage[(y * width + x) as usize] = c;
+                            }
+                        });
+                        let (uv_min, uv_max) = atlas.insert(width, height, |x, y| {
+                            let alpha = coverage[(y * width + x) as usize].clamp(0.0, 1.0);
+                            image::Rgba([255, 255, 255, (alpha * 255.0).round() as u8])
+                        })?;
+                        Some(GlyphQuad {
+                            offset: [bounds.min.x, bounds.min.y],
+                            size: [width as f32, height as f32],
+                            uv_min,
+                            uv_max,
+                        })
+                    });
+                Glyph {
+                    advance: scaled.h_advance(id),
+                    quad,
+                }
+            }
+            FontSource::Bitmap {
+                chars, pages, base, ..
+            } => {
+                // Characters the font doesn't have fall back to `?`, if it has that.
+                match chars.get(&c).or_else(|| chars.get(&'?')) {
+                    None => Glyph {
+                        advance: 0.0,
+                        quad: None,
+                    },
+                    Some(char) => {
+                        let page = &pages[char.page as usize];
+                        let (page_width, page_height) = page.dimensions();
+                        let quad = (char.width > 0 && char.height > 0)
+                            .then(|| {
+                                atlas.insert(char.width, char.height, |x, y| {
+                                    let (x, y) = (char.x + x, char.y + y);
+                                    if x < page_width && y < page_height {
+                                        *page.get_pixel(x, y)
+                                    } else {
+                                        image::Rgba([0; 4])
+                                    }
+                                })
+                            })
+                            .flatten()
+                            .map(|(uv_min, uv_max)| GlyphQuad {
+                                offset: [char.offset[0], char.offset[1] - base],
+                                size: [char.width as f32, char.height as f32],
+                                uv_min,
+                                uv_max,
+                            });
+                        Glyph {
+                            advance: char.advance,
+                            quad,
+                        }
+                    }
+                }
+            }
+        };
+        self.glyphs.insert(c, glyph);
+        glyph
+    }
+}
+
+/// Splits the `key=value` pairs of a BMFont line, where values may be quoted.
+fn parse_attributes(text: &str) -> Result<HashMap<&str, &str>, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let equals = rest
+            .find('=')
+            .ok_or_else(|| format!("expected `key=value`, found `{rest}`"))?;
+        let key = rest[..equals].trim();
+        if key.contains(char::is_whitespace) {
+            return Err(format!("expected `key=value`, found `{key}`"));
+        }
+        rest = &rest[equals + 1..];
+        let value;
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| format!("unterminated quote in `{key}`"))?;
+            value = &quoted[..end];
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            value = &rest[..end];
+            rest = &rest[end..];
+        }
+        attributes.insert(key, value);
+        rest = rest.trim_start();
+    }
+    Ok(attributes)
+}
+
+/// A camera for text and other overlays positioned in pixels, with the origin in
+/// the bottom left corner and y pointing up like world space. Bind it at group 0
+/// in place of the scene camera.
+pub struct ScreenSpace {
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ScreenSpace {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Screen Space Buffer"),
+            contents: bytemuck::bytes_of(&Self::uniform(width, height)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("Screen Space Bind Group"),
+        });
+        Self { buffer, bind_group }
+    }
+
+    fn uniform(width: u32, height: u32) -> CameraUniform {
+        let projection = cgmath::ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0);
+        CameraUniform::from_view_proj(OPENGL_TO_WGPU_MATRIX * projection)
+    }
+
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::bytes_of(&Self::uniform(width, height)),
+        );
+    }
+}
@@ -1,5 +1,143 @@
-use image::GenericImageView;
-use wgpu;
+use std::fmt;
+
+use crate::mipmap::MipmapGenerator;
+
+#[derive(Debug)]
+pub enum TextureError {
+    Image(image::ImageError),
+    /// A texture array was created without any layers.
+    NoLayers,
+    /// A texture array layer differs in size from the first layer.
+    LayerSizeMismatch {
+        index: usize,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    /// Pixels written into an existing texture differ in size from it.
+    SizeMismatch {
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    /// Pixels were written into a texture with mipmaps without a
+    /// [`MipmapGenerator`] to regenerate them.
+    NoMipmapGenerator,
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::Image(e) => e.fmt(f),
+            TextureError::NoLayers => write!(f, "a texture array needs at least one layer"),
+            TextureError::LayerSizeMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "texture array layer {index} is {}x{} but layer 0 is {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+            TextureError::SizeMismatch { expected, found } => write!(
+                f,
+                "can't write {}x{} pixels into a {}x{} texture",
+                found.0, found.1, expected.0, expected.1
+            ),
+            TextureError::NoMipmapGenerator => {
+                write!(f, "regenerating mipmaps needs a MipmapGenerator")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<image::ImageError> for TextureError {
+    fn from(e: image::ImageError) -> Self {
+        TextureError::Image(e)
+    }
+}
+
+/// How an [`ImageTexture`] is stored and sampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureOptions {
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    /// Maximum anisotropy, 1 to 16. Only takes effect when all three filters are
+    /// `Linear`, otherwise it is ignored.
+    pub anisotropy: u16,
+    /// Store the texture as sRGB so it is decoded to linear when sampled. Turn this
+    /// off for data textures such as normal maps and lookup tables.
+    pub srgb: bool,
+    /// Generate the full mip chain on the GPU after upload. Needs a
+    /// [`MipmapGenerator`] passed to the constructor.
+    pub generate_mipmaps: bool,
+    /// Multiply colour by alpha before upload, for `BlendMode::PremultipliedAlpha`.
+    pub premultiply_alpha: bool,
+}
+
+impl Default for TextureOptions {
+    /// Crisp pixel art: nearest filtering, clamped edges, sRGB and no mips.
+    fn default() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            anisotropy: 1,
+            srgb: true,
+            generate_mipmaps: false,
+            premultiply_alpha: false,
+        }
+    }
+}
+
+impl TextureOptions {
+    /// Smooth trilinear filtering with mipmaps, for large images that get zoomed out.
+    pub fn smooth() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            generate_mipmaps: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        if self.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        }
+    }
+
+    pub fn create_sampler(&self, device: &wgpu::Device, label: Option<&str>) -> wgpu::Sampler {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|&f| f == wgpu::FilterMode::Linear);
+        // wgpu rejects anisotropic samplers unless every filter is linear.
+        let anisotropy_clamp = if all_linear {
+            self.anisotropy.clamp(1, 16)
+        } else {
+            1
+        };
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
 
 pub struct ImageTexture {
     pub texture: wgpu::Texture,
@@ -13,9 +151,49 @@ impl ImageTexture {
         queue: &wgpu::Queue,
         bytes: &[u8],
         label: &str,
+    ) -> Result<Self, image::ImageError> {
+        Self::from_bytes_with_options(
+            device,
+            queue,
+            bytes,
+            label,
+            &TextureOptions::default(),
+            None,
+        )
+    }
+
+    /// Like [`ImageTexture::from_bytes`] but multiplies colour by alpha before upload,
+    /// for use with `BlendMode::PremultipliedAlpha`.
+    pub fn from_bytes_premultiplied(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self, image::ImageError> {
+        let options = TextureOptions {
+            premultiply_alpha: true,
+            ..Default::default()
+        };
+        Self::from_bytes_with_options(device, queue, bytes, label, &options, None)
+    }
+
+    pub fn from_bytes_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+        options: &TextureOptions,
+        mipmaps: Option<&MipmapGenerator>,
     ) -> Result<Self, image::ImageError> {
         let loaded_image = image::load_from_memory(bytes)?;
-        Ok(Self::from_image(device, queue, &loaded_image, Some(label)))
+        Ok(Self::from_rgba(
+            device,
+            queue,
+            loaded_image.to_rgba8(),
+            Some(label),
+            options,
+            mipmaps,
+        ))
     }
 
     pub fn from_image(
@@ -24,50 +202,144 @@ impl ImageTexture {
         img: &image::DynamicImage,
         label: Option<&str>,
     ) -> Self {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        Self::from_rgba(
+            device,
+            queue,
+            img.to_rgba8(),
+            label,
+            &TextureOptions::default(),
+            None,
+        )
+    }
+
+    /// `mipmaps` renders the mip chain when `options.generate_mipmaps` is set. Without
+    /// it the texture gets no mipmaps and a warning is logged.
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: image::RgbaImage,
+        label: Option<&str>,
+        options: &TextureOptions,
+        mipmaps: Option<&MipmapGenerator>,
+    ) -> Self {
+        Self::from_layers(
+            device,
+            queue,
+            vec![rgba],
+            label,
+            options,
+            mipmaps,
+            wgpu::TextureViewDimension::D2,
+        )
+    }
+
+    /// Decodes each of `images` into one layer of a 2D texture array, in order.
+    /// Every image must have the same dimensions.
+    pub fn array_from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[&[u8]],
+        label: &str,
+        options: &TextureOptions,
+        mipmaps: Option<&MipmapGenerator>,
+    ) -> Result<Self, TextureError> {
+        let layers = images
+            .iter()
+            .map(|bytes| Ok(image::load_from_memory(bytes)?.to_rgba8()))
+            .collect::<Result<Vec<_>, TextureError>>()?;
+        Self::array_from_rgba(device, queue, layers, Some(label), options, mipmaps)
+    }
+
+    pub fn array_from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layers: Vec<image::RgbaImage>,
+        label: Option<&str>,
+        options: &TextureOptions,
+        mipmaps: Option<&MipmapGenerator>,
+    ) -> Result<Self, TextureError> {
+        let expected = layers.first().ok_or(TextureError::NoLayers)?.dimensions();
+        if let Some((index, layer)) = layers
+            .iter()
+            .enumerate()
+            .find(|(_, layer)| layer.dimensions() != expected)
+        {
+            return Err(TextureError::LayerSizeMismatch {
+                index,
+                expected,
+                found: layer.dimensions(),
+            });
+        }
+        Ok(Self::from_layers(
+            device,
+            queue,
+            layers,
+            label,
+            options,
+            mipmaps,
+            wgpu::TextureViewDimension::D2Array,
+        ))
+    }
+
+    fn from_layers(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut layers: Vec<image::RgbaImage>,
+        label: Option<&str>,
+        options: &TextureOptions,
+        mipmaps: Option<&MipmapGenerator>,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> Self {
+        if options.premultiply_alpha {
+            for rgba in &mut layers {
+                premultiply_alpha(rgba, options.srgb);
+            }
+        }
+        let dimensions = layers[0].dimensions();
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
-            depth_or_array_layers: 1,
+            depth_or_array_layers: layers.len() as u32,
         };
+        if options.generate_mipmaps && mipmaps.is_none() {
+            log::warn!(
+                "no MipmapGenerator to generate mipmaps for texture '{}', so it has none",
+                label.unwrap_or("unnamed")
+            );
+        }
+        let mip_level_count = match mipmaps {
+            Some(_) if options.generate_mipmaps => size.max_mips(wgpu::TextureDimension::D2),
+            _ => 1,
+        };
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if mip_level_count > 1 {
+            // Each mip level is rendered from the one above it.
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
-            size: size,
-            mip_level_count: 1,
+            size,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: label,
+            format: options.format(),
+            usage,
+            label,
             view_formats: &[],
         });
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+        for (layer, rgba) in layers.iter().enumerate() {
+            write_layer(queue, &texture, layer as u32, rgba);
+        }
+        if let Some(mipmaps) = mipmaps.filter(|_| mip_level_count > 1) {
+            mipmaps.generate(device, queue, &texture);
+        }
+        // Spelled out so a single-layer array still gets an array view.
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(view_dimension),
             ..Default::default()
         });
+        let sampler = options.create_sampler(device, label);
         Self {
             texture,
             view,
@@ -75,6 +347,110 @@ impl ImageTexture {
         }
     }
 
+    /// Replaces the pixels of a single-layer texture in place and regenerates its
+    /// mipmaps, so bind groups made from it show the new image. `rgba` is written
+    /// as is, so premultiply it first if the texture was loaded premultiplied.
+    /// `mipmaps` is needed if the texture has mipmaps, and nothing is written
+    /// without it.
+    pub fn write_rgba(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        mipmaps: Option<&MipmapGenerator>,
+    ) -> Result<(), TextureError> {
+        let expected = (self.width(), self.height());
+        if self.layers() != 1 || rgba.dimensions() != expected {
+            return Err(TextureError::SizeMismatch {
+                expected,
+                found: rgba.dimensions(),
+            });
+        }
+        let has_mips = self.texture.mip_level_count() > 1;
+        if has_mips && mipmaps.is_none() {
+            return Err(TextureError::NoMipmapGenerator);
+        }
+        write_layer(queue, &self.texture, 0, rgba);
+        if let Some(mipmaps) = mipmaps.filter(|_| has_mips) {
+            mipmaps.generate(device, queue, &self.texture);
+        }
+        Ok(())
+    }
+
+    /// Number of layers, 1 unless this is a texture array.
+    pub fn layers(&self) -> u32 {
+        self.texture.depth_or_array_layers()
+    }
+
+    /// Layout for a texture + sampler pair, as sampled by `textured.wgsl`.
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        Self::layout_with_dimension(
+            device,
+            wgpu::TextureViewDimension::D2,
+            "texture_bind_group_layout",
+        )
+    }
+
+    /// Layout for a texture array + sampler pair, as sampled by `textured_array.wgsl`.
+    pub fn array_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        Self::layout_with_dimension(
+            device,
+            wgpu::TextureViewDimension::D2Array,
+            "texture_array_bind_group_layout",
+        )
+    }
+
+    fn layout_with_dimension(
+        device: &wgpu::Device,
+        view_dimension: wgpu::TextureViewDimension,
+        label: &str,
+    ) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // This should match the filterable field of the
+                    // corresponding Texture entry above.
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some(label),
+        })
+    }
+
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("diffuse_bind_group"),
+        })
+    }
+
     pub fn height(&self) -> u32 {
         self.texture.height()
     }
@@ -83,3 +459,165 @@ impl ImageTexture {
         self.texture.width()
     }
 }
+
+/// A depth attachment sized to match a colour target.
+pub struct DepthTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl DepthTexture {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    /// `sample_count` has to match the colour attachment it is used with.
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+
+    /// Attachment that clears to the far plane at the start of the pass.
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }
+    }
+}
+
+/// A multisampled colour attachment that is resolved into a single-sampled texture,
+/// usually the swapchain image, at the end of the pass.
+pub struct MultisampleTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl MultisampleTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Multisample Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+
+    /// Attachment that draws into the multisampled texture and resolves into
+    /// `resolve_target`. The samples themselves are discarded afterwards.
+    pub fn attachment<'a>(
+        &'a self,
+        resolve_target: &'a wgpu::TextureView,
+        clear: wgpu::Color,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.view,
+            resolve_target: Some(resolve_target),
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear),
+                store: wgpu::StoreOp::Discard,
+            },
+        }
+    }
+}
+
+/// Uploads `rgba` as mip level 0 of one layer of `texture`.
+fn write_layer(queue: &wgpu::Queue, texture: &wgpu::Texture, layer: u32, rgba: &image::RgbaImage) {
+    let (width, height) = rgba.dimensions();
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: 0,
+                z: layer,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        rgba,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+/// Multiplies each pixel's colour by its alpha in place.
+///
+/// For sRGB textures the multiply happens in linear space and is encoded back
+/// afterwards. Doing it on the encoded bytes would darken soft edges.
+pub fn premultiply_alpha(rgba: &mut image::RgbaImage, srgb: bool) {
+    for pixel in rgba.pixels_mut() {
+        let alpha = pixel[3] as f32 / 255.0;
+        for channel in &mut pixel.0[..3] {
+            let value = *channel as f32 / 255.0;
+            let premultiplied = if srgb {
+                linear_to_srgb(srgb_to_linear(value) * alpha)
+            } else {
+                value * alpha
+            };
+            *channel = (premultiplied * 255.0).round() as u8;
+        }
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,503 @@
+use std::f32::consts::{PI, TAU};
+
+use crate::lerp::Lerp;
+
+/// Shortest duration a tween is played over, so repeating a zero length tween
+/// can't stall [`Tween::update`].
+const MIN_DURATION: f32 = 0.0001;
+
+/// Easing functions mapping progress from 0 to 1 onto how far along the value
+/// is. See <https://easings.net> for how each one moves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Ease {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    QuartIn,
+    QuartOut,
+    QuartInOut,
+    QuintIn,
+    QuintOut,
+    QuintInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    CircIn,
+    CircOut,
+    CircInOut,
+    /// Pulls back a little before setting off.
+    BackIn,
+    /// Overshoots the end and settles back.
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    /// Springs past the end and wobbles into place.
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    /// Drops onto the end and bounces to rest.
+    BounceOut,
+    BounceInOut,
+}
+
+impl Ease {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        // Runs an ease-in forwards over the first half and mirrored over the second.
+        let in_out = |ease_in: fn(f32) -> f32| {
+            if t < 0.5 {
+                ease_in(t * 2.0) / 2.0
+            } else {
+                1.0 - ease_in(2.0 - t * 2.0) / 2.0
+            }
+        };
+        let out = |ease_in: fn(f32) -> f32| 1.0 - ease_in(1.0 - t);
+        match self {
+            Self::Linear => t,
+            Self::QuadIn => t.powi(2),
+            Self::QuadOut => out(|t| t.powi(2)),
+            Self::QuadInOut => in_out(|t| t.powi(2)),
+            Self::CubicIn => t.powi(3),
+            Self::CubicOut => out(|t| t.powi(3)),
+            Self::CubicInOut => in_out(|t| t.powi(3)),
+            Self::QuartIn => t.powi(4),
+            Self::QuartOut => out(|t| t.powi(4)),
+            Self::QuartInOut => in_out(|t| t.powi(4)),
+            Self::QuintIn => t.powi(5),
+            Self::QuintOut => out(|t| t.powi(5)),
+            Self::QuintInOut => in_out(|t| t.powi(5)),
+            Self::SineIn => sine_in(t),
+            Self::SineOut => out(sine_in),
+            Self::SineInOut => in_out(sine_in),
+            Self::ExpoIn => expo_in(t),
+            Self::ExpoOut => out(expo_in),
+            Self::ExpoInOut => in_out(expo_in),
+            Self::CircIn => circ_in(t),
+            Self::CircOut => out(circ_in),
+            Self::CircInOut => in_out(circ_in),
+            Self::BackIn => back_in(t),
+            Self::BackOut => out(back_in),
+            Self::BackInOut => in_out(back_in),
+            Self::ElasticIn => elastic_in(t),
+            Self::ElasticOut => out(elastic_in),
+            Self::ElasticInOut => in_out(elastic_in),
+            Self::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Self::BounceOut => bounce_out(t),
+            Self::BounceInOut => in_out(|t| 1.0 - bounce_out(1.0 - t)),
+        }
+    }
+}
+
+fn sine_in(t: f32) -> f32 {
+    1.0 - (t * PI / 2.0).cos()
+}
+
+fn expo_in(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else {
+        2f32.powf(10.0 * t - 10.0)
+    }
+}
+
+fn circ_in(t: f32) -> f32 {
+    1.0 - (1.0 - t * t).sqrt()
+}
+
+fn back_in(t: f32) -> f32 {
+    // Overshoots by about 10%.
+    const C1: f32 = 1.70158;
+    (C1 + 1.0) * t.powi(3) - C1 * t.powi(2)
+}
+
+fn elastic_in(t: f32) -> f32 {
+    if t <= 0.0 || t >= 1.0 {
+        return t;
+    }
+    -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * TAU / 3.0).sin()
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// Called with the target when an animation finishes.
+type OnComplete<T> = Box<dyn FnMut(&mut T)>;
+
+/// How many more times a tween plays after the first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Repeat {
+    #[default]
+    Never,
+    Times(u32),
+    Forever,
+}
+
+/// Something that changes a `T` over time, stepped by the game clock.
+pub trait Animate<T> {
+    /// Moves on by `dt` seconds, writing into `target`. Returns whatever part of
+    /// `dt` was left over after finishing, so what follows can start on time.
+    fn update(&mut self, target: &mut T, dt: f32) -> f32;
+
+    fn is_finished(&self) -> bool;
+}
+
+/// Animates one field of a `T` from one value to another.
+///
+/// The field is picked by a function returning it from the target, e.g.
+/// `|camera: &mut Camera| &mut camera.eye`, so one `T` can have many fields
+/// tweened at once. [`Tween::value`] tweens a plain value instead.
+pub struct Tween<T, V> {
+    field: fn(&mut T) -> &mut V,
+    /// Where the tween started from, if given up front.
+    from: Option<V>,
+    /// Where this play started from, taken from the target when not given.
+    start: Option<V>,
+    to: V,
+    duration: f32,
+    ease: Ease,
+    delay: f32,
+    repeat: Repeat,
+    yoyo: bool,
+    on_complete: Option<OnComplete<T>>,
+    /// Time spent waiting or playing so far.
+    elapsed: f32,
+    plays: u32,
+    finished: bool,
+}
+
+impl<T, V: Lerp> Tween<T, V> {
+    /// Moves the field from whatever it holds when the tween starts to `to`
+    /// over `duration` seconds.
+    pub fn new(field: fn(&mut T) -> &mut V, to: V, duration: f32) -> Self {
+        Self {
+            field,
+            from: None,
+            start: None,
+            to,
+            duration,
+            ease: Ease::Linear,
+            delay: 0.0,
+            repeat: Repeat::Never,
+            yoyo: false,
+            on_complete: None,
+            elapsed: 0.0,
+            plays: 0,
+            finished: false,
+        }
+    }
+
+    /// Starts from `from` rather than the field's current value.
+    pub fn from(self, from: V) -> Self {
+        Self {
+            from: Some(from),
+            ..self
+        }
+    }
+
+    pub fn with_ease(self, ease: Ease) -> Self {
+        Self { ease, ..self }
+    }
+
+    /// Waits `delay` seconds before starting.
+    pub fn with_delay(self, delay: f32) -> Self {
+        Self { delay, ..self }
+    }
+
+    pub fn with_repeat(self, repeat: Repeat) -> Self {
+        Self { repeat, ..self }
+    }
+
+    /// Plays every repeat in the opposite direction to the one before.
+    pub fn with_yoyo(self) -> Self {
+        Self { yoyo: true, ..self }
+    }
+
+    /// Calls `on_complete` with the target once the last repeat has played.
+    pub fn on_complete(self, on_complete: impl FnMut(&mut T) + 'static) -> Self {
+        Self {
+            on_complete: Some(Box::new(on_complete)),
+            ..self
+        }
+    }
+
+    /// Progress through the current play, from 0 to 1, before easing.
+    fn progress(&self) -> f32 {
+        let progress =
+            ((self.elapsed - self.delay) / self.duration.max(MIN_DURATION)).clamp(0.0, 1.0);
+        if self.yoyo && self.plays % 2 == 1 {
+            1.0 - progress
+        } else {
+            progress
+        }
+    }
+
+    fn plays_left(&self) -> bool {
+        match self.repeat {
+            Repeat::Never => false,
+            Repeat::Times(times) => self.plays < times,
+            Repeat::Forever => true,
+        }
+    }
+}
+
+impl<V: Lerp> Tween<V, V> {
+    /// Tweens a value that is the whole target, e.g. an `f32` held by the caller.
+    pub fn value(to: V, duration: f32) -> Self {
+        Self::new(|value| value, to, duration)
+    }
+}
+
+impl<T, V: Lerp> Animate<T> for Tween<T, V> {
+    fn update(&mut self, target: &mut T, dt: f32) -> f32 {
+        if self.finished {
+            return dt;
+        }
+        self.elapsed += dt;
+        if self.elapsed < self.delay {
+            return 0.0;
+        }
+
+        let field = (self.field)(target);
+        let start = *self.start.get_or_insert(self.from.unwrap_or(*field));
+        let duration = self.duration.max(MIN_DURATION);
+        let mut left_over = 0.0;
+        while self.elapsed - self.delay >= duration {
+            if !self.plays_left() {
+                left_over = self.elapsed - self.delay - duration;
+                self.elapsed = self.delay + duration;
+                self.finished = true;
+                break;
+            }
+            self.elapsed -= duration;
+            self.plays += 1;
+        }
+        *field = start.lerp(self.to, self.ease.apply(self.progress()));
+
+        if self.finished {
+            if let Some(on_complete) = &mut self.on_complete {
+                on_complete(target);
+            }
+        }
+        left_over
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+/// Does nothing for a while, e.g. to space out the steps of a [`Sequence`].
+pub struct Wait {
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Wait {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl<T> Animate<T> for Wait {
+    fn update(&mut self, _target: &mut T, dt: f32) -> f32 {
+        self.elapsed += dt;
+        (self.elapsed - self.duration).max(0.0)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Plays animations one after another. Tweens without a starting value pick up
+/// from wherever the steps before them left the field.
+pub struct Sequence<T> {
+    steps: Vec<Box<dyn Animate<T>>>,
+    current: usize,
+    on_complete: Option<OnComplete<T>>,
+}
+
+impl<T> Default for Sequence<T> {
+    fn default() -> Self {
+        Self {
+            steps: Vec::new(),
+            current: 0,
+            on_complete: None,
+        }
+    }
+}
+
+impl<T> Sequence<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, step: impl Animate<T> + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Waits `duration` seconds before the next step.
+    pub fn wait(self, duration: f32) -> Self
+    where
+        T: 'static,
+    {
+        self.then(Wait::new(duration))
+    }
+
+    /// Calls `on_complete` with the target once the last step is done.
+    pub fn on_complete(self, on_complete: impl FnMut(&mut T) + 'static) -> Self {
+        Self {
+            on_complete: Some(Box::new(on_complete)),
+            ..self
+        }
+    }
+}
+
+impl<T> Animate<T> for Sequence<T> {
+    fn update(&mut self, target: &mut T, mut dt: f32) -> f32 {
+        if self.is_finished() {
+            return dt;
+        }
+        while let Some(step) = self.steps.get_mut(self.current) {
+            dt = step.update(target, dt);
+            if !step.is_finished() {
+                return 0.0;
+            }
+            self.current += 1;
+        }
+        if let Some(on_complete) = &mut self.on_complete {
+            on_complete(target);
+        }
+        dt
+    }
+
+    fn is_finished(&self) -> bool {
+        self.current >= self.steps.len()
+    }
+}
+
+/// Plays animations all at once, finishing with the longest.
+pub struct Parallel<T> {
+    tracks: Vec<Box<dyn Animate<T>>>,
+    on_complete: Option<OnComplete<T>>,
+    finished: bool,
+}
+
+impl<T> Default for Parallel<T> {
+    fn default() -> Self {
+        Self {
+            tracks: Vec::new(),
+            on_complete: None,
+            finished: false,
+        }
+    }
+}
+
+impl<T> Parallel<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, track: impl Animate<T> + 'static) -> Self {
+        self.tracks.push(Box::new(track));
+        self
+    }
+
+    /// Calls `on_complete` with the target once every track is done.
+    pub fn on_complete(self, on_complete: impl FnMut(&mut T) + 'static) -> Self {
+        Self {
+            on_complete: Some(Box::new(on_complete)),
+            ..self
+        }
+    }
+}
+
+impl<T> Animate<T> for Parallel<T> {
+    fn update(&mut self, target: &mut T, dt: f32) -> f32 {
+        if self.finished {
+            return dt;
+        }
+        let left_over = self
+            .tracks
+            .iter_mut()
+            .map(|track| track.update(target, dt))
+            .fold(dt, f32::min);
+        if !self.tracks.iter().all(|track| track.is_finished()) {
+            return 0.0;
+        }
+        self.finished = true;
+        if let Some(on_complete) = &mut self.on_complete {
+            on_complete(target);
+        }
+        left_over
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+/// Runs any number of independent animations on one target, dropping each as it
+/// finishes. Keep one next to whatever is animated, e.g. the camera.
+pub struct Tweens<T> {
+    active: Vec<Box<dyn Animate<T>>>,
+}
+
+impl<T> Default for Tweens<T> {
+    fn default() -> Self {
+        Self { active: Vec::new() }
+    }
+}
+
+impl<T> Tweens<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, animation: impl Animate<T> + 'static) {
+        self.active.push(Box::new(animation));
+    }
+
+    /// Stops every animation, leaving the target as it is.
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Steps every animation by `dt` seconds, in the order they were added.
+    pub fn update(&mut self, target: &mut T, dt: f32) {
+        for animation in &mut self.active {
+            animation.update(target, dt);
+        }
+        self.active.retain(|animation| !animation.is_finished());
+    }
+}
@@ -1,22 +1,174 @@
 use std::mem;
 
-pub trait Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 2];
-    fn description() -> wgpu::VertexBufferLayout<'static>;
+/// A type that can be uploaded as one vertex buffer element.
+///
+/// Implement this through the [`vertex!`](crate::vertex!) macro rather than by hand so
+/// the attribute list always matches the struct's fields.
+pub trait Vertex: bytemuck::Pod {
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+
+    fn description() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Shader location of the first attribute of an [`Instance`]. Mesh vertices use the
+/// locations below it, so any vertex type can be drawn with any instance type.
+pub const FIRST_INSTANCE_LOCATION: u32 = 8;
+
+/// A type uploaded as one element of a per-instance vertex buffer, read once for
+/// every copy of a mesh in an instanced draw.
+///
+/// Implement this through the [`instance!`](crate::instance!) macro, which numbers
+/// the attributes from [`FIRST_INSTANCE_LOCATION`].
+pub trait Instance: bytemuck::Pod {
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+
+    fn description() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Maps a Rust field type onto the `wgpu::VertexFormat` it is uploaded as.
+pub trait AsVertexFormat {
+    const FORMAT: wgpu::VertexFormat;
+}
+
+macro_rules! as_vertex_format {
+    ($($ty:ty => $format:ident),* $(,)?) => {
+        $(
+            impl AsVertexFormat for $ty {
+                const FORMAT: wgpu::VertexFormat = wgpu::VertexFormat::$format;
+            }
+        )*
+    };
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct ColoredVertex {
-    position: [f32; 3],
-    color: [f32; 4],
+as_vertex_format! {
+    f32 => Float32,
+    [f32; 2] => Float32x2,
+    [f32; 3] => Float32x3,
+    [f32; 4] => Float32x4,
+    u32 => Uint32,
+    [u32; 2] => Uint32x2,
+    [u32; 3] => Uint32x3,
+    [u32; 4] => Uint32x4,
+    i32 => Sint32,
+    [i32; 2] => Sint32x2,
+    [i32; 3] => Sint32x3,
+    [i32; 4] => Sint32x4,
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct TexturedVertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2],
+/// Declares a `#[repr(C)]` vertex struct and implements [`Vertex`] for it.
+///
+/// Each field becomes one attribute. Shader locations are assigned in declaration
+/// order starting at 0 and the format is taken from the field's type.
+#[macro_export]
+macro_rules! vertex {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $crate::vertex! {
+            @impl $crate::vertex::Vertex, 0,
+            $(#[$meta])*
+            $vis struct $name {
+                $($field_vis $field: $ty),*
+            }
+        }
+    };
+    (
+        @impl $trait:path, $first_location:expr,
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $ty:ty),*
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        impl $trait for $name {
+            const ATTRIBUTES: &'static [wgpu::VertexAttribute] = &{
+                const COUNT: usize = [$(stringify!($field)),*].len();
+                let formats = [$(<$ty as $crate::vertex::AsVertexFormat>::FORMAT),*];
+                let offsets = [$(::std::mem::offset_of!($name, $field) as wgpu::BufferAddress),*];
+                let mut attributes = [wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32,
+                    offset: 0,
+                    shader_location: 0,
+                }; COUNT];
+                let mut i = 0;
+                while i < COUNT {
+                    attributes[i] = wgpu::VertexAttribute {
+                        format: formats[i],
+                        offset: offsets[i],
+                        shader_location: $first_location + i as u32,
+                    };
+                    i += 1;
+                }
+                attributes
+            };
+        }
+    };
+}
+
+/// Declares a `#[repr(C)]` per-instance struct and implements [`Instance`] for it.
+///
+/// Works like [`vertex!`](crate::vertex!) except that shader locations start at
+/// [`FIRST_INSTANCE_LOCATION`].
+#[macro_export]
+macro_rules! instance {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $crate::vertex! {
+            @impl $crate::vertex::Instance, $crate::vertex::FIRST_INSTANCE_LOCATION,
+            $(#[$meta])*
+            $vis struct $name {
+                $($field_vis $field: $ty),*
+            }
+        }
+    };
+}
+
+vertex! {
+    pub struct ColoredVertex {
+        position: [f32; 3],
+        color: [f32; 4],
+    }
+}
+
+vertex! {
+    pub struct TexturedVertex {
+        position: [f32; 3],
+        tex_coords: [f32; 2],
+    }
+}
+
+vertex! {
+    /// A textured vertex that also selects which layer of a texture array to sample.
+    pub struct ArrayTexturedVertex {
+        position: [f32; 3],
+        tex_coords: [f32; 2],
+        layer: u32,
+    }
 }
 
 impl TexturedVertex {
@@ -26,6 +178,20 @@ impl TexturedVertex {
             tex_coords,
         }
     }
+
+    pub fn with_layer(self, layer: u32) -> ArrayTexturedVertex {
+        ArrayTexturedVertex::new(self.position, self.tex_coords, layer)
+    }
+}
+
+impl ArrayTexturedVertex {
+    pub fn new(position: [f32; 3], tex_coords: [f32; 2], layer: u32) -> Self {
+        Self {
+            position,
+            tex_coords,
+            layer,
+        }
+    }
 }
 
 impl ColoredVertex {
@@ -34,48 +200,64 @@ impl ColoredVertex {
     }
 }
 
-impl Vertex for ColoredVertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+/// A vertex whose position can be moved, e.g. into world space by the
+/// [scene graph](crate::scene::Scene).
+pub trait Positioned: Vertex {
+    fn position(&self) -> [f32; 3];
+    fn with_position(self, position: [f32; 3]) -> Self;
+}
 
-    fn description() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBS,
-        }
+impl Positioned for ColoredVertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn with_position(self, position: [f32; 3]) -> Self {
+        Self { position, ..self }
     }
 }
 
-impl Vertex for TexturedVertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 2] = [
-        wgpu::VertexAttribute {
-            offset: 0,
-            shader_location: 0,
-            format: wgpu::VertexFormat::Float32x3,
-        },
-        wgpu::VertexAttribute {
-            offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-            shader_location: 1,
-            format: wgpu::VertexFormat::Float32x2,
-        },
-    ];
+impl Positioned for TexturedVertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
 
-    fn description() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBS,
-        }
+    fn with_position(self, position: [f32; 3]) -> Self {
+        Self { position, ..self }
+    }
+}
+
+impl Positioned for ArrayTexturedVertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn with_position(self, position: [f32; 3]) -> Self {
+        Self { position, ..self }
     }
 }
 
 pub const VERTICES: &[TexturedVertex] = &[
-    TexturedVertex { position: [-0.0868241, 0.49240386, 0.0], tex_coords: [0.4131759, 0.00759614], }, // A
-    TexturedVertex { position: [-0.49513406, 0.06958647, 0.0], tex_coords: [0.0048659444, 0.43041354], }, // B
-    TexturedVertex { position: [-0.21918549, -0.44939706, 0.0], tex_coords: [0.28081453, 0.949397], }, // C
-    TexturedVertex { position: [0.35966998, -0.3473291, 0.0], tex_coords: [0.85967, 0.84732914], }, // D
-    TexturedVertex { position: [0.44147372, 0.2347359, 0.0], tex_coords: [0.9414737, 0.2652641], }, // E
+    TexturedVertex {
+        position: [-0.0868241, 0.49240386, 0.0],
+        tex_coords: [0.4131759, 0.00759614],
+    }, // A
+    TexturedVertex {
+        position: [-0.49513406, 0.06958647, 0.0],
+        tex_coords: [0.0048659444, 0.43041354],
+    }, // B
+    TexturedVertex {
+        position: [-0.21918549, -0.44939706, 0.0],
+        tex_coords: [0.28081453, 0.949397],
+    }, // C
+    TexturedVertex {
+        position: [0.35966998, -0.3473291, 0.0],
+        tex_coords: [0.85967, 0.84732914],
+    }, // D
+    TexturedVertex {
+        position: [0.44147372, 0.2347359, 0.0],
+        tex_coords: [0.9414737, 0.2652641],
+    }, // E
 ];
 // pub const VERTICES: &[ColoredTexturedVertex] = &[
 //     ColoredVertex {